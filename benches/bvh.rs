@@ -0,0 +1,64 @@
+//! Compares the linear object scan against the BVH on the random spheres
+//! scene. Run with `cargo bench --bench bvh`.
+
+use std::time::Instant;
+
+use ray_tracer::{
+    ray::ray::Ray,
+    sampler::sampler::{Sampler, SamplerKind},
+    scene::random_spheres::{random_spheres_camera, random_spheres_scene},
+    utils::{constants::INFINITY, interval::Interval},
+    world::{hit_record::HitRecord, scene_object_list::SceneObjectList},
+};
+
+const RAY_COUNT: usize = 2_000_000;
+
+fn trace(rays: &[Ray], hit: impl Fn(&Ray, Interval, &mut HitRecord) -> bool) -> (f64, usize) {
+    let mut rec = HitRecord::default();
+    let start = Instant::now();
+    let hits = rays
+        .iter()
+        .filter(|ray| hit(ray, Interval::new(0.001, INFINITY), &mut rec))
+        .count();
+    (start.elapsed().as_secs_f64(), hits)
+}
+
+fn main() {
+    let mut world: SceneObjectList = random_spheres_scene(&mut rand::thread_rng());
+    let cam = random_spheres_camera();
+    let mut sampler = Sampler::new(SamplerKind::Independent, 1);
+
+    let rays: Vec<Ray> = (0..RAY_COUNT)
+        .map(|i| {
+            let x = (i as u32) % cam.image_width;
+            let y = (i as u32 / cam.image_width) % cam.image_height;
+            cam.get_ray(x, y, &mut sampler)
+        })
+        .collect();
+
+    let build_start = Instant::now();
+    world.build_bvh();
+    let build_time = build_start.elapsed().as_secs_f64();
+
+    let (linear_time, linear_hits) = trace(&rays, |r, t, rec| world.hit_linear(r, t, rec));
+    let (bvh_time, bvh_hits) = trace(&rays, |r, t, rec| world.hit(r, t, rec));
+    assert_eq!(linear_hits, bvh_hits, "BVH and linear scan disagree");
+
+    println!(
+        "{} objects, {} BVH nodes, built in {:.2} ms",
+        world.objects.len(),
+        world.bvh().map_or(0, |bvh| bvh.node_count()),
+        build_time * 1e3
+    );
+    println!(
+        "linear: {:>8.3} s  ({:>6.2} Mrays/s)",
+        linear_time,
+        RAY_COUNT as f64 / linear_time / 1e6
+    );
+    println!(
+        "bvh:    {:>8.3} s  ({:>6.2} Mrays/s)",
+        bvh_time,
+        RAY_COUNT as f64 / bvh_time / 1e6
+    );
+    println!("speedup: {:.1}x", linear_time / bvh_time);
+}
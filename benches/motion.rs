@@ -0,0 +1,80 @@
+//! Compares hitting a sphere placed by a fixed transform against the same
+//! sphere under a keyframed one, which is interpolated for every hit test.
+//! Run with `cargo bench --bench motion`.
+
+use std::{sync::Arc, time::Instant};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use ray_tracer::{
+    ray::ray::Ray,
+    utils::{constants::INFINITY, interval::Interval},
+    vec3::{
+        transform::{AnimatedTransform, Keyframe, Quaternion},
+        vec3::{Point3, Vec3},
+    },
+    world::{hit_record::HitRecord, material::Material, scene_object::SceneObject},
+};
+
+const RAY_COUNT: usize = 5_000_000;
+
+fn trace(object: &SceneObject, rays: &[Ray]) -> (f64, usize) {
+    let mut rec = HitRecord::default();
+    let start = Instant::now();
+    let hits = rays
+        .iter()
+        .filter(|ray| object.hit(ray, Interval::new(0.001, INFINITY), &mut rec))
+        .count();
+    (start.elapsed().as_secs_f64(), hits)
+}
+
+fn main() {
+    let sphere = Arc::new(SceneObject::Sphere {
+        radius: 1.0,
+        center: Point3::default(),
+        material: Arc::new(Material::default()),
+    });
+    let keyframe = |time, degrees, x| Keyframe {
+        time,
+        scale: Vec3::new(1.0, 2.0, 1.0),
+        rotation: Quaternion::from_axis_angle(Vec3::new(0.0, 0.0, 1.0), degrees),
+        translate: Vec3::new(x, 0.0, 0.0),
+    };
+    let motion = AnimatedTransform::new(vec![
+        keyframe(0.0, 0.0, -1.0),
+        keyframe(0.5, 60.0, 0.0),
+        keyframe(1.0, 120.0, 1.0),
+    ]);
+    let fixed = SceneObject::Instance {
+        object: Arc::clone(&sphere),
+        transform: motion.at(0.5),
+    };
+    let moving = SceneObject::MovingInstance {
+        object: sphere,
+        motion,
+    };
+
+    // Rays from a square in front of the sphere, most of which hit it
+    let mut rng = StdRng::seed_from_u64(1);
+    let rays: Vec<Ray> = (0..RAY_COUNT)
+        .map(|_| {
+            let origin = Point3::new(rng.gen_range(-3.0..3.0), rng.gen_range(-3.0..3.0), 5.0);
+            Ray::new(origin, Vec3::new(0.0, 0.0, -1.0)).with_time(rng.gen())
+        })
+        .collect();
+
+    let (fixed_time, fixed_hits) = trace(&fixed, &rays);
+    let (moving_time, moving_hits) = trace(&moving, &rays);
+    println!(
+        "fixed:  {:>8.3} s  ({:>6.2} Mrays/s, {} hits)",
+        fixed_time,
+        RAY_COUNT as f64 / fixed_time / 1e6,
+        fixed_hits
+    );
+    println!(
+        "moving: {:>8.3} s  ({:>6.2} Mrays/s, {} hits)",
+        moving_time,
+        RAY_COUNT as f64 / moving_time / 1e6,
+        moving_hits
+    );
+    println!("slowdown: {:.2}x", moving_time / fixed_time);
+}
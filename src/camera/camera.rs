@@ -1,20 +1,143 @@
-use std::io::{self};
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
 
-use crate::utils::helpers::degress_to_radians;
+use crate::output::tonemap::{luminance, DisplayTransform, ToneMapper};
+use crate::render::{film::FilmTile, filter::Filter, stats::RayStats};
+use crate::sampler::sampler::{Sampler, SamplerKind};
+use crate::utils::helpers::{degress_to_radians, derive_seed, seed_random};
 use crate::world::hit_record::HitRecord;
+use crate::world::material::{Material, ScatterRecord};
+use crate::world::scene_object::free_flight_distance;
 use crate::{
     ray::ray::Ray,
-    utils::{constants::INFINITY, helpers::random_f32, interval::Interval},
+    utils::{constants::INFINITY, interval::Interval},
 };
 use crate::{
     vec3::{
-        color3::{write_color, Color3},
+        color3::Color3,
         vec3::{Point3, Vec3},
     },
     world::scene_object_list::SceneObjectList,
 };
 
+/// What a ray that leaves the scene sees.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Background {
+    /// White-to-blue vertical gradient, the only light source of the book scenes.
+    Sky,
+    /// Uniform color; black turns the background off for scenes lit by emitters.
+    Color(Color3),
+}
+
+impl Background {
+    #[inline]
+    pub fn color(&self, direction: Vec3) -> Color3 {
+        match *self {
+            Self::Sky => {
+                let unit_vector = Vec3::unit(direction);
+                let a = 0.5 * (unit_vector.y + 1.0);
+                (1.0 - a) * Color3::new(1.0, 1.0, 1.0) + a * Color3::new(0.5, 0.7, 1.0)
+            }
+            Self::Color(color) => color,
+        }
+    }
+}
+
+/// Homogeneous medium filling the space between objects within `radius`
+/// of `center`, such as haze or fog. Rays that miss every object still
+/// cross it on their way out to the background, which fades just like the
+/// objects do.
+#[derive(Debug, Clone)]
+pub struct Atmosphere {
+    pub density: f32,
+    /// Phase function of the medium's particles.
+    pub phase: Arc<Material>,
+    pub center: Point3,
+    pub radius: f32,
+}
+
+impl Atmosphere {
+    /// Part of `ray_t` a ray spends within the medium.
+    pub fn span(&self, ray: &Ray, ray_t: Interval) -> Option<Interval> {
+        let oc = self.center - ray.origin();
+        let a = ray.direction().length_squared();
+        let h = Vec3::dot_product(ray.direction(), oc);
+        let c = oc.length_squared() - self.radius * self.radius;
+        let discriminant = h * h - a * c;
+        if discriminant < 0.0 {
+            return None;
+        }
+        let sqrtd = discriminant.sqrt();
+        let span = Interval::new(
+            ((h - sqrtd) / a).max(ray_t.min),
+            ((h + sqrtd) / a).min(ray_t.max),
+        );
+        (span.min < span.max).then_some(span)
+    }
+
+    /// Fraction of the light travelling along `ray` over `ray_t` that the
+    /// medium lets through unscattered.
+    pub fn transmittance(&self, ray: &Ray, ray_t: Interval) -> f32 {
+        self.span(ray, ray_t).map_or(1.0, |span| {
+            (-self.density * (span.max - span.min) * ray.direction().length()).exp()
+        })
+    }
+}
+
+/// How paths pick up light from emissive objects.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LightSampling {
+    /// Only follow the rays scattered by materials; lights are found by chance.
+    Bsdf,
+    /// Also cast shadow rays towards sampled lights (next-event estimation),
+    /// weighting both strategies with the balance heuristic.
+    Balance,
+    /// Next-event estimation weighted with the power heuristic (beta = 2).
+    Power,
+}
+
+impl LightSampling {
+    /// Weight of a sample drawn with density `pdf` when the other strategy
+    /// would have drawn it with `other_pdf`.
+    #[inline]
+    fn mis_weight(&self, pdf: f32, other_pdf: f32) -> f32 {
+        match self {
+            Self::Bsdf => 1.0,
+            Self::Balance => pdf / (pdf + other_pdf),
+            Self::Power => (pdf * pdf) / (pdf * pdf + other_pdf * other_pdf),
+        }
+    }
+}
+
+/// Stops sampling a pixel early once its estimate is good enough, leaving
+/// `samples_per_pixel` as the upper bound.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AdaptiveSampling {
+    /// Samples every pixel takes before it may stop.
+    pub min_samples: u32,
+    /// Largest accepted error of the pixel's luminance, relative to the
+    /// luminance itself, at 95% confidence.
+    pub threshold: f32,
+}
+
+impl AdaptiveSampling {
+    /// Relative errors of very dark pixels are measured against this
+    /// luminance instead, or they would never converge.
+    const MIN_LUMINANCE: f32 = 0.01;
+
+    /// Whether a pixel with `count` samples of luminance mean `mean` and sum
+    /// of squared deviations `m2` can stop.
+    #[inline]
+    fn converged(&self, count: u32, mean: f32, m2: f32) -> bool {
+        if count < self.min_samples.max(2) {
+            return false;
+        }
+        let n = count as f32;
+        let variance = m2 / (n - 1.0);
+        let error = 1.96 * (variance / n).sqrt();
+        error <= self.threshold * mean.max(Self::MIN_LUMINANCE)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Camera {
     pub image_width: u32,
@@ -27,22 +150,33 @@ pub struct Camera {
     pub upvector: Vec3,
     pub defocus_angle: f32,
     pub focus_dist: f32,
+    pub background: Background,
+    pub light_sampling: LightSampling,
+    pub tone_mapper: ToneMapper,
+    /// Exposure adjustment in stops.
+    pub exposure: f32,
+    /// Display gamma, or 0 for the sRGB transfer function.
+    pub gamma_correction: f32,
+    pub adaptive_sampling: Option<AdaptiveSampling>,
+    pub sampler: SamplerKind,
+    pub filter: Filter,
+    /// Times at which the shutter opens and closes; rays are spread
+    /// uniformly between them. Equal times render a single instant.
+    pub shutter_open: f32,
+    pub shutter_close: f32,
+    pub atmosphere: Option<Atmosphere>,
 
     pub image_height: u32,
     pixel_delta_u: Vec3,
     pixel_delta_v: Vec3,
     pixel00_origin: Point3,
     center: Point3,
-    pixel_sample_scale: f32,
-    gamma_correction: f32,
-    u: Vec3,
-    v: Vec3,
-    w: Vec3,
     defocus_disk_u: Vec3,
     defocus_disk_v: Vec3,
 }
 
 impl Camera {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         image_width: u32,
         aspect_ratio: f32,
@@ -55,8 +189,6 @@ impl Camera {
         defocus_angle: f32,
         focus_dist: f32,
     ) -> Self {
-        let pixel_sample_scale = 1.0 / (samples_per_pixel as f32);
-
         let mut image_height = (image_width as f32 / aspect_ratio) as u32;
         if image_height < 1 {
             image_height = 1;
@@ -77,7 +209,7 @@ impl Camera {
         let v = Vec3::cross_product(w, u);
 
         let viewport_u = viewport_width * u;
-        let viewport_v = viewport_height * -1.0 * v;
+        let viewport_v = -(viewport_height * v);
 
         let pixel_delta_u = viewport_u / image_width as f32;
         let pixel_delta_v = viewport_v / image_height as f32;
@@ -101,132 +233,518 @@ impl Camera {
             upvector,
             defocus_angle,
             focus_dist,
+            background: Background::Sky,
+            light_sampling: LightSampling::Power,
+            tone_mapper: ToneMapper::Clamp,
+            exposure: 0.0,
+            gamma_correction: 0.0,
+            adaptive_sampling: None,
+            sampler: SamplerKind::Independent,
+            filter: Filter::default(),
+            shutter_open: 0.0,
+            shutter_close: 0.0,
+            atmosphere: None,
             image_height,
             pixel_delta_u,
             pixel_delta_v,
             pixel00_origin,
             center,
-            pixel_sample_scale,
-            gamma_correction: 0.0, // Initialize with default value
-            u,
-            v,
-            w,
             defocus_disk_u,
             defocus_disk_v,
         }
     }
 
-    pub fn render(
+    pub fn with_background(mut self, background: Background) -> Self {
+        self.background = background;
+        self
+    }
+
+    pub fn with_light_sampling(mut self, light_sampling: LightSampling) -> Self {
+        self.light_sampling = light_sampling;
+        self
+    }
+
+    pub fn with_tone_mapping(mut self, tone_mapper: ToneMapper, exposure: f32) -> Self {
+        self.tone_mapper = tone_mapper;
+        self.exposure = exposure;
+        self
+    }
+
+    pub fn with_gamma_correction(mut self, gamma_correction: f32) -> Self {
+        self.gamma_correction = gamma_correction;
+        self
+    }
+
+    pub fn with_sampler(mut self, sampler: SamplerKind) -> Self {
+        self.sampler = sampler;
+        self
+    }
+
+    pub fn with_filter(mut self, filter: Filter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    pub fn with_shutter(mut self, shutter_open: f32, shutter_close: f32) -> Self {
+        self.shutter_open = shutter_open;
+        self.shutter_close = shutter_close;
+        self
+    }
+
+    pub fn with_atmosphere(mut self, atmosphere: Option<Atmosphere>) -> Self {
+        self.atmosphere = atmosphere;
+        self
+    }
+
+    pub fn with_adaptive_sampling(mut self, adaptive_sampling: Option<AdaptiveSampling>) -> Self {
+        self.adaptive_sampling = adaptive_sampling;
+        self
+    }
+
+    /// How the rendered radiance is turned into display values.
+    pub fn display_transform(&self) -> DisplayTransform {
+        DisplayTransform {
+            tone_mapper: self.tone_mapper,
+            exposure: self.exposure,
+            gamma: self.gamma_correction,
+        }
+    }
+
+    /// Renders the pixels of `film_tile`'s tile up to `sample_end` samples
+    /// each, splatting every sample into it through the reconstruction
+    /// filter. Pixels continue from the samples they already took, so a
+    /// render can be split into passes.
+    ///
+    /// Every sample reseeds the random generator from `seed`, its pixel and
+    /// its index, so the result doesn't depend on which thread renders the
+    /// tile, what it rendered before, or how the samples are split into
+    /// passes.
+    pub fn render_tile(
         &self,
-        world: Arc<SceneObjectList>,
-        x_range: (usize, usize),
-        y_range: (usize, usize),
-        pixels: &mut Vec<Color3>,
+        world: &SceneObjectList,
+        film_tile: &mut FilmTile,
+        seed: u64,
+        sample_end: u32,
+        stats: &mut RayStats,
     ) {
-        let total_pixels = (y_range.1 - y_range.0) * (x_range.1 - x_range.0);
-        //let batch_size = total_pixels / 3;
-        //let mut completed_pixels = 0;
-        let thread_id = std::thread::current().id();
-
-        println!(
-            "Thread {:?} starting: {} pixels to render",
-            thread_id, total_pixels
-        );
-        for y in y_range.0..y_range.1 {
-            for x in x_range.0..x_range.1 {
-                let mut pixel_color = Color3::new(0.0, 0.0, 0.0);
+        let tile = film_tile.tile;
+        let sample_end = sample_end.min(self.samples_per_pixel);
+        let mut sampler = Sampler::new(self.sampler, self.samples_per_pixel);
+        for y in tile.y0..tile.y1 {
+            for x in tile.x0..tile.x1 {
+                let pixel_seed = derive_seed(seed, &[x as u64, y as u64]);
+                let mut samples = *film_tile.samples_mut(x, y);
 
+                while samples.count < sample_end && !samples.converged {
+                    let index = samples.count;
+                    seed_random(derive_seed(seed, &[x as u64, y as u64, index as u64]));
+                    sampler.start_sample(pixel_seed, index);
+                    let (dx, dy) = sampler.get_2d();
+                    let (film_x, film_y) = (x as f32 + dx, y as f32 + dy);
+                    let ray = self.ray_through(film_x, film_y, &mut sampler);
+                    let sample = self.ray_color(&ray, self.max_depth, world, &mut sampler, stats);
+                    // Display encoding happens on output
+                    film_tile.add_sample(film_x, film_y, sample);
+                    samples.count += 1;
 
-                for _ in 0..self.samples_per_pixel {
-                    let ray = self.get_ray(x as u32, y as u32);
-                    pixel_color += self.ray_color(&ray, self.max_depth, &world);
+                    if let Some(adaptive) = &self.adaptive_sampling {
+                        let l = luminance(sample);
+                        let delta = l - samples.mean;
+                        samples.mean += delta / samples.count as f32;
+                        samples.m2 += delta * (l - samples.mean);
+                        samples.converged =
+                            adaptive.converged(samples.count, samples.mean, samples.m2);
+                    }
                 }
-
-                pixel_color *= self.pixel_sample_scale;
-                let gamma_corrected_color = Color3::new(
-                    pixel_color.x.sqrt(),
-                    pixel_color.y.sqrt(),
-                    pixel_color.z.sqrt(),
-                );
-
-                let local_y = y - y_range.0; 
-                let index = local_y * self.image_width as usize + x;
-                pixels[index] = gamma_corrected_color;
-
-                //completed_pixels += 1;
-                
-                /* 
-                if completed_pixels % batch_size == 0 || completed_pixels == total_pixels {
-                    let progress = (completed_pixels as f32 / total_pixels as f32) * 100.0;
-
-                    println!(
-                        "Thread {:?}: [{:>3.1}%] {:>6}/{:<6} pixels",
-                        thread_id, progress, completed_pixels, total_pixels,
-                    );
-                }*/
+                *film_tile.samples_mut(x, y) = samples;
             }
         }
     }
 
+    /// Camera ray through a point of pixel (`i`, `j`), with the pixel and
+    /// lens positions drawn from `sampler`.
+    #[inline]
+    pub fn get_ray(&self, i: u32, j: u32, sampler: &mut Sampler) -> Ray {
+        let (dx, dy) = sampler.get_2d();
+        self.ray_through(i as f32 + dx, j as f32 + dy, sampler)
+    }
+
+    /// Camera ray through film position (`x`, `y`), in pixels from the
+    /// image's top left corner, with the lens position and the time within
+    /// the shutter interval drawn from `sampler`.
     #[inline]
-    fn get_ray(&self, i: u32, j: u32) -> Ray {
-        let offset = Vec3::new(random_f32() - 0.5, random_f32() - 0.5, 0.0);
-        let pixel_sample = self.pixel00_origin
-            + (i as f32 + offset.x) * self.pixel_delta_u
-            + (j as f32 + offset.y) * self.pixel_delta_v;
+    fn ray_through(&self, x: f32, y: f32, sampler: &mut Sampler) -> Ray {
+        // `pixel00_origin` is the center of the first pixel
+        let pixel_sample =
+            self.pixel00_origin + (x - 0.5) * self.pixel_delta_u + (y - 0.5) * self.pixel_delta_v;
         let origin = if self.defocus_angle > 0.0 {
-            self.defocus_disk_sample()
+            let (u1, u2) = sampler.get_2d();
+            self.defocus_disk_sample(u1, u2)
         } else {
             self.center
         };
         let direction = pixel_sample - origin;
+        // Only drawn while the shutter is open for some time, so still
+        // renders keep their sample sequences
+        let time = if self.shutter_close > self.shutter_open {
+            self.shutter_open + sampler.get_1d() * (self.shutter_close - self.shutter_open)
+        } else {
+            self.shutter_open
+        };
 
-        Ray::new(origin, direction)
+        Ray::new(origin, direction).with_time(time)
     }
 
     #[inline]
-    fn defocus_disk_sample(&self) -> Point3 {
-        let p = Vec3::random_in_unit_disk();
+    fn defocus_disk_sample(&self, u1: f32, u2: f32) -> Point3 {
+        let p = Vec3::sample_unit_disk(u1, u2);
         self.center + (p.x * self.defocus_disk_u) + (p.y * self.defocus_disk_v)
     }
 
-    fn ray_color(&self, initial_ray: &Ray, depth: u32, world: &SceneObjectList) -> Color3 {
+    fn ray_color(
+        &self,
+        initial_ray: &Ray,
+        depth: u32,
+        world: &SceneObjectList,
+        sampler: &mut Sampler,
+        stats: &mut RayStats,
+    ) -> Color3 {
         let mut ray_origin = initial_ray.origin();
         let mut ray_direction = initial_ray.direction();
-        let mut color = Color3::new(1.0, 1.0, 1.0);
+        // Every bounce of a path sees the scene at the same instant
+        let time = initial_ray.time();
+        let mut throughput = Color3::new(1.0, 1.0, 1.0);
+        let mut radiance = Color3::new(0.0, 0.0, 0.0);
+
+        let sample_lights = self.light_sampling != LightSampling::Bsdf && world.has_lights();
+        // Camera rays and specular bounces cannot be matched by light
+        // sampling, so emission they hit counts fully.
+        let mut previous_specular = true;
+        let mut previous_pdf = 0.0;
 
         let mut hit_rec: HitRecord = HitRecord::default();
-        let mut scattered = Ray::default();
-        let mut attenuation = Color3::default();
+        let mut shadow_rec: HitRecord = HitRecord::default();
+        let mut srec = ScatterRecord::default();
 
+        stats.camera_rays += 1;
         for _ in 0..depth {
-            let current_ray = Ray::new(ray_origin, ray_direction);
-
-            if world.hit(&current_ray, Interval::new(0.001, INFINITY), &mut hit_rec) {
-                if hit_rec.material.scatter(
-                    &current_ray,
-                    &hit_rec,
-                    &mut attenuation,
-                    &mut scattered,
-                ) {
-                    color = color * attenuation;
-                    // Update ray parameters for next iteration
-                    ray_origin = scattered.origin();
-                    ray_direction = scattered.direction();
-                } else {
-                    return Color3::new(0.0, 0.0, 0.0);
+            let current_ray = Ray::new(ray_origin, ray_direction).with_time(time);
+            stats.rays += 1;
+            stats.path_segments += 1;
+
+            let mut hit_anything =
+                world.hit(&current_ray, Interval::new(0.001, INFINITY), &mut hit_rec);
+            if let Some(atmosphere) = &self.atmosphere {
+                // Free flight through the air up to the surface, or out of
+                // the air if there is none; scattering on the way replaces
+                // the hit with a point in the medium
+                let ray_end = if hit_anything { hit_rec.t } else { INFINITY };
+                if let Some(span) = atmosphere.span(&current_ray, Interval::new(0.001, ray_end)) {
+                    let distance = free_flight_distance(atmosphere.density, sampler.get_1d());
+                    let t = span.min + distance / ray_direction.length();
+                    if t < span.max {
+                        hit_rec.set_medium_point(&current_ray, t, &atmosphere.phase);
+                        hit_anything = true;
+                    }
                 }
+            }
+            // Likewise through the media in front of whatever was hit
+            let ray_end = if hit_anything { hit_rec.t } else { INFINITY };
+            hit_anything |= world.sample_media(
+                &current_ray,
+                Interval::new(0.001, ray_end),
+                sampler,
+                &mut hit_rec,
+            );
+            if !hit_anything {
+                return radiance + throughput * self.background.color(ray_direction);
+            }
+
+            let emitted = hit_rec.material.emitted(&hit_rec);
+            if sample_lights && !previous_specular {
+                let light_pdf = world.light_pdf(ray_origin, ray_direction, time);
+                let weight = self.light_sampling.mis_weight(previous_pdf, light_pdf);
+                radiance += throughput * emitted * weight;
             } else {
-                // Hit sky
-                let unit_vector = Vec3::unit(ray_direction);
-                let a = 0.5 * (unit_vector.y + 1.0);
-                let sky_color =
-                    (1.0 - a) * Color3::new(1.0, 1.0, 1.0) + a * Color3::new(0.5, 0.7, 1.0);
-                return color * sky_color;
+                radiance += throughput * emitted;
+            }
+
+            if !hit_rec
+                .material
+                .scatter(&current_ray, &hit_rec, &mut srec, sampler)
+            {
+                return radiance;
             }
+
+            // Next-event estimation: shadow ray towards a random light
+            let light_direction = if sample_lights && !srec.is_specular {
+                world.random_light_direction(hit_rec.p, time, sampler)
+            } else {
+                None
+            };
+            if let Some(light_direction) = light_direction {
+                let light_pdf = world.light_pdf(hit_rec.p, light_direction, time);
+                let f = hit_rec.material.eval(&hit_rec, light_direction);
+
+                if light_pdf > 0.0 && f.length_squared() > 0.0 {
+                    let shadow_ray = Ray::new(hit_rec.p, light_direction).with_time(time);
+                    stats.rays += 1;
+                    if world.hit(&shadow_ray, Interval::new(0.001, INFINITY), &mut shadow_rec) {
+                        let shadow_t = Interval::new(0.001, shadow_rec.t);
+                        let mut light_emitted = shadow_rec.material.emitted(&shadow_rec)
+                            * world.media_transmittance(&shadow_ray, shadow_t);
+                        if let Some(atmosphere) = &self.atmosphere {
+                            light_emitted *= atmosphere.transmittance(&shadow_ray, shadow_t);
+                        }
+                        let bsdf_pdf = hit_rec.material.pdf(&hit_rec, light_direction);
+                        let weight = self.light_sampling.mis_weight(light_pdf, bsdf_pdf);
+                        radiance += throughput * f * light_emitted * (weight / light_pdf);
+                    }
+                }
+            }
+
+            throughput = throughput * srec.attenuation;
+            previous_specular = srec.is_specular;
+            previous_pdf = srec.pdf;
+            // Update ray parameters for next iteration
+            ray_origin = srec.scattered.origin();
+            ray_direction = srec.scattered.direction();
         }
 
         // Exhausted all bounces
-        Color3::new(0.0, 0.0, 0.0)
+        radiance
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::render::{film::Film, tile::Tile};
+    use crate::world::{material::Material, scene_object::SceneObject};
+    use std::sync::Arc;
+
+    fn lit_scene() -> SceneObjectList {
+        let mut world = SceneObjectList::new();
+        world.add(SceneObject::Sphere {
+            center: Point3::new(0.0, -1000.0, 0.0),
+            radius: 1000.0,
+            material: Arc::new(Material::Lambertian {
+                albedo: Color3::new(0.5, 0.5, 0.5).into(),
+            }),
+        });
+        world.add(SceneObject::Sphere {
+            center: Point3::new(0.0, 3.0, 0.0),
+            radius: 1.0,
+            material: Arc::new(Material::DiffuseLight {
+                emit: Color3::new(4.0, 4.0, 4.0),
+            }),
+        });
+        world
+    }
+
+    fn mean_radiance(
+        light_sampling: LightSampling,
+        atmosphere: Option<Atmosphere>,
+        samples: usize,
+    ) -> f32 {
+        let world = lit_scene();
+        let cam = Camera::new(
+            16,
+            1.0,
+            1,
+            40.0,
+            8,
+            Point3::new(0.0, 1.0, 6.0),
+            Point3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            0.0,
+            6.0,
+        )
+        .with_background(Background::Color(Color3::new(0.0, 0.0, 0.0)))
+        .with_light_sampling(light_sampling)
+        .with_atmosphere(atmosphere);
+
+        // Looks at the ground, so only indirect paths reach the light
+        let ray = Ray::new(cam.center, Point3::new(1.0, 0.0, 0.0) - cam.center);
+        let mut sum = 0.0;
+        let mut stats = RayStats::default();
+        let mut sampler = Sampler::new(SamplerKind::Independent, 1);
+        for _ in 0..samples {
+            sum += cam
+                .ray_color(&ray, cam.max_depth, &world, &mut sampler, &mut stats)
+                .x;
+        }
+        sum / samples as f32
+    }
+
+    #[test]
+    fn test_light_sampling_matches_bsdf_sampling() {
+        let bsdf = mean_radiance(LightSampling::Bsdf, None, 200_000);
+        let balance = mean_radiance(LightSampling::Balance, None, 50_000);
+        let power = mean_radiance(LightSampling::Power, None, 50_000);
+
+        assert!(
+            (balance - bsdf).abs() / bsdf < 0.05,
+            "{} vs {}",
+            balance,
+            bsdf
+        );
+        assert!((power - bsdf).abs() / bsdf < 0.05, "{} vs {}", power, bsdf);
+    }
+
+    #[test]
+    fn test_adaptive_sampling_stops_early_on_flat_pixels() {
+        let cam = Camera::new(
+            8,
+            1.0,
+            64,
+            40.0,
+            8,
+            Point3::new(0.0, 1.0, 6.0),
+            Point3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            0.0,
+            6.0,
+        );
+        let tile = Tile {
+            x0: 0,
+            y0: 0,
+            x1: 8,
+            y1: 8,
+        };
+        let render = |cam: &Camera, world: &SceneObjectList| {
+            let mut film = Film::new(8, 8);
+            let mut film_tile = film.tile(tile, cam.filter);
+            cam.render_tile(world, &mut film_tile, 1, 64, &mut RayStats::default());
+            film.merge([&film_tile]);
+            film.sample_counts()
+        };
+
+        // Without it every pixel takes the full count
+        assert!(render(&cam, &lit_scene()).iter().all(|&n| n == 64));
+
+        let cam = cam.with_adaptive_sampling(Some(AdaptiveSampling {
+            min_samples: 8,
+            threshold: 0.05,
+        }));
+        // The bare sky barely varies within a pixel
+        assert!(render(&cam, &SceneObjectList::new())
+            .iter()
+            .all(|&n| n == 8));
+        // Soft shadows and indirect light on the ground need more
+        let counts = render(&cam, &lit_scene());
+        assert!(counts.iter().all(|&n| (8..=64).contains(&n)));
+        assert!(counts.iter().any(|&n| n > 8));
+    }
+
+    #[test]
+    fn test_shutter_spreads_ray_times() {
+        let cam = Camera::new(
+            8,
+            1.0,
+            1,
+            40.0,
+            8,
+            Point3::new(0.0, 1.0, 6.0),
+            Point3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            0.0,
+            6.0,
+        );
+        let mut sampler = Sampler::new(SamplerKind::Independent, 1);
+        sampler.start_sample(0, 0);
+        assert_eq!(cam.get_ray(4, 4, &mut sampler).time(), 0.0);
+
+        let cam = cam.with_shutter(0.25, 0.75);
+        let times: Vec<f32> = (0..1000)
+            .map(|i| {
+                sampler.start_sample(0, i);
+                cam.get_ray(4, 4, &mut sampler).time()
+            })
+            .collect();
+        assert!(times.iter().all(|t| (0.25..0.75).contains(t)));
+        let mean = times.iter().sum::<f32>() / times.len() as f32;
+        assert!((mean - 0.5).abs() < 0.02, "{}", mean);
+    }
+
+    #[test]
+    fn test_atmosphere() {
+        // Without scattering albedo the air only absorbs: a light 5 away
+        // behind fog of density 0.2 keeps e^-1 of its radiance
+        let mut world = SceneObjectList::new();
+        world.add(SceneObject::Quad {
+            q: Point3::new(-1.0, -1.0, -5.0),
+            u: Vec3::new(2.0, 0.0, 0.0),
+            v: Vec3::new(0.0, 2.0, 0.0),
+            material: Arc::new(Material::DiffuseLight {
+                emit: Color3::new(1.0, 1.0, 1.0),
+            }),
+        });
+        let dark = Atmosphere {
+            density: 0.2,
+            phase: Arc::new(Material::Isotropic {
+                albedo: Color3::new(0.0, 0.0, 0.0).into(),
+            }),
+            center: Point3::new(0.0, 0.0, 0.0),
+            radius: 100.0,
+        };
+        let cam = Camera::new(
+            16,
+            1.0,
+            1,
+            40.0,
+            8,
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(0.0, 0.0, -1.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            0.0,
+            1.0,
+        )
+        .with_background(Background::Color(Color3::new(0.0, 0.0, 0.0)))
+        .with_atmosphere(Some(dark));
+        let ray = Ray::new(cam.center, Vec3::new(0.0, 0.0, -1.0));
+        let mut stats = RayStats::default();
+        let mut sampler = Sampler::new(SamplerKind::Independent, 1);
+        let samples = 20_000;
+        let mean = (0..samples)
+            .map(|_| {
+                cam.ray_color(&ray, cam.max_depth, &world, &mut sampler, &mut stats)
+                    .x
+            })
+            .sum::<f32>()
+            / samples as f32;
+        assert!((mean - (-1.0f32).exp()).abs() < 0.01, "{}", mean);
+
+        // Looking away from the light, the background fades by the 10
+        // units of air before its edge
+        let cam = cam.with_background(Background::Color(Color3::new(1.0, 1.0, 1.0)));
+        let away = Ray::new(cam.center, Vec3::new(0.0, 0.0, 1.0));
+        let mut sky = cam.clone();
+        sky.atmosphere.as_mut().unwrap().radius = 10.0;
+        let mean = (0..samples)
+            .map(|_| {
+                sky.ray_color(&away, sky.max_depth, &world, &mut sampler, &mut stats)
+                    .x
+            })
+            .sum::<f32>()
+            / samples as f32;
+        assert!((mean - (-2.0f32).exp()).abs() < 0.01, "{}", mean);
+
+        // Shadow rays through the air are attenuated as much as the paths
+        // finding the light by chance
+        let haze = || {
+            Some(Atmosphere {
+                density: 0.1,
+                phase: Arc::new(Material::HenyeyGreenstein {
+                    albedo: Color3::new(0.8, 0.8, 0.8).into(),
+                    g: 0.3,
+                }),
+                center: Point3::new(0.0, 1.0, 0.0),
+                radius: 8.0,
+            })
+        };
+        let bsdf = mean_radiance(LightSampling::Bsdf, haze(), 200_000);
+        let power = mean_radiance(LightSampling::Power, haze(), 50_000);
+        assert!((power - bsdf).abs() / bsdf < 0.05, "{} vs {}", power, bsdf);
     }
 }
@@ -0,0 +1,395 @@
+use std::{num::NonZeroUsize, path::PathBuf, thread};
+
+use clap::{Parser, ValueEnum};
+use ray_tracer::{
+    camera::camera::{AdaptiveSampling, LightSampling},
+    output::{
+        exr::{ExrCompression, ExrOptions, ExrPixelType},
+        image::ImageFormat,
+        tonemap::ToneMapper,
+    },
+    render::filter::{Filter, FilterKind},
+    sampler::sampler::SamplerKind,
+};
+
+/// Render a scene with the ray tracer.
+///
+/// Settings given on the command line override the ones in the scene file.
+#[derive(Parser, Debug)]
+#[command(version, about)]
+pub struct Args {
+    /// TOML scene description; renders the built-in random spheres scene if omitted
+    pub scene: Option<PathBuf>,
+
+    /// Image width in pixels
+    #[arg(short, long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub width: Option<u32>,
+
+    /// Aspect ratio as `width:height` (e.g. 16:9) or a decimal number
+    #[arg(short, long, value_parser = parse_aspect_ratio)]
+    pub aspect: Option<f32>,
+
+    /// Samples per pixel
+    #[arg(short, long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub spp: Option<u32>,
+
+    /// Maximum number of bounces per path
+    #[arg(short, long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub depth: Option<u32>,
+
+    /// Sequence the pixel, lens and BSDF samples are drawn from
+    /// [default: from the scene, or independent]
+    #[arg(long, value_enum)]
+    pub sampler: Option<SamplerArg>,
+
+    /// Pixel reconstruction filter [default: from the scene, or box]
+    #[arg(long, value_enum)]
+    pub filter: Option<FilterArg>,
+
+    /// Filter radius in pixels [default: 0.5 for box, 1 for tent, 1.5 for
+    /// gaussian, 2 for mitchell and lanczos]
+    #[arg(long, value_parser = parse_positive)]
+    pub filter_radius: Option<f32>,
+
+    /// Stop sampling a pixel once its relative error at 95% confidence is
+    /// below this, making `--spp` the maximum
+    #[arg(long, value_parser = parse_positive)]
+    pub adaptive_threshold: Option<f32>,
+
+    /// Samples every pixel takes before adaptive sampling may stop
+    /// [default: 16]
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub min_spp: Option<u32>,
+
+    /// Also write a heatmap of the samples taken per pixel to this path
+    #[arg(long)]
+    pub heatmap: Option<PathBuf>,
+
+    /// Number of render threads [default: available parallelism]
+    #[arg(short, long)]
+    pub threads: Option<NonZeroUsize>,
+
+    /// Edge length in pixels of the square tiles threads render at a time
+    #[arg(long, default_value = "32")]
+    pub tile_size: NonZeroUsize,
+
+    /// Output image path
+    #[arg(short, long, default_value = "image.ppm")]
+    pub output: PathBuf,
+
+    /// Output image format [default: from the output file extension]
+    #[arg(short, long, value_enum)]
+    pub format: Option<ImageFormatArg>,
+
+    /// Sample type of OpenEXR output
+    #[arg(long, value_enum, default_value_t = ExrPixelTypeArg::Half)]
+    pub exr_pixel_type: ExrPixelTypeArg,
+
+    /// Compression of OpenEXR output
+    #[arg(long, value_enum, default_value_t = ExrCompressionArg::Zip)]
+    pub exr_compression: ExrCompressionArg,
+
+    /// Tone mapping operator for 8-bit output [default: from the scene, or clamp]
+    #[arg(long, value_enum)]
+    pub tonemap: Option<ToneMapperArg>,
+
+    /// Luminance mapped to white by `--tonemap reinhard-extended` [default: 4]
+    #[arg(long, value_parser = parse_positive)]
+    pub white_point: Option<f32>,
+
+    /// Exposure adjustment in stops, e.g. -1 halves the brightness
+    #[arg(long, allow_negative_numbers = true, value_parser = parse_finite)]
+    pub exposure: Option<f32>,
+
+    /// Display gamma for 8-bit output; 0 selects the sRGB transfer function
+    #[arg(long, value_parser = parse_non_negative)]
+    pub gamma: Option<f32>,
+
+    /// Render with a black background instead of the sky gradient
+    #[arg(long)]
+    pub no_sky: bool,
+
+    /// Strategy for finding light from emissive objects [default: from the
+    /// scene, or power]
+    #[arg(long, value_enum)]
+    pub light_sampling: Option<LightSamplingArg>,
+
+    /// Seed for all random decisions; the same seed gives a bit-identical
+    /// image for any thread count [default: random, printed at start]
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    /// Render in passes of this many samples per pixel, which checkpoints
+    /// are saved between [default: all samples in one pass, or a sixteenth
+    /// of them with --checkpoint]
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub pass_spp: Option<u32>,
+
+    /// Save the render's progress to this file between passes
+    #[arg(long)]
+    pub checkpoint: Option<PathBuf>,
+
+    /// Minimum number of seconds between two checkpoints
+    #[arg(long, default_value_t = 300)]
+    pub checkpoint_interval: u64,
+
+    /// Continue the render saved in this checkpoint, which keeps being
+    /// updated unless --checkpoint names another file
+    #[arg(long)]
+    pub resume: Option<PathBuf>,
+
+    /// Don't draw the progress bar or print the render summary
+    #[arg(short, long)]
+    pub quiet: bool,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ImageFormatArg {
+    /// Binary P6 PPM
+    Ppm,
+    /// Plain-text P3 PPM
+    PlainPpm,
+    /// 8-bit PNG with the render settings in text chunks
+    Png,
+    /// Floating point PFM with the linear radiance
+    Pfm,
+    /// OpenEXR with the linear radiance
+    Exr,
+}
+
+impl From<ImageFormatArg> for ImageFormat {
+    fn from(format: ImageFormatArg) -> Self {
+        match format {
+            ImageFormatArg::Ppm => ImageFormat::Ppm,
+            ImageFormatArg::PlainPpm => ImageFormat::PlainPpm,
+            ImageFormatArg::Png => ImageFormat::Png,
+            ImageFormatArg::Pfm => ImageFormat::Pfm,
+            ImageFormatArg::Exr => ImageFormat::Exr(ExrOptions::default()),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ExrPixelTypeArg {
+    /// 16-bit floats
+    Half,
+    /// 32-bit floats
+    Float,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ExrCompressionArg {
+    /// Uncompressed
+    None,
+    /// Run-length encoding
+    Rle,
+    /// Zlib, one scanline per block
+    Zips,
+    /// Zlib, 16 scanlines per block
+    Zip,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ToneMapperArg {
+    /// Clip channels at 1
+    Clamp,
+    /// Reinhard on luminance
+    Reinhard,
+    /// Reinhard with a white point, see --white-point
+    ReinhardExtended,
+    /// ACES filmic curve
+    Aces,
+    /// Uncharted 2 filmic curve
+    Uncharted2,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum LightSamplingArg {
+    /// Only follow scattered rays
+    Bsdf,
+    /// Light sampling combined with BSDF sampling via the balance heuristic
+    Balance,
+    /// Light sampling combined with BSDF sampling via the power heuristic
+    Power,
+}
+
+impl From<LightSamplingArg> for LightSampling {
+    fn from(arg: LightSamplingArg) -> Self {
+        match arg {
+            LightSamplingArg::Bsdf => LightSampling::Bsdf,
+            LightSamplingArg::Balance => LightSampling::Balance,
+            LightSamplingArg::Power => LightSampling::Power,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum SamplerArg {
+    /// Independent uniform random numbers
+    Independent,
+    /// Jittered grid
+    Stratified,
+    /// Owen-scrambled Halton sequence
+    Halton,
+    /// Owen-scrambled Sobol sequence
+    Sobol,
+}
+
+impl From<SamplerArg> for SamplerKind {
+    fn from(arg: SamplerArg) -> Self {
+        match arg {
+            SamplerArg::Independent => SamplerKind::Independent,
+            SamplerArg::Stratified => SamplerKind::Stratified,
+            SamplerArg::Halton => SamplerKind::Halton,
+            SamplerArg::Sobol => SamplerKind::Sobol,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum FilterArg {
+    /// Plain average of the samples in each pixel
+    Box,
+    /// Linear falloff
+    Tent,
+    /// Gaussian falloff
+    Gaussian,
+    /// Mitchell-Netravali cubic, slightly sharpening
+    Mitchell,
+    /// Windowed sinc, sharpest but may ring
+    Lanczos,
+}
+
+impl From<FilterArg> for FilterKind {
+    fn from(arg: FilterArg) -> Self {
+        match arg {
+            FilterArg::Box => FilterKind::Box,
+            FilterArg::Tent => FilterKind::Tent,
+            FilterArg::Gaussian => FilterKind::Gaussian,
+            FilterArg::Mitchell => FilterKind::Mitchell,
+            FilterArg::Lanczos => FilterKind::Lanczos,
+        }
+    }
+}
+
+impl Args {
+    /// Output format from `--format` or the output file extension, with the
+    /// OpenEXR settings applied.
+    pub fn image_format(&self) -> Option<ImageFormat> {
+        let format = self
+            .format
+            .map(Into::into)
+            .or_else(|| ImageFormat::from_path(&self.output))?;
+        Some(match format {
+            ImageFormat::Exr(_) => ImageFormat::Exr(ExrOptions {
+                pixel_type: match self.exr_pixel_type {
+                    ExrPixelTypeArg::Half => ExrPixelType::Half,
+                    ExrPixelTypeArg::Float => ExrPixelType::Float,
+                },
+                compression: match self.exr_compression {
+                    ExrCompressionArg::None => ExrCompression::None,
+                    ExrCompressionArg::Rle => ExrCompression::Rle,
+                    ExrCompressionArg::Zips => ExrCompression::Zips,
+                    ExrCompressionArg::Zip => ExrCompression::Zip,
+                },
+            }),
+            format => format,
+        })
+    }
+
+    /// Tone mapper from `--tonemap` and `--white-point`, falling back to
+    /// the scene's.
+    pub fn tone_mapper(&self, scene: ToneMapper) -> ToneMapper {
+        let white = self.white_point.unwrap_or(match scene {
+            ToneMapper::ExtendedReinhard { white } => white,
+            _ => 4.0,
+        });
+        match self.tonemap {
+            None => match scene {
+                ToneMapper::ExtendedReinhard { .. } => ToneMapper::ExtendedReinhard { white },
+                mapper => mapper,
+            },
+            Some(ToneMapperArg::Clamp) => ToneMapper::Clamp,
+            Some(ToneMapperArg::Reinhard) => ToneMapper::Reinhard,
+            Some(ToneMapperArg::ReinhardExtended) => ToneMapper::ExtendedReinhard { white },
+            Some(ToneMapperArg::Aces) => ToneMapper::Aces,
+            Some(ToneMapperArg::Uncharted2) => ToneMapper::Uncharted2,
+        }
+    }
+
+    /// Reconstruction filter from `--filter` and `--filter-radius`, falling
+    /// back to the scene's. A filter picked here starts from its own
+    /// default radius rather than the scene's.
+    pub fn filter(&self, scene: Filter) -> Filter {
+        let kind = self.filter.map_or(scene.kind, Into::into);
+        let radius = self.filter_radius.unwrap_or(if self.filter.is_some() {
+            kind.default_radius()
+        } else {
+            scene.radius
+        });
+        Filter::new(kind, radius)
+    }
+
+    /// Adaptive sampling from `--adaptive-threshold` and `--min-spp`,
+    /// falling back to the scene's.
+    pub fn adaptive_sampling(&self, scene: Option<AdaptiveSampling>) -> Option<AdaptiveSampling> {
+        let threshold = self.adaptive_threshold.or(scene.map(|a| a.threshold))?;
+        let min_samples = self.min_spp.or(scene.map(|a| a.min_samples)).unwrap_or(16);
+        Some(AdaptiveSampling {
+            min_samples,
+            threshold,
+        })
+    }
+
+    pub fn thread_count(&self) -> usize {
+        self.threads
+            .or_else(|| thread::available_parallelism().ok())
+            .map_or(1, NonZeroUsize::get)
+    }
+}
+
+fn parse_aspect_ratio(s: &str) -> Result<f32, String> {
+    let ratio = match s.split_once(':') {
+        Some((w, h)) => {
+            let w: f32 = w
+                .trim()
+                .parse()
+                .map_err(|_| format!("invalid width `{}`", w))?;
+            let h: f32 = h
+                .trim()
+                .parse()
+                .map_err(|_| format!("invalid height `{}`", h))?;
+            w / h
+        }
+        None => s
+            .parse()
+            .map_err(|_| format!("invalid aspect ratio `{}`", s))?,
+    };
+
+    if ratio.is_finite() && ratio > 0.0 {
+        Ok(ratio)
+    } else {
+        Err(format!("aspect ratio must be positive, got `{}`", s))
+    }
+}
+
+fn parse_finite(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(v) if v.is_finite() => Ok(v),
+        _ => Err(format!("expected a finite number, got `{}`", s)),
+    }
+}
+
+fn parse_positive(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(v) if v.is_finite() && v > 0.0 => Ok(v),
+        _ => Err(format!("expected a positive number, got `{}`", s)),
+    }
+}
+
+fn parse_non_negative(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(v) if v.is_finite() && v >= 0.0 => Ok(v),
+        _ => Err(format!("expected a non-negative number, got `{}`", s)),
+    }
+}
@@ -0,0 +1,11 @@
+#![allow(clippy::module_inception)]
+
+pub mod camera;
+pub mod output;
+pub mod ray;
+pub mod render;
+pub mod sampler;
+pub mod scene;
+pub mod utils;
+pub mod vec3;
+pub mod world;
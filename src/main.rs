@@ -1,202 +1,197 @@
-mod camera;
-mod ray;
-mod utils;
-mod vec3;
-mod world;
-
-use crate::{
-    utils::helpers::random_f32,
-    vec3::{
-        color3::Color3,
-        vec3::{Point3, Vec3},
+mod cli;
+
+use clap::Parser;
+use cli::Args;
+use rand::{rngs::StdRng, SeedableRng};
+use ray_tracer::{
+    camera::camera::{Background, Camera},
+    output::{
+        image::{write_image, ImageFormat, RenderMetadata},
+        tonemap::DisplayTransform,
     },
-    world::material::Material,
-};
-use camera::camera::Camera;
-use rand::Rng;
-use std::{
-    fs::File,
-    io::{self, Write},
-    sync::Arc,
-    thread,
+    render::{checkpoint::Checkpoint, heatmap::sample_heatmap, renderer::Renderer},
+    scene::{
+        loader::{load_scene, Scene},
+        random_spheres::{random_spheres_camera, random_spheres_scene},
+    },
+    vec3::color3::Color3,
 };
-use world::{scene_object::SceneObject, scene_object_list::SceneObjectList};
+use std::{process, time::Duration};
 
 fn main() {
-    let mut world = SceneObjectList::new();
-    let mut rng = rand::thread_rng();
-
-    // Ground
-    let ground_material = Arc::new(Material::Lambertian {
-        albedo: Color3::new(0.5, 0.5, 0.5),
-    });
-    world.add(SceneObject::Sphere {
-        center: Vec3::new(0.0, -1000.0, 0.0),
-        radius: 1000.0,
-        material: ground_material.clone(),
-    });
+    let args = Args::parse();
 
-    for a in -11..11 {
-        for b in -11..11 {
-            let choose_mat: f64 = rng.gen();
-            let center = Vec3::new(
-                a as f32 + 0.9 * rng.gen::<f32>(),
-                0.2,
-                b as f32 + 0.9 * rng.gen::<f32>(),
+    // A resumed render continues with the seed it started with
+    let resume = args.resume.as_ref().map(|path| {
+        let checkpoint = Checkpoint::read(path).unwrap_or_else(|err| {
+            eprintln!("{}: failed to read checkpoint: {}", path.display(), err);
+            process::exit(1);
+        });
+        if args.seed.is_some_and(|seed| seed != checkpoint.seed) {
+            eprintln!(
+                "{}: checkpoint was rendered with seed {}",
+                path.display(),
+                checkpoint.seed
             );
-
-            if (center - Vec3::new(4.0, 0.2, 0.0)).length() > 0.9 {
-                let material = if choose_mat < 0.8 {
-                    // diffuse
-                    let albedo = Color3::random() * Color3::random();
-                    Arc::new(Material::Lambertian { albedo })
-                } else if choose_mat < 0.95 {
-                    // metal
-                    let albedo = Color3::random_range(0.5, 1.0);
-                    let fuzz = rng.gen_range(0.0..0.5);
-                    Arc::new(Material::Metal { albedo, fuzz })
-                } else {
-                    // glass
-                    Arc::new(Material::Dielectric {
-                        refraction_index: 1.5,
-                    })
-                };
-
-                if random_f32() > 0.5 {
-                    world.add(SceneObject::Sphere {
-                        center,
-                        radius: 0.2,
-                        material: material,
-                    });
-                } else {
-                    world.add(SceneObject::Cylinder {
-                        radius: 0.2,
-                        center: center,
-                        height: 0.5,
-                        material: material,
-                    });
-                }
-            }
+            process::exit(1);
         }
-    }
-
-    // Three main spheres
-    let material1 = Arc::new(Material::Dielectric {
-        refraction_index: 1.5,
-    });
-    let material2 = Arc::new(Material::Lambertian {
-        albedo: Color3::new(0.4, 0.2, 0.1),
-    });
-    let material3 = Arc::new(Material::Metal {
-        albedo: Color3::new(0.7, 0.6, 0.5),
-        fuzz: 0.0,
+        if args
+            .pass_spp
+            .is_some_and(|pass_spp| pass_spp != checkpoint.pass_samples)
+        {
+            eprintln!(
+                "{}: checkpoint was rendered in passes of {} samples",
+                path.display(),
+                checkpoint.pass_samples
+            );
+            process::exit(1);
+        }
+        checkpoint
     });
 
-    world.add(SceneObject::Sphere {
-        center: Vec3::new(0.0, 1.0, 0.0),
-        radius: 1.0,
-        material: material1,
+    // A fresh seed is still reported so the render can be reproduced
+    let seed = resume
+        .as_ref()
+        .map(|checkpoint| checkpoint.seed)
+        .or(args.seed)
+        .unwrap_or_else(rand::random);
+
+    // Resolve the output format before spending time on the render
+    let format = args.image_format().unwrap_or_else(|| {
+        eprintln!(
+            "{}: unknown image extension, use .png, .ppm, .exr or .pfm or pass --format",
+            args.output.display()
+        );
+        process::exit(1);
     });
-    world.add(SceneObject::Sphere {
-        center: Vec3::new(-4.0, 1.0, 0.0),
-        radius: 1.0,
-        material: material2,
-    });
-    world.add(SceneObject::Sphere {
-        center: Vec3::new(4.0, 1.0, 0.0),
-        radius: 1.0,
-        material: material3,
+    let heatmap_format = args.heatmap.as_ref().map(|path| {
+        ImageFormat::from_path(path).unwrap_or_else(|| {
+            eprintln!(
+                "{}: unknown image extension, use .png, .ppm, .exr or .pfm",
+                path.display()
+            );
+            process::exit(1);
+        })
     });
-    // Camera configuration
-    let cam = Camera::new(
-        1200,
-        16.0 / 9.0,
-        500,
-        25.0,
-        20,
-        Point3::new(13.0, 2.0, 3.0),
-        Point3::new(0.0, 0.0, 0.0),
-        Vec3::new(0.0, 1.0, 0.0),
-        0.6,
-        10.0,
-    );
 
-    let image_height: usize = (cam.image_width as f32 / cam.aspect_ratio) as usize;
+    // Scene file given on the command line, or the built-in random spheres scene
+    let Scene { mut world, camera } = match &args.scene {
+        Some(path) => load_scene(path).unwrap_or_else(|err| {
+            eprintln!("{}: {}", path.display(), err);
+            process::exit(1);
+        }),
+        None => {
+            let mut rng = StdRng::seed_from_u64(seed);
+            Scene {
+                world: random_spheres_scene(&mut rng),
+                camera: random_spheres_camera(),
+            }
+        }
+    };
+    world.build_bvh();
 
-    let num_threads = 7;
+    // Command-line settings take precedence over the scene file
+    let cam = Camera::new(
+        args.width.unwrap_or(camera.image_width),
+        args.aspect.unwrap_or(camera.aspect_ratio),
+        args.spp.unwrap_or(camera.samples_per_pixel),
+        camera.vfov,
+        args.depth.unwrap_or(camera.max_depth),
+        camera.camera_position,
+        camera.lookat,
+        camera.upvector,
+        camera.defocus_angle,
+        camera.focus_dist,
+    )
+    .with_background(if args.no_sky {
+        Background::Color(Color3::new(0.0, 0.0, 0.0))
+    } else {
+        camera.background
+    })
+    .with_light_sampling(
+        args.light_sampling
+            .map_or(camera.light_sampling, Into::into),
+    )
+    .with_tone_mapping(
+        args.tone_mapper(camera.tone_mapper),
+        args.exposure.unwrap_or(camera.exposure),
+    )
+    .with_gamma_correction(args.gamma.unwrap_or(camera.gamma_correction))
+    .with_sampler(args.sampler.map_or(camera.sampler, Into::into))
+    .with_filter(args.filter(camera.filter))
+    .with_shutter(camera.shutter_open, camera.shutter_close)
+    .with_atmosphere(camera.atmosphere.clone())
+    .with_adaptive_sampling(args.adaptive_sampling(camera.adaptive_sampling));
+
+    let threads = args.thread_count();
     println!(
-        "Rendering {}x{} image with {} threads...",
-        cam.image_width, image_height, num_threads
-    );
-
-    let rows_per_thread = image_height / num_threads;
-    let mut handles = vec![];
-    let mut subimages: Vec<Vec<Color3>> =
-        vec![
-            vec![Color3::new(0.0, 0.0, 0.0); rows_per_thread * cam.image_width as usize];
-            num_threads
-        ];
-
-    subimages[num_threads - 1].resize(
-        (image_height - (num_threads - 1) * rows_per_thread) * cam.image_width as usize,
-        Color3::new(0.0, 0.0, 0.0),
+        "Rendering {}x{} image with {} threads (seed {})...",
+        cam.image_width, cam.image_height, threads, seed
     );
 
-    let shared_world = Arc::new(world);
-
-    for (i, mut subimage) in subimages.into_iter().enumerate() {
-        let start_row = i * rows_per_thread;
-        let end_row = if i == num_threads - 1 {
-            image_height // Last thread handles remaining rows
-        } else {
-            (i + 1) * rows_per_thread
-        };
-
-        let cam_clone = cam.clone();
-        let world_clone = Arc::clone(&shared_world);
-
-        let handle = thread::spawn(move || {
-            cam_clone.render(
-                world_clone,
-                (0, cam_clone.image_width as usize),
-                (start_row, end_row),
-                &mut subimage,
-            );
-            subimage
-        });
-        handles.push(handle);
+    let mut renderer = Renderer::new(&cam, &world)
+        .with_threads(threads)
+        .with_tile_size(args.tile_size.get())
+        .with_seed(seed)
+        .with_progress(!args.quiet);
+    if let Some(pass_spp) = args.pass_spp {
+        renderer = renderer.with_pass_samples(pass_spp);
     }
-
-    // Collect results from all threads
-    let mut image: Vec<Vec<Color3>> = vec![];
-    for handle in handles {
-        image.push(handle.join().unwrap());
+    if let Some(path) = args.checkpoint.as_ref().or(args.resume.as_ref()) {
+        let interval = Duration::from_secs(args.checkpoint_interval);
+        renderer = renderer.with_checkpoint(path.clone(), interval);
+    }
+    if let Some(checkpoint) = resume {
+        if let Err(err) = checkpoint.check_compatible(&cam, args.tile_size.get()) {
+            eprintln!("{}: {}", args.resume.as_ref().unwrap().display(), err);
+            process::exit(1);
+        }
+        println!(
+            "Resuming after {} of {} passes",
+            checkpoint.passes_done,
+            checkpoint.pass_count()
+        );
+        renderer = renderer.with_resume(checkpoint);
+    }
+    let (image, stats) = renderer.render();
+    if !args.quiet {
+        eprintln!("{}", stats);
     }
 
-    write_image_to_file(&cam, image_height, &image).expect("Failed to write image file");
-}
-
-fn write_image_to_file(
-    cam: &Camera,
-    image_height: usize,
-    image: &Vec<Vec<Color3>>,
-) -> io::Result<()> {
-    let mut file = File::create("image.ppm")?;
-
-    // Write PPM header
-    writeln!(file, "P3")?;
-    writeln!(file, "{} {}", cam.image_width, image_height)?;
-    writeln!(file, "255")?;
-
-    for subimage in image.iter() {
-        for color in subimage {
-            let r = (255.0 * color.x.clamp(0.0, 1.0)) as u8;
-            let g = (255.0 * color.y.clamp(0.0, 1.0)) as u8;
-            let b = (255.0 * color.z.clamp(0.0, 1.0)) as u8;
+    let metadata = RenderMetadata {
+        samples_per_pixel: cam.samples_per_pixel,
+        max_depth: cam.max_depth,
+        render_time: stats.wall_time,
+        seed: Some(seed),
+        scene: args.scene.as_ref().map(|path| path.display().to_string()),
+    };
+    if let Err(err) = write_image(
+        &args.output,
+        Some(format),
+        &image,
+        &metadata,
+        &cam.display_transform(),
+    ) {
+        eprintln!("{}: failed to write image: {}", args.output.display(), err);
+        process::exit(1);
+    }
 
-            writeln!(file, "{} {} {}", r, g, b)?;
+    if let (Some(path), Some(heatmap_format)) = (&args.heatmap, heatmap_format) {
+        let heatmap = sample_heatmap(
+            image.width,
+            image.height,
+            &stats.sample_counts,
+            cam.samples_per_pixel,
+        );
+        if let Err(err) = write_image(
+            path,
+            Some(heatmap_format),
+            &heatmap,
+            &metadata,
+            &DisplayTransform::default(),
+        ) {
+            eprintln!("{}: failed to write heatmap: {}", path.display(), err);
+            process::exit(1);
         }
     }
-
-    Ok(())
 }
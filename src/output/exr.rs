@@ -0,0 +1,484 @@
+//! Single-part scanline OpenEXR writer.
+//!
+//! Writes R, G and B channels as half or float samples, either uncompressed
+//! or with the RLE, ZIPS (one scanline per block) or ZIP (16 scanlines per
+//! block) compression schemes.
+
+use std::io::{self, Write};
+
+use flate2::{write::ZlibEncoder, Compression};
+
+use crate::output::image::{Image, RenderMetadata};
+
+const MAGIC: [u8; 4] = [0x76, 0x2f, 0x31, 0x01];
+// Version 2, single-part scanline file
+const VERSION: [u8; 4] = [2, 0, 0, 0];
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum ExrPixelType {
+    /// 16-bit float, plenty for final images at half the size.
+    #[default]
+    Half,
+    Float,
+}
+
+impl ExrPixelType {
+    fn code(&self) -> i32 {
+        match self {
+            Self::Half => 1,
+            Self::Float => 2,
+        }
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            Self::Half => 2,
+            Self::Float => 4,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum ExrCompression {
+    None,
+    Rle,
+    Zips,
+    #[default]
+    Zip,
+}
+
+impl ExrCompression {
+    fn code(&self) -> u8 {
+        match self {
+            Self::None => 0,
+            Self::Rle => 1,
+            Self::Zips => 2,
+            Self::Zip => 3,
+        }
+    }
+
+    fn lines_per_block(&self) -> usize {
+        match self {
+            Self::Zip => 16,
+            _ => 1,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct ExrOptions {
+    pub pixel_type: ExrPixelType,
+    pub compression: ExrCompression,
+}
+
+/// Writes the linear pixels of `image` with the metadata as string
+/// attributes.
+pub fn write_exr(
+    mut writer: impl Write,
+    image: &Image,
+    metadata: &RenderMetadata,
+    options: ExrOptions,
+) -> io::Result<()> {
+    let mut header = Vec::new();
+    header.extend_from_slice(&MAGIC);
+    header.extend_from_slice(&VERSION);
+
+    // Channels are stored in alphabetical order
+    let mut channels = Vec::new();
+    for name in ["B", "G", "R"] {
+        channels.extend_from_slice(name.as_bytes());
+        channels.push(0);
+        channels.extend_from_slice(&options.pixel_type.code().to_le_bytes());
+        // pLinear and reserved bytes, then x and y sampling
+        channels.extend_from_slice(&[0, 0, 0, 0]);
+        channels.extend_from_slice(&1i32.to_le_bytes());
+        channels.extend_from_slice(&1i32.to_le_bytes());
+    }
+    channels.push(0);
+    write_attribute(&mut header, "channels", "chlist", &channels);
+    write_attribute(
+        &mut header,
+        "compression",
+        "compression",
+        &[options.compression.code()],
+    );
+
+    let window: Vec<u8> = [0, 0, image.width as i32 - 1, image.height as i32 - 1]
+        .iter()
+        .flat_map(|v| v.to_le_bytes())
+        .collect();
+    write_attribute(&mut header, "dataWindow", "box2i", &window);
+    write_attribute(&mut header, "displayWindow", "box2i", &window);
+    // Increasing y
+    write_attribute(&mut header, "lineOrder", "lineOrder", &[0]);
+    write_attribute(
+        &mut header,
+        "pixelAspectRatio",
+        "float",
+        &1.0f32.to_le_bytes(),
+    );
+    write_attribute(&mut header, "screenWindowCenter", "v2f", &[0; 8]);
+    write_attribute(
+        &mut header,
+        "screenWindowWidth",
+        "float",
+        &1.0f32.to_le_bytes(),
+    );
+    for (key, value) in metadata.entries() {
+        // Attribute names are camel case by convention
+        let name: String = key
+            .split(' ')
+            .enumerate()
+            .map(|(i, word)| {
+                let (first, rest) = word.split_at(1);
+                if i == 0 {
+                    first.to_lowercase() + rest
+                } else {
+                    first.to_uppercase() + rest
+                }
+            })
+            .collect();
+        write_attribute(&mut header, &name, "string", value.as_bytes());
+    }
+    header.push(0);
+
+    let lines_per_block = options.compression.lines_per_block();
+    let blocks: Vec<Vec<u8>> = (0..image.height)
+        .step_by(lines_per_block)
+        .map(|y| {
+            let rows = y..(y + lines_per_block).min(image.height);
+            let raw = block_data(image, rows, options.pixel_type);
+            let packed = compress(&raw, options.compression);
+            let mut chunk = Vec::with_capacity(packed.len() + 8);
+            chunk.extend_from_slice(&(y as i32).to_le_bytes());
+            chunk.extend_from_slice(&(packed.len() as i32).to_le_bytes());
+            chunk.extend_from_slice(&packed);
+            chunk
+        })
+        .collect();
+
+    // Offset table, then the chunks in order
+    let mut offset = (header.len() + blocks.len() * 8) as u64;
+    for block in &blocks {
+        header.extend_from_slice(&offset.to_le_bytes());
+        offset += block.len() as u64;
+    }
+    writer.write_all(&header)?;
+    for block in &blocks {
+        writer.write_all(block)?;
+    }
+    Ok(())
+}
+
+fn write_attribute(out: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+    out.extend_from_slice(name.as_bytes());
+    out.push(0);
+    out.extend_from_slice(kind.as_bytes());
+    out.push(0);
+    out.extend_from_slice(&(value.len() as i32).to_le_bytes());
+    out.extend_from_slice(value);
+}
+
+/// Uncompressed samples of `rows`: for each scanline, every pixel of B, then
+/// of G, then of R.
+fn block_data(image: &Image, rows: std::ops::Range<usize>, pixel_type: ExrPixelType) -> Vec<u8> {
+    let mut data = Vec::with_capacity(rows.len() * image.width * 3 * pixel_type.size());
+    for y in rows {
+        let row = &image.pixels[y * image.width..(y + 1) * image.width];
+        for channel in [2, 1, 0] {
+            for pixel in row {
+                let value = match channel {
+                    0 => pixel.x,
+                    1 => pixel.y,
+                    _ => pixel.z,
+                };
+                match pixel_type {
+                    ExrPixelType::Half => data.extend_from_slice(&f32_to_half(value).to_le_bytes()),
+                    ExrPixelType::Float => data.extend_from_slice(&value.to_le_bytes()),
+                }
+            }
+        }
+    }
+    data
+}
+
+/// Compresses one block, falling back to the raw data when compression
+/// doesn't make it smaller, which readers detect from the size.
+fn compress(raw: &[u8], compression: ExrCompression) -> Vec<u8> {
+    let packed = match compression {
+        ExrCompression::None => return raw.to_vec(),
+        ExrCompression::Rle => rle_compress(&predict(&interleave(raw))),
+        ExrCompression::Zips | ExrCompression::Zip => {
+            let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+            encoder
+                .write_all(&predict(&interleave(raw)))
+                .and_then(|_| encoder.finish())
+                .expect("writing to a Vec can't fail")
+        }
+    };
+    if packed.len() < raw.len() {
+        packed
+    } else {
+        raw.to_vec()
+    }
+}
+
+/// Moves the even-indexed bytes to the first half and the odd ones to the
+/// second, grouping the low and high bytes of the samples.
+fn interleave(data: &[u8]) -> Vec<u8> {
+    let half = data.len().div_ceil(2);
+    let mut out = vec![0; data.len()];
+    for (i, &byte) in data.iter().enumerate() {
+        if i % 2 == 0 {
+            out[i / 2] = byte;
+        } else {
+            out[half + i / 2] = byte;
+        }
+    }
+    out
+}
+
+/// Replaces each byte with its difference from the previous one.
+fn predict(data: &[u8]) -> Vec<u8> {
+    let mut out = data.to_vec();
+    for i in (1..data.len()).rev() {
+        out[i] = data[i].wrapping_sub(data[i - 1]).wrapping_add(128);
+    }
+    out
+}
+
+/// OpenEXR's byte-oriented run-length encoding: a non-negative count `n`
+/// followed by a byte repeated `n + 1` times, or a negative count `-n`
+/// followed by `n` literal bytes.
+fn rle_compress(data: &[u8]) -> Vec<u8> {
+    const MIN_RUN: usize = 3;
+    const MAX_RUN: usize = 127;
+
+    let mut out = Vec::new();
+    let mut start = 0;
+    while start < data.len() {
+        let mut end = start + 1;
+        while end < data.len() && data[end] == data[start] && end - start < MAX_RUN + 1 {
+            end += 1;
+        }
+        if end - start >= MIN_RUN {
+            out.push((end - start - 1) as u8);
+            out.push(data[start]);
+        } else {
+            // Extend the literal until a run of at least three begins
+            while end < data.len()
+                && end - start < MAX_RUN
+                && !(end + 2 < data.len()
+                    && data[end] == data[end + 1]
+                    && data[end] == data[end + 2])
+            {
+                end += 1;
+            }
+            out.push((-((end - start) as i32)) as u8);
+            out.extend_from_slice(&data[start..end]);
+        }
+        start = end;
+    }
+    out
+}
+
+/// Rounds to the nearest IEEE 754 half, ties to even, saturating to
+/// infinity above its range.
+pub fn f32_to_half(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7f_ffff;
+
+    if exponent == 0xff {
+        // Infinity, or NaN with a mantissa bit kept set
+        let nan = if mantissa != 0 { 0x200 } else { 0 };
+        return sign | 0x7c00 | nan;
+    }
+
+    let half_exponent = exponent - 127 + 15;
+    if half_exponent >= 0x1f {
+        return sign | 0x7c00;
+    }
+    if half_exponent <= 0 {
+        // Subnormal half, or zero when too small
+        if half_exponent < -10 {
+            return sign;
+        }
+        let mantissa = mantissa | 0x80_0000;
+        let shift = (14 - half_exponent) as u32;
+        let half_mantissa = mantissa >> shift;
+        let remainder = mantissa & ((1 << shift) - 1);
+        let halfway = 1 << (shift - 1);
+        let round_up = remainder > halfway || (remainder == halfway && half_mantissa & 1 == 1);
+        return sign | (half_mantissa + round_up as u32) as u16;
+    }
+
+    let half = ((half_exponent as u32) << 10) | (mantissa >> 13);
+    let remainder = mantissa & 0x1fff;
+    let round_up = remainder > 0x1000 || (remainder == 0x1000 && half & 1 == 1);
+    // A carry out of the mantissa correctly bumps the exponent, up to infinity
+    sign | (half + round_up as u32) as u16
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::vec3::color3::Color3;
+
+    fn rle_decompress(data: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut i = 0;
+        while i < data.len() {
+            let count = data[i] as i8;
+            i += 1;
+            if count < 0 {
+                let n = (-count) as usize;
+                out.extend_from_slice(&data[i..i + n]);
+                i += n;
+            } else {
+                out.extend(std::iter::repeat_n(data[i], count as usize + 1));
+                i += 1;
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn test_half_conversion() {
+        assert_eq!(f32_to_half(0.0), 0x0000);
+        assert_eq!(f32_to_half(-0.0), 0x8000);
+        assert_eq!(f32_to_half(1.0), 0x3c00);
+        assert_eq!(f32_to_half(-2.0), 0xc000);
+        assert_eq!(f32_to_half(0.5), 0x3800);
+        assert_eq!(f32_to_half(65504.0), 0x7bff);
+        assert_eq!(f32_to_half(1e6), 0x7c00);
+        assert_eq!(f32_to_half(f32::INFINITY), 0x7c00);
+        assert_eq!(f32_to_half(f32::NAN) & 0x7e00, 0x7e00);
+        // Smallest subnormal, and a value that rounds away to zero
+        assert_eq!(f32_to_half(5.960_464_5e-8), 0x0001);
+        assert_eq!(f32_to_half(1e-9), 0x0000);
+        // 1 + 2^-11 is a tie between 1 and the next half, rounding to even
+        assert_eq!(f32_to_half(1.0 + 1.0 / 2048.0), 0x3c00);
+        assert_eq!(f32_to_half(1.0 + 3.0 / 2048.0), 0x3c02);
+    }
+
+    #[test]
+    fn test_rle_round_trip() {
+        let mut data = vec![7u8; 300];
+        data.extend([1, 2, 3, 3, 4, 5, 5, 5, 5, 6]);
+        data.extend((0..=255).cycle().take(400));
+        let packed = rle_compress(&data);
+        assert!(packed.len() < data.len());
+        assert_eq!(rle_decompress(&packed), data);
+    }
+
+    #[test]
+    fn test_predictor_and_interleave() {
+        let data = [10, 200, 11, 201, 12, 202, 13];
+        assert_eq!(interleave(&data), vec![10, 11, 12, 13, 200, 201, 202]);
+        let predicted = predict(&interleave(&data));
+        assert_eq!(&predicted[..4], &[10, 129, 129, 129]);
+    }
+
+    fn gradient_image() -> Image {
+        let (width, height) = (37, 21);
+        let pixels = (0..width * height)
+            .map(|i| {
+                let (x, y) = ((i % width) as f32, (i / width) as f32);
+                Color3::new(x / width as f32 * 4.0, y / height as f32, 0.25)
+            })
+            .collect();
+        Image::new(width, height, pixels)
+    }
+
+    /// Reads back the offset table and the first chunk of a file.
+    fn first_chunk(bytes: &[u8], chunk_count: usize) -> (i32, &[u8]) {
+        let table_start = header_len(bytes);
+        let offsets: Vec<u64> = (0..chunk_count)
+            .map(|i| {
+                let at = table_start + i * 8;
+                u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap())
+            })
+            .collect();
+        let at = offsets[0] as usize;
+        let y = i32::from_le_bytes(bytes[at..at + 4].try_into().unwrap());
+        let size = i32::from_le_bytes(bytes[at + 4..at + 8].try_into().unwrap()) as usize;
+        (y, &bytes[at + 8..at + 8 + size])
+    }
+
+    /// Skips the attributes up to the empty name that ends the header.
+    fn header_len(bytes: &[u8]) -> usize {
+        let mut at = 8;
+        loop {
+            if bytes[at] == 0 {
+                return at + 1;
+            }
+            let name_end = at + bytes[at..].iter().position(|&b| b == 0).unwrap();
+            let kind_end =
+                name_end + 1 + bytes[name_end + 1..].iter().position(|&b| b == 0).unwrap();
+            let size = i32::from_le_bytes(bytes[kind_end + 1..kind_end + 5].try_into().unwrap());
+            at = kind_end + 5 + size as usize;
+        }
+    }
+
+    #[test]
+    fn test_uncompressed_float_layout() {
+        let image = gradient_image();
+        let options = ExrOptions {
+            pixel_type: ExrPixelType::Float,
+            compression: ExrCompression::None,
+        };
+        let mut bytes = Vec::new();
+        write_exr(&mut bytes, &image, &RenderMetadata::default(), options).unwrap();
+        assert_eq!(&bytes[..4], &MAGIC);
+
+        let (y, data) = first_chunk(&bytes, image.height);
+        assert_eq!(y, 0);
+        assert_eq!(data.len(), image.width * 3 * 4);
+        // First scanline starts with the blue channel, then green and red
+        let sample = |i: usize| f32::from_le_bytes(data[i * 4..i * 4 + 4].try_into().unwrap());
+        assert_eq!(sample(0), 0.25);
+        assert_eq!(sample(image.width), 0.0);
+        assert_eq!(sample(2 * image.width + 1), image.pixels[1].x);
+    }
+
+    #[test]
+    fn test_compressed_blocks_decode() {
+        let image = gradient_image();
+        for compression in [
+            ExrCompression::Rle,
+            ExrCompression::Zips,
+            ExrCompression::Zip,
+        ] {
+            let options = ExrOptions {
+                pixel_type: ExrPixelType::Half,
+                compression,
+            };
+            let mut bytes = Vec::new();
+            write_exr(&mut bytes, &image, &RenderMetadata::default(), options).unwrap();
+            let chunk_count = image.height.div_ceil(compression.lines_per_block());
+            let (y, packed) = first_chunk(&bytes, chunk_count);
+            assert_eq!(y, 0);
+
+            let rows = compression.lines_per_block().min(image.height);
+            let raw = block_data(&image, 0..rows, ExrPixelType::Half);
+            let unpacked = if packed.len() == raw.len() {
+                packed.to_vec()
+            } else if compression == ExrCompression::Rle {
+                rle_decompress(packed)
+            } else {
+                let mut out = Vec::new();
+                let mut decoder = flate2::read::ZlibDecoder::new(packed);
+                std::io::Read::read_to_end(&mut decoder, &mut out).unwrap();
+                out
+            };
+            let expected = if packed.len() == raw.len() {
+                raw
+            } else {
+                predict(&interleave(&raw))
+            };
+            assert_eq!(unpacked, expected, "{:?}", compression);
+        }
+    }
+}
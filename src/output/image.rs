@@ -0,0 +1,200 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+    time::Duration,
+};
+
+use crate::{
+    output::{
+        exr::{write_exr, ExrOptions},
+        pfm::write_pfm,
+        png::write_png,
+        ppm::write_ppm,
+        tonemap::DisplayTransform,
+    },
+    vec3::color3::Color3,
+};
+
+/// Rendered image in linear radiance, stored row by row from the top.
+#[derive(Debug, Clone)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<Color3>,
+}
+
+impl Image {
+    pub fn new(width: usize, height: usize, pixels: Vec<Color3>) -> Self {
+        assert_eq!(pixels.len(), width * height, "pixel count must match size");
+        Image {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    /// 8-bit RGB triples of the pixels passed through `display`.
+    pub fn to_rgb8(&self, display: &DisplayTransform) -> Vec<u8> {
+        let quantize = |c: f32| (255.0 * c + 0.5) as u8;
+        self.pixels
+            .iter()
+            .flat_map(|&radiance| {
+                let c = display.apply(radiance);
+                [quantize(c.x), quantize(c.y), quantize(c.z)]
+            })
+            .collect()
+    }
+}
+
+/// Render settings stored alongside the pixels by formats that can hold
+/// text.
+#[derive(Debug, Clone, Default)]
+pub struct RenderMetadata {
+    pub samples_per_pixel: u32,
+    pub max_depth: u32,
+    pub render_time: Duration,
+    pub seed: Option<u64>,
+    pub scene: Option<String>,
+}
+
+impl RenderMetadata {
+    /// Key/value pairs in a stable order.
+    pub fn entries(&self) -> Vec<(&'static str, String)> {
+        let mut entries = vec![
+            (
+                "Software",
+                format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")),
+            ),
+            ("Samples per pixel", self.samples_per_pixel.to_string()),
+            ("Max depth", self.max_depth.to_string()),
+            (
+                "Render time",
+                format!("{:.3} s", self.render_time.as_secs_f64()),
+            ),
+        ];
+        if let Some(seed) = self.seed {
+            entries.push(("Seed", seed.to_string()));
+        }
+        if let Some(scene) = &self.scene {
+            entries.push(("Scene", scene.clone()));
+        }
+        entries
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ImageFormat {
+    /// Plain-text P3 PPM.
+    PlainPpm,
+    /// Binary P6 PPM.
+    Ppm,
+    Png,
+    /// Floating point PFM with the linear radiance.
+    Pfm,
+    /// OpenEXR with the linear radiance.
+    Exr(ExrOptions),
+}
+
+impl ImageFormat {
+    /// Whether the format keeps the linear radiance instead of clamped
+    /// display values.
+    pub fn is_hdr(&self) -> bool {
+        matches!(self, Self::Pfm | Self::Exr(_))
+    }
+
+    /// Format implied by the extension of `path`, case-insensitively.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "ppm" => Some(Self::Ppm),
+            "png" => Some(Self::Png),
+            "pfm" => Some(Self::Pfm),
+            "exr" => Some(Self::Exr(ExrOptions::default())),
+            _ => None,
+        }
+    }
+
+    /// Encodes `image`, going through `display` unless the format keeps
+    /// the linear radiance.
+    pub fn encode(
+        &self,
+        writer: impl Write,
+        image: &Image,
+        metadata: &RenderMetadata,
+        display: &DisplayTransform,
+    ) -> io::Result<()> {
+        match self {
+            Self::PlainPpm => write_ppm(writer, image, metadata, display, false),
+            Self::Ppm => write_ppm(writer, image, metadata, display, true),
+            Self::Png => write_png(writer, image, metadata, display),
+            Self::Pfm => write_pfm(writer, image),
+            Self::Exr(options) => write_exr(writer, image, metadata, *options),
+        }
+    }
+}
+
+/// Writes `image` to `path` in `format`, or in the format implied by the
+/// file extension when none is given.
+pub fn write_image(
+    path: &Path,
+    format: Option<ImageFormat>,
+    image: &Image,
+    metadata: &RenderMetadata,
+    display: &DisplayTransform,
+) -> io::Result<()> {
+    let format = format
+        .or_else(|| ImageFormat::from_path(path))
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "cannot tell the image format from the file extension, expected .png, .ppm, .exr or .pfm",
+            )
+        })?;
+
+    let mut writer = BufWriter::new(File::create(path)?);
+    format.encode(&mut writer, image, metadata, display)?;
+    writer.flush()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_format_from_extension() {
+        assert_eq!(
+            ImageFormat::from_path(Path::new("out/render.PNG")),
+            Some(ImageFormat::Png)
+        );
+        assert_eq!(
+            ImageFormat::from_path(Path::new("image.ppm")),
+            Some(ImageFormat::Ppm)
+        );
+        assert_eq!(
+            ImageFormat::from_path(Path::new("beauty.exr")),
+            Some(ImageFormat::Exr(ExrOptions::default()))
+        );
+        assert!(ImageFormat::from_path(Path::new("beauty.pfm"))
+            .unwrap()
+            .is_hdr());
+        assert_eq!(ImageFormat::from_path(Path::new("image.jpg")), None);
+        assert_eq!(ImageFormat::from_path(Path::new("image")), None);
+    }
+
+    #[test]
+    fn test_to_rgb8_applies_display_transform() {
+        let image = Image::new(
+            2,
+            1,
+            vec![Color3::new(-1.0, 0.25, 4.0), Color3::new(1.0, 0.0, 0.0625)],
+        );
+        let gamma2 = DisplayTransform {
+            gamma: 2.0,
+            ..Default::default()
+        };
+        assert_eq!(image.to_rgb8(&gamma2), vec![0, 128, 255, 255, 0, 64]);
+        let srgb = DisplayTransform::default();
+        assert_eq!(image.to_rgb8(&srgb)[1], 137);
+    }
+}
@@ -0,0 +1,6 @@
+pub mod exr;
+pub mod image;
+pub mod pfm;
+pub mod png;
+pub mod ppm;
+pub mod tonemap;
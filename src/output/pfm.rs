@@ -0,0 +1,47 @@
+use std::io::{self, Write};
+
+use crate::output::image::Image;
+
+/// Writes a little-endian color PFM. Rows are stored bottom to top.
+pub fn write_pfm(mut writer: impl Write, image: &Image) -> io::Result<()> {
+    // A negative scale marks little-endian samples
+    write!(writer, "PF\n{} {}\n-1.0\n", image.width, image.height)?;
+
+    let mut row_bytes = Vec::with_capacity(image.width * 12);
+    for row in image.pixels.chunks_exact(image.width).rev() {
+        row_bytes.clear();
+        for pixel in row {
+            for value in [pixel.x, pixel.y, pixel.z] {
+                row_bytes.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        writer.write_all(&row_bytes)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::vec3::color3::Color3;
+
+    #[test]
+    fn test_pfm_layout() {
+        let image = Image::new(
+            1,
+            2,
+            vec![Color3::new(4.0, 0.0, 0.0), Color3::new(0.0, 0.5, 0.0)],
+        );
+        let mut bytes = Vec::new();
+        write_pfm(&mut bytes, &image).unwrap();
+
+        let header = b"PF\n1 2\n-1.0\n";
+        assert_eq!(&bytes[..header.len()], header);
+        let samples: Vec<f32> = bytes[header.len()..]
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes(b.try_into().unwrap()))
+            .collect();
+        // Bottom row first, values unclamped
+        assert_eq!(samples, vec![0.0, 0.5, 0.0, 4.0, 0.0, 0.0]);
+    }
+}
@@ -0,0 +1,103 @@
+use std::io::{self, Write};
+
+use crate::output::{
+    image::{Image, RenderMetadata},
+    tonemap::DisplayTransform,
+};
+
+/// Writes an 8-bit RGB PNG of `image` passed through `display`, with the
+/// metadata in tEXt chunks, or iTXt chunks for values outside Latin-1,
+/// such as scene paths in other scripts.
+pub fn write_png(
+    writer: impl Write,
+    image: &Image,
+    metadata: &RenderMetadata,
+    display: &DisplayTransform,
+) -> io::Result<()> {
+    let mut encoder = ::png::Encoder::new(writer, image.width as u32, image.height as u32);
+    encoder.set_color(::png::ColorType::Rgb);
+    encoder.set_depth(::png::BitDepth::Eight);
+    if display.gamma > 0.0 {
+        encoder.set_source_gamma(::png::ScaledFloat::new(1.0 / display.gamma));
+    } else {
+        encoder.set_source_srgb(::png::SrgbRenderingIntent::Perceptual);
+    }
+    for (key, value) in metadata.entries() {
+        if value.chars().all(|c| c <= '\u{ff}') {
+            encoder.add_text_chunk(key.to_string(), value)?;
+        } else {
+            encoder.add_itxt_chunk(key.to_string(), value)?;
+        }
+    }
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&image.to_rgb8(display))?;
+    writer.finish()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::vec3::color3::Color3;
+    use std::time::Duration;
+
+    #[test]
+    fn test_png_round_trip_with_metadata() {
+        let image = Image::new(
+            1,
+            2,
+            vec![Color3::new(1.0, 0.0, 0.0), Color3::new(0.0, 1.0, 0.0)],
+        );
+        let metadata = RenderMetadata {
+            samples_per_pixel: 64,
+            max_depth: 12,
+            render_time: Duration::from_millis(1500),
+            seed: Some(7),
+            scene: None,
+        };
+        let mut bytes = Vec::new();
+        write_png(&mut bytes, &image, &metadata, &DisplayTransform::default()).unwrap();
+
+        let decoder = ::png::Decoder::new(bytes.as_slice());
+        let mut reader = decoder.read_info().unwrap();
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buffer).unwrap();
+        assert_eq!((info.width, info.height), (1, 2));
+        assert_eq!(&buffer[..6], &[255, 0, 0, 0, 255, 0]);
+
+        let text = &reader.info().uncompressed_latin1_text;
+        let value = |key: &str| {
+            text.iter()
+                .find(|chunk| chunk.keyword == key)
+                .map(|chunk| chunk.text.clone())
+        };
+        assert_eq!(value("Samples per pixel").as_deref(), Some("64"));
+        assert_eq!(value("Max depth").as_deref(), Some("12"));
+        assert_eq!(value("Render time").as_deref(), Some("1.500 s"));
+        assert_eq!(value("Seed").as_deref(), Some("7"));
+    }
+
+    #[test]
+    fn test_png_with_non_latin1_scene_path() {
+        let image = Image::new(1, 1, vec![Color3::new(0.5, 0.5, 0.5)]);
+        let metadata = RenderMetadata {
+            samples_per_pixel: 1,
+            max_depth: 1,
+            render_time: Duration::ZERO,
+            seed: None,
+            scene: Some("scenes/場景 🌫.toml".to_string()),
+        };
+        let mut bytes = Vec::new();
+        write_png(&mut bytes, &image, &metadata, &DisplayTransform::default()).unwrap();
+
+        let reader = ::png::Decoder::new(bytes.as_slice()).read_info().unwrap();
+        let scene = reader
+            .info()
+            .utf8_text
+            .iter()
+            .find(|chunk| chunk.keyword == "Scene")
+            .unwrap();
+        assert_eq!(scene.get_text().unwrap(), "scenes/場景 🌫.toml");
+    }
+}
@@ -0,0 +1,81 @@
+use std::io::{self, Write};
+
+use crate::output::{
+    image::{Image, RenderMetadata},
+    tonemap::DisplayTransform,
+};
+
+/// Writes a binary P6 PPM, or a plain-text P3 one when `binary` is false.
+/// The metadata goes into header comments.
+pub fn write_ppm(
+    mut writer: impl Write,
+    image: &Image,
+    metadata: &RenderMetadata,
+    display: &DisplayTransform,
+    binary: bool,
+) -> io::Result<()> {
+    writeln!(writer, "{}", if binary { "P6" } else { "P3" })?;
+    for (key, value) in metadata.entries() {
+        writeln!(writer, "# {}: {}", key, value)?;
+    }
+    writeln!(writer, "{} {}", image.width, image.height)?;
+    writeln!(writer, "255")?;
+
+    let rgb = image.to_rgb8(display);
+    if binary {
+        writer.write_all(&rgb)
+    } else {
+        for pixel in rgb.chunks_exact(3) {
+            writeln!(writer, "{} {} {}", pixel[0], pixel[1], pixel[2])?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::vec3::color3::Color3;
+
+    fn image() -> Image {
+        Image::new(
+            2,
+            1,
+            vec![Color3::new(1.0, 0.0, 0.0), Color3::new(0.0, 0.0, 1.0)],
+        )
+    }
+
+    #[test]
+    fn test_binary_ppm() {
+        let mut bytes = Vec::new();
+        write_ppm(
+            &mut bytes,
+            &image(),
+            &RenderMetadata::default(),
+            &DisplayTransform::default(),
+            true,
+        )
+        .unwrap();
+        let header_end = bytes.len() - 6;
+        let header = std::str::from_utf8(&bytes[..header_end]).unwrap();
+        assert!(header.starts_with("P6\n# Software: "));
+        assert!(header.ends_with("2 1\n255\n"));
+        assert_eq!(&bytes[header_end..], &[255, 0, 0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn test_plain_ppm() {
+        let mut bytes = Vec::new();
+        write_ppm(
+            &mut bytes,
+            &image(),
+            &RenderMetadata::default(),
+            &DisplayTransform::default(),
+            false,
+        )
+        .unwrap();
+        let text = String::from_utf8(bytes).unwrap();
+        assert!(text.starts_with("P3\n"));
+        assert!(text.ends_with("2 1\n255\n255 0 0\n0 0 255\n"));
+    }
+}
@@ -0,0 +1,181 @@
+use crate::vec3::color3::Color3;
+
+/// Operator compressing linear radiance into the displayable [0, 1] range.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub enum ToneMapper {
+    /// Cuts every channel off at 1.
+    #[default]
+    Clamp,
+    /// `L / (1 + L)` on luminance, keeping the hue.
+    Reinhard,
+    /// Reinhard with luminance `white` and above mapped to 1.
+    ExtendedReinhard { white: f32 },
+    /// Narkowicz's fit of the ACES filmic curve.
+    Aces,
+    /// John Hable's filmic curve from Uncharted 2.
+    Uncharted2,
+}
+
+impl ToneMapper {
+    pub fn apply(&self, color: Color3) -> Color3 {
+        match self {
+            Self::Clamp => color,
+            Self::Reinhard => scale_luminance(color, |l| l / (1.0 + l)),
+            Self::ExtendedReinhard { white } => {
+                let white_sq = white * white;
+                scale_luminance(color, |l| l * (1.0 + l / white_sq) / (1.0 + l))
+            }
+            Self::Aces => map_channels(color, |x| {
+                (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14)
+            }),
+            Self::Uncharted2 => {
+                const EXPOSURE_BIAS: f32 = 2.0;
+                const WHITE: f32 = 11.2;
+                let white_scale = 1.0 / hable(WHITE);
+                map_channels(color, |x| hable(EXPOSURE_BIAS * x) * white_scale)
+            }
+        }
+    }
+}
+
+/// Rec. 709 luminance of a linear color.
+#[inline]
+pub fn luminance(color: Color3) -> f32 {
+    0.2126 * color.x + 0.7152 * color.y + 0.0722 * color.z
+}
+
+#[inline]
+fn scale_luminance(color: Color3, curve: impl Fn(f32) -> f32) -> Color3 {
+    let l = luminance(color);
+    if l <= 0.0 {
+        return Color3::new(0.0, 0.0, 0.0);
+    }
+    color * (curve(l) / l)
+}
+
+#[inline]
+fn map_channels(color: Color3, curve: impl Fn(f32) -> f32) -> Color3 {
+    Color3::new(curve(color.x), curve(color.y), curve(color.z))
+}
+
+#[inline]
+fn hable(x: f32) -> f32 {
+    const A: f32 = 0.15;
+    const B: f32 = 0.50;
+    const C: f32 = 0.10;
+    const D: f32 = 0.20;
+    const E: f32 = 0.02;
+    const F: f32 = 0.30;
+    ((x * (A * x + C * B) + D * E) / (x * (A * x + B) + D * F)) - E / F
+}
+
+/// Linear to sRGB-encoded value per IEC 61966-2-1.
+#[inline]
+pub fn srgb_encode(c: f32) -> f32 {
+    if c <= 0.003_130_8 {
+        12.92 * c
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Turns linear radiance into display values: exposure, tone mapping, then
+/// the transfer function.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct DisplayTransform {
+    pub tone_mapper: ToneMapper,
+    /// Exposure adjustment in stops; each one doubles the brightness.
+    pub exposure: f32,
+    /// Display gamma, or 0 for the sRGB transfer function.
+    pub gamma: f32,
+}
+
+impl DisplayTransform {
+    /// Display value of `radiance`, each channel in [0, 1].
+    pub fn apply(&self, radiance: Color3) -> Color3 {
+        let exposed = radiance * self.exposure.exp2();
+        let mapped = self.tone_mapper.apply(exposed);
+        let encode = |c: f32| {
+            let c = c.clamp(0.0, 1.0);
+            if self.gamma > 0.0 {
+                c.powf(1.0 / self.gamma)
+            } else {
+                srgb_encode(c)
+            }
+        };
+        map_channels(mapped, encode)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const MAPPERS: [ToneMapper; 5] = [
+        ToneMapper::Clamp,
+        ToneMapper::Reinhard,
+        ToneMapper::ExtendedReinhard { white: 4.0 },
+        ToneMapper::Aces,
+        ToneMapper::Uncharted2,
+    ];
+
+    fn grey(v: f32) -> Color3 {
+        Color3::new(v, v, v)
+    }
+
+    #[test]
+    fn test_mappers_are_monotonic_and_bounded() {
+        let transform = |tone_mapper| DisplayTransform {
+            tone_mapper,
+            ..Default::default()
+        };
+        for mapper in MAPPERS {
+            let mut previous = -1.0;
+            for i in 0..=200 {
+                let value = transform(mapper).apply(grey(i as f32 * 0.1)).x;
+                assert!((0.0..=1.0).contains(&value), "{:?}", mapper);
+                assert!(value >= previous, "{:?} at {}", mapper, i);
+                previous = value;
+            }
+            assert!(transform(mapper).apply(grey(0.0)).x < 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_reinhard_curves() {
+        assert!((ToneMapper::Reinhard.apply(grey(1.0)).x - 0.5).abs() < 1e-6);
+        // The white point maps to exactly 1
+        let extended = ToneMapper::ExtendedReinhard { white: 4.0 };
+        assert!((extended.apply(grey(4.0)).x - 1.0).abs() < 1e-6);
+        // Hue survives, unlike per-channel curves
+        let color = ToneMapper::Reinhard.apply(Color3::new(4.0, 2.0, 1.0));
+        assert!((color.x / color.y - 2.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_filmic_curves_saturate() {
+        assert!(ToneMapper::Aces.apply(grey(100.0)).x > 0.99);
+        assert!((ToneMapper::Uncharted2.apply(grey(5.6)).x - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_exposure_and_transfer() {
+        let srgb = DisplayTransform::default();
+        // sRGB encodes mid grey 0.18 to about 0.46, not gamma 2's 0.42
+        assert!((srgb.apply(grey(0.18)).x - 0.4614).abs() < 1e-3);
+        assert!((srgb.apply(grey(0.001)).x - 0.01292).abs() < 1e-7);
+
+        let one_stop_down = DisplayTransform {
+            exposure: -1.0,
+            gamma: 1.0,
+            ..Default::default()
+        };
+        assert_eq!(one_stop_down.apply(grey(0.5)).x, 0.25);
+
+        let gamma2 = DisplayTransform {
+            gamma: 2.0,
+            ..Default::default()
+        };
+        assert_eq!(gamma2.apply(grey(0.25)).x, 0.5);
+    }
+}
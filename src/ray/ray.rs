@@ -4,6 +4,9 @@ use crate::vec3::vec3::Point3;
 pub struct Ray {
     origin: Point3,
     direction: Point3,
+    /// Moment within the shutter interval the ray samples; moving objects
+    /// are intersected where they are at this time.
+    time: f32,
 }
 
 impl Ray {
@@ -11,9 +14,15 @@ impl Ray {
         Ray {
             origin: ori,
             direction: dir,
+            time: 0.0,
         }
     }
 
+    pub fn with_time(mut self, time: f32) -> Self {
+        self.time = time;
+        self
+    }
+
     pub fn at(&self, t: f32) -> Point3 {
         self.origin + t * self.direction
     }
@@ -25,4 +34,8 @@ impl Ray {
     pub fn direction(&self) -> Point3 {
         self.direction
     }
+
+    pub fn time(&self) -> f32 {
+        self.time
+    }
 }
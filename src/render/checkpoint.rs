@@ -0,0 +1,328 @@
+//! Saved state of an unfinished progressive render.
+//!
+//! A checkpoint holds the accumulated film, with every pixel's weighted
+//! sums and sample statistics stored bit for bit, so resuming from it gives
+//! exactly the image an uninterrupted render would have.
+
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+use crate::{
+    camera::camera::Camera,
+    render::{
+        film::{Film, FilmPixel, PixelSamples},
+        filter::{Filter, FilterKind},
+    },
+    sampler::sampler::SamplerKind,
+    vec3::color3::Color3,
+};
+
+const MAGIC: &[u8] = b"RTCHECKPOINT\n";
+const VERSION: u32 = 2;
+
+/// Largest film a checkpoint may hold, so a corrupt header can't make
+/// decoding allocate without bound. Enough for 16k x 16k.
+const MAX_PIXELS: usize = 1 << 28;
+
+// Stored by their index in these tables
+const FILTER_KINDS: [FilterKind; 5] = [
+    FilterKind::Box,
+    FilterKind::Tent,
+    FilterKind::Gaussian,
+    FilterKind::Mitchell,
+    FilterKind::Lanczos,
+];
+const SAMPLER_KINDS: [SamplerKind; 4] = [
+    SamplerKind::Independent,
+    SamplerKind::Stratified,
+    SamplerKind::Halton,
+    SamplerKind::Sobol,
+];
+
+/// Progress of a render split into passes of `pass_samples` samples per
+/// pixel, along with the settings that decide which samples the passes
+/// take and how they are filtered.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Checkpoint {
+    pub seed: u64,
+    pub samples_per_pixel: u32,
+    pub pass_samples: u32,
+    pub filter: Filter,
+    pub sampler: SamplerKind,
+    pub tile_size: usize,
+    /// Passes already accumulated into `film`.
+    pub passes_done: u32,
+    pub film: Film,
+}
+
+impl Checkpoint {
+    /// Start of a render of `camera`'s view in tiles of `tile_size` pixels.
+    pub fn new(camera: &Camera, seed: u64, pass_samples: u32, tile_size: usize) -> Self {
+        Checkpoint {
+            seed,
+            samples_per_pixel: camera.samples_per_pixel,
+            pass_samples,
+            filter: camera.filter,
+            sampler: camera.sampler,
+            tile_size,
+            passes_done: 0,
+            film: Film::new(camera.image_width as usize, camera.image_height as usize),
+        }
+    }
+
+    pub fn pass_count(&self) -> u32 {
+        self.samples_per_pixel.div_ceil(self.pass_samples)
+    }
+
+    /// Whether a render with `camera` in tiles of `tile_size` pixels can
+    /// continue from this checkpoint.
+    pub fn check_compatible(&self, camera: &Camera, tile_size: usize) -> Result<(), String> {
+        let (width, height) = (camera.image_width as usize, camera.image_height as usize);
+        if (self.film.width, self.film.height) != (width, height) {
+            return Err(format!(
+                "checkpoint is {}x{} but the camera renders {}x{}",
+                self.film.width, self.film.height, width, height
+            ));
+        }
+        if self.samples_per_pixel != camera.samples_per_pixel {
+            return Err(format!(
+                "checkpoint takes {} samples per pixel but the camera takes {}",
+                self.samples_per_pixel, camera.samples_per_pixel
+            ));
+        }
+        if self.filter != camera.filter {
+            return Err(format!(
+                "checkpoint uses a {:?} filter of radius {} but the camera uses a {:?} filter of radius {}",
+                self.filter.kind, self.filter.radius, camera.filter.kind, camera.filter.radius
+            ));
+        }
+        if self.sampler != camera.sampler {
+            return Err(format!(
+                "checkpoint uses the {:?} sampler but the camera uses the {:?} sampler",
+                self.sampler, camera.sampler
+            ));
+        }
+        // Filters wider than a pixel make the result depend on the tiling
+        if self.tile_size != tile_size {
+            return Err(format!(
+                "checkpoint renders tiles of {} pixels but the renderer uses {}",
+                self.tile_size, tile_size
+            ));
+        }
+        Ok(())
+    }
+
+    /// Writes the checkpoint next to `path` first and then moves it into
+    /// place, so a crash while writing leaves the previous one intact.
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let mut temporary = path.as_os_str().to_owned();
+        temporary.push(".tmp");
+        {
+            let mut writer = BufWriter::new(File::create(&temporary)?);
+            self.encode(&mut writer)?;
+            writer.flush()?;
+        }
+        fs::rename(&temporary, path)
+    }
+
+    pub fn read(path: &Path) -> io::Result<Checkpoint> {
+        Self::decode(&mut BufReader::new(File::open(path)?))
+    }
+
+    fn encode(&self, writer: &mut impl Write) -> io::Result<()> {
+        writer.write_all(MAGIC)?;
+        for value in [
+            VERSION,
+            self.film.width as u32,
+            self.film.height as u32,
+            self.samples_per_pixel,
+            self.pass_samples,
+            self.passes_done,
+            index_of(&FILTER_KINDS, self.filter.kind),
+            self.filter.radius.to_bits(),
+            index_of(&SAMPLER_KINDS, self.sampler),
+            self.tile_size as u32,
+        ] {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+        writer.write_all(&self.seed.to_le_bytes())?;
+
+        for (pixel, samples) in self.film.pixels.iter().zip(&self.film.samples) {
+            let sum = pixel.weighted_sum;
+            for value in [sum.x, sum.y, sum.z, pixel.weight_sum] {
+                writer.write_all(&value.to_le_bytes())?;
+            }
+            writer.write_all(&samples.count.to_le_bytes())?;
+            writer.write_all(&samples.mean.to_le_bytes())?;
+            writer.write_all(&samples.m2.to_le_bytes())?;
+            writer.write_all(&[samples.converged as u8])?;
+        }
+        Ok(())
+    }
+
+    fn decode(reader: &mut impl Read) -> io::Result<Checkpoint> {
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message);
+
+        let mut magic = [0; MAGIC.len()];
+        reader.read_exact(&mut magic)?;
+        if magic != MAGIC {
+            return Err(invalid("not a render checkpoint"));
+        }
+        if read_u32(reader)? != VERSION {
+            return Err(invalid("unsupported checkpoint version"));
+        }
+        let width = read_u32(reader)? as usize;
+        let height = read_u32(reader)? as usize;
+        let samples_per_pixel = read_u32(reader)?;
+        let pass_samples = read_u32(reader)?;
+        let passes_done = read_u32(reader)?;
+        let filter_kind = FILTER_KINDS.get(read_u32(reader)? as usize).copied();
+        let filter_radius = read_f32(reader)?;
+        let sampler = SAMPLER_KINDS.get(read_u32(reader)? as usize).copied();
+        let tile_size = read_u32(reader)? as usize;
+        let mut seed = [0; 8];
+        reader.read_exact(&mut seed)?;
+        if pass_samples == 0 || samples_per_pixel == 0 {
+            return Err(invalid("checkpoint has no samples per pass"));
+        }
+        let (Some(filter_kind), Some(sampler)) = (filter_kind, sampler) else {
+            return Err(invalid("unknown filter or sampler in checkpoint"));
+        };
+        if !(filter_radius > 0.0 && filter_radius.is_finite()) || tile_size == 0 {
+            return Err(invalid("invalid filter radius or tile size in checkpoint"));
+        }
+        if width
+            .checked_mul(height)
+            .is_none_or(|pixels| pixels > MAX_PIXELS)
+        {
+            return Err(invalid("checkpoint film is too large"));
+        }
+
+        let mut film = Film::new(width, height);
+        for (pixel, samples) in film.pixels.iter_mut().zip(film.samples.iter_mut()) {
+            *pixel = FilmPixel {
+                weighted_sum: Color3::new(read_f32(reader)?, read_f32(reader)?, read_f32(reader)?),
+                weight_sum: read_f32(reader)?,
+            };
+            let count = read_u32(reader)?;
+            let mean = read_f32(reader)?;
+            let m2 = read_f32(reader)?;
+            let mut converged = [0];
+            reader.read_exact(&mut converged)?;
+            *samples = PixelSamples {
+                count,
+                mean,
+                m2,
+                converged: converged[0] != 0,
+            };
+        }
+
+        Ok(Checkpoint {
+            seed: u64::from_le_bytes(seed),
+            samples_per_pixel,
+            pass_samples,
+            filter: Filter::new(filter_kind, filter_radius),
+            sampler,
+            tile_size,
+            passes_done,
+            film,
+        })
+    }
+}
+
+fn index_of<T: PartialEq>(table: &[T], value: T) -> u32 {
+    table.iter().position(|v| *v == value).unwrap() as u32
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_f32(reader: &mut impl Read) -> io::Result<f32> {
+    read_u32(reader).map(f32::from_bits)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::vec3::vec3::{Point3, Vec3};
+
+    fn camera() -> Camera {
+        Camera::new(
+            3,
+            1.5,
+            64,
+            90.0,
+            10,
+            Point3::default(),
+            Point3::new(0.0, 0.0, -1.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            0.0,
+            1.0,
+        )
+        .with_filter(Filter::new(FilterKind::Mitchell, 2.0))
+        .with_sampler(SamplerKind::Sobol)
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut checkpoint = Checkpoint::new(&camera(), 0x1234_5678_9abc, 16, 8);
+        checkpoint.passes_done = 2;
+        checkpoint.film.pixels[4] = FilmPixel {
+            weighted_sum: Color3::new(0.1, -2.5, f32::MAX),
+            weight_sum: 32.0,
+        };
+        checkpoint.film.samples[4] = PixelSamples {
+            count: 32,
+            mean: 0.7,
+            m2: 1.0e-3,
+            converged: true,
+        };
+
+        let mut bytes = Vec::new();
+        checkpoint.encode(&mut bytes).unwrap();
+        let decoded = Checkpoint::decode(&mut bytes.as_slice()).unwrap();
+        assert_eq!(decoded, checkpoint);
+        assert_eq!(decoded.pass_count(), 4);
+
+        // Cut short, e.g. by a crash while writing
+        bytes.truncate(bytes.len() - 5);
+        assert!(Checkpoint::decode(&mut bytes.as_slice()).is_err());
+        assert!(Checkpoint::decode(&mut &b"P6\n3 2\n255\n"[..]).is_err());
+    }
+
+    #[test]
+    fn test_rejects_huge_film() {
+        let mut bytes = Vec::new();
+        Checkpoint::new(&camera(), 1, 16, 8)
+            .encode(&mut bytes)
+            .unwrap();
+        // Width and height follow the magic and version
+        let size = MAGIC.len() + 4;
+        bytes[size..size + 8].copy_from_slice(&[0xff; 8]);
+        let err = Checkpoint::decode(&mut bytes.as_slice()).unwrap_err();
+        assert!(err.to_string().contains("too large"), "{}", err);
+    }
+
+    #[test]
+    fn test_check_compatible() {
+        let checkpoint = Checkpoint::new(&camera(), 1, 16, 8);
+        checkpoint.check_compatible(&camera(), 8).unwrap();
+
+        let other_filter = camera().with_filter(Filter::new(FilterKind::Mitchell, 1.0));
+        let other_sampler = camera().with_sampler(SamplerKind::Halton);
+        for (camera, tile_size, expected) in [
+            (other_filter, 8, "filter"),
+            (other_sampler, 8, "sampler"),
+            (camera(), 16, "tiles"),
+        ] {
+            let err = checkpoint.check_compatible(&camera, tile_size).unwrap_err();
+            assert!(err.contains(expected), "{}", err);
+        }
+    }
+}
@@ -0,0 +1,259 @@
+use crate::{
+    output::image::Image,
+    render::{filter::Filter, tile::Tile},
+    vec3::color3::Color3,
+};
+
+/// Filter-weighted sum of the samples reaching a pixel.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct FilmPixel {
+    pub weighted_sum: Color3,
+    pub weight_sum: f32,
+}
+
+/// Samples a pixel has taken so far, with the running luminance
+/// statistics adaptive sampling decides on. Kept across passes.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct PixelSamples {
+    pub count: u32,
+    /// Welford's running mean and sum of squared deviations.
+    pub mean: f32,
+    pub m2: f32,
+    /// Set once adaptive sampling stopped the pixel.
+    pub converged: bool,
+}
+
+/// Samples splatted by the pixels of one tile during a pass. Wide filters
+/// spread them into a margin around the tile, overlapping its neighbours.
+#[derive(Debug, Clone)]
+pub struct FilmTile {
+    /// Pixels whose samples this tile holds.
+    pub tile: Tile,
+    /// `tile` grown by the filter margin and clipped to the image.
+    bounds: Tile,
+    filter: Filter,
+    pixels: Vec<FilmPixel>,
+    samples: Vec<PixelSamples>,
+}
+
+impl FilmTile {
+    /// Adds `radiance` seen through film position (`x`, `y`), in pixels
+    /// from the image's top left corner, to every pixel whose filter
+    /// covers it.
+    pub fn add_sample(&mut self, x: f32, y: f32, radiance: Color3) {
+        let radius = self.filter.radius;
+        // Pixel centers sit at half-integer positions
+        let x_min = ((x - 0.5 - radius).ceil().max(0.0) as usize).max(self.bounds.x0);
+        let y_min = ((y - 0.5 - radius).ceil().max(0.0) as usize).max(self.bounds.y0);
+        let x_max = ((x - 0.5 + radius).floor() as isize).min(self.bounds.x1 as isize - 1);
+        let y_max = ((y - 0.5 + radius).floor() as isize).min(self.bounds.y1 as isize - 1);
+
+        for py in y_min as isize..=y_max {
+            for px in x_min as isize..=x_max {
+                let weight = self
+                    .filter
+                    .evaluate(px as f32 + 0.5 - x, py as f32 + 0.5 - y);
+                if weight != 0.0 {
+                    let index = (py as usize - self.bounds.y0) * self.bounds.width()
+                        + (px as usize - self.bounds.x0);
+                    let pixel = &mut self.pixels[index];
+                    pixel.weighted_sum += weight * radiance;
+                    pixel.weight_sum += weight;
+                }
+            }
+        }
+    }
+
+    /// Sample statistics of pixel (`x`, `y`) of the tile.
+    pub fn samples_mut(&mut self, x: usize, y: usize) -> &mut PixelSamples {
+        let index = (y - self.tile.y0) * self.tile.width() + (x - self.tile.x0);
+        &mut self.samples[index]
+    }
+}
+
+/// Accumulated samples of every pixel, which the passes of a render add to
+/// and checkpoints save.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Film {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<FilmPixel>,
+    pub samples: Vec<PixelSamples>,
+}
+
+impl Film {
+    pub fn new(width: usize, height: usize) -> Self {
+        Film {
+            width,
+            height,
+            pixels: vec![FilmPixel::default(); width * height],
+            samples: vec![PixelSamples::default(); width * height],
+        }
+    }
+
+    /// Empty tile for the next pass over `tile`, starting from the sample
+    /// statistics its pixels have so far.
+    pub fn tile(&self, tile: Tile, filter: Filter) -> FilmTile {
+        let margin = filter.margin();
+        let bounds = Tile {
+            x0: tile.x0.saturating_sub(margin),
+            y0: tile.y0.saturating_sub(margin),
+            x1: (tile.x1 + margin).min(self.width),
+            y1: (tile.y1 + margin).min(self.height),
+        };
+        let samples = (tile.y0..tile.y1)
+            .flat_map(|y| &self.samples[y * self.width + tile.x0..y * self.width + tile.x1])
+            .copied()
+            .collect();
+        FilmTile {
+            tile,
+            bounds,
+            filter,
+            pixels: vec![FilmPixel::default(); bounds.pixel_count()],
+            samples,
+        }
+    }
+
+    /// Adds the tiles of a pass.
+    ///
+    /// Neighbouring tiles overlap where wide filters reach across, and
+    /// floating point sums depend on their order, so callers should pass
+    /// them in a fixed order rather than the order threads finished them.
+    pub fn merge<'a>(&mut self, tiles: impl IntoIterator<Item = &'a FilmTile>) {
+        for film_tile in tiles {
+            let bounds = film_tile.bounds;
+            for (row, y) in (bounds.y0..bounds.y1).enumerate() {
+                for (column, x) in (bounds.x0..bounds.x1).enumerate() {
+                    let source = film_tile.pixels[row * bounds.width() + column];
+                    let target = &mut self.pixels[y * self.width + x];
+                    target.weighted_sum += source.weighted_sum;
+                    target.weight_sum += source.weight_sum;
+                }
+            }
+            let tile = film_tile.tile;
+            for (row, y) in (tile.y0..tile.y1).enumerate() {
+                let source = &film_tile.samples[row * tile.width()..(row + 1) * tile.width()];
+                self.samples[y * self.width + tile.x0..y * self.width + tile.x1]
+                    .copy_from_slice(source);
+            }
+        }
+    }
+
+    /// The image reconstructed from the samples so far.
+    pub fn image(&self) -> Image {
+        let pixels = self.pixels.iter().map(resolve).collect();
+        Image::new(self.width, self.height, pixels)
+    }
+
+    pub fn sample_counts(&self) -> Vec<u32> {
+        self.samples.iter().map(|s| s.count).collect()
+    }
+}
+
+/// Radiance of a pixel from its weighted sums; black if no sample reached
+/// it.
+#[inline]
+pub fn resolve(pixel: &FilmPixel) -> Color3 {
+    if pixel.weight_sum != 0.0 {
+        pixel.weighted_sum / pixel.weight_sum
+    } else {
+        Color3::new(0.0, 0.0, 0.0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::render::filter::FilterKind;
+
+    fn whole(width: usize, height: usize) -> Tile {
+        Tile {
+            x0: 0,
+            y0: 0,
+            x1: width,
+            y1: height,
+        }
+    }
+
+    #[test]
+    fn test_box_filter_averages_each_pixel() {
+        let mut film = Film::new(2, 1);
+        let mut film_tile = film.tile(whole(2, 1), Filter::default());
+        film_tile.add_sample(0.25, 0.5, Color3::new(1.0, 0.0, 0.0));
+        film_tile.add_sample(0.75, 0.5, Color3::new(3.0, 0.0, 0.0));
+        film_tile.add_sample(1.5, 0.5, Color3::new(0.0, 2.0, 0.0));
+        film_tile.samples_mut(0, 0).count = 2;
+        film_tile.samples_mut(1, 0).count = 1;
+
+        film.merge([&film_tile]);
+        let image = film.image();
+        assert_eq!(image.pixels[0], Color3::new(2.0, 0.0, 0.0));
+        assert_eq!(image.pixels[1], Color3::new(0.0, 2.0, 0.0));
+        assert_eq!(film.sample_counts(), vec![2, 1]);
+    }
+
+    #[test]
+    fn test_constant_radiance_survives_every_filter() {
+        let grey = Color3::new(0.5, 0.5, 0.5);
+        for kind in [
+            FilterKind::Tent,
+            FilterKind::Gaussian,
+            FilterKind::Mitchell,
+            FilterKind::Lanczos,
+        ] {
+            let filter = Filter::new(kind, kind.default_radius());
+            // Two side by side tiles splatting across their shared edge
+            let mut film = Film::new(8, 4);
+            let tiles: Vec<FilmTile> = [0, 4]
+                .into_iter()
+                .map(|x0| {
+                    let tile = Tile {
+                        x0,
+                        y0: 0,
+                        x1: x0 + 4,
+                        y1: 4,
+                    };
+                    let mut film_tile = film.tile(tile, filter);
+                    for y in 0..4 {
+                        for x in x0..x0 + 4 {
+                            for (dx, dy) in [(0.2, 0.3), (0.7, 0.1), (0.4, 0.8), (0.9, 0.6)] {
+                                film_tile.add_sample(x as f32 + dx, y as f32 + dy, grey);
+                            }
+                        }
+                    }
+                    film_tile
+                })
+                .collect();
+            film.merge(&tiles);
+            for pixel in film.image().pixels {
+                assert!((pixel - grey).length() < 1e-5, "{:?}: {:?}", kind, pixel);
+            }
+        }
+    }
+
+    #[test]
+    fn test_wide_filter_reaches_neighbours() {
+        let filter = Filter::new(FilterKind::Tent, 1.0);
+        let mut film = Film::new(3, 1);
+        let mut film_tile = film.tile(whole(3, 1), filter);
+        film_tile.add_sample(1.9, 0.5, Color3::new(1.0, 1.0, 1.0));
+        film.merge([&film_tile]);
+        assert_eq!(film.pixels[0].weight_sum, 0.0);
+        // Centers 0.4 and 0.6 pixels away
+        assert!((film.pixels[1].weight_sum - 0.6).abs() < 1e-6);
+        assert!((film.pixels[2].weight_sum - 0.4).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_passes_accumulate() {
+        let mut film = Film::new(1, 1);
+        for value in [1.0, 3.0] {
+            let mut film_tile = film.tile(whole(1, 1), Filter::default());
+            film_tile.add_sample(0.5, 0.5, Color3::new(value, value, value));
+            film_tile.samples_mut(0, 0).count += 1;
+            film.merge([&film_tile]);
+        }
+        assert_eq!(film.image().pixels[0], Color3::new(2.0, 2.0, 2.0));
+        assert_eq!(film.sample_counts(), vec![2]);
+    }
+}
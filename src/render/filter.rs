@@ -0,0 +1,156 @@
+use crate::utils::constants::PI;
+
+/// Shape of a pixel reconstruction filter.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum FilterKind {
+    /// Every sample within the radius counts the same; with the default
+    /// radius of half a pixel it averages the samples of each pixel.
+    #[default]
+    Box,
+    /// Weights falling off linearly to zero at the radius.
+    Tent,
+    /// Gaussian with a standard deviation of a third of the radius, shifted
+    /// down to reach zero at the radius.
+    Gaussian,
+    /// Mitchell-Netravali cubic with B = C = 1/3. Its small negative lobes
+    /// sharpen edges.
+    Mitchell,
+    /// Sinc windowed by a wider sinc, reaching zero at the radius. Sharpest,
+    /// at the price of some ringing.
+    Lanczos,
+}
+
+impl FilterKind {
+    /// Radius in pixels the filter is usually used with.
+    pub fn default_radius(&self) -> f32 {
+        match self {
+            Self::Box => 0.5,
+            Self::Tent => 1.0,
+            Self::Gaussian => 1.5,
+            Self::Mitchell | Self::Lanczos => 2.0,
+        }
+    }
+}
+
+/// Separable filter weighting each sample by its offset from a pixel's
+/// center.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Filter {
+    pub kind: FilterKind,
+    /// Distance in pixels beyond which samples get no weight.
+    pub radius: f32,
+}
+
+impl Filter {
+    pub fn new(kind: FilterKind, radius: f32) -> Self {
+        assert!(radius > 0.0, "filter radius must be positive");
+        Filter { kind, radius }
+    }
+
+    /// Weight of a sample `dx`, `dy` pixels away from a pixel center.
+    #[inline]
+    pub fn evaluate(&self, dx: f32, dy: f32) -> f32 {
+        self.evaluate_1d(dx) * self.evaluate_1d(dy)
+    }
+
+    /// Pixels on each side of a pixel whose samples can reach it.
+    pub fn margin(&self) -> usize {
+        (self.radius - 0.5).ceil().max(0.0) as usize
+    }
+
+    fn evaluate_1d(&self, x: f32) -> f32 {
+        let x = x.abs();
+        let r = self.radius;
+        if x >= r {
+            return 0.0;
+        }
+        match self.kind {
+            FilterKind::Box => 1.0,
+            FilterKind::Tent => r - x,
+            FilterKind::Gaussian => {
+                let sigma = r / 3.0;
+                let gaussian = |x: f32| (-x * x / (2.0 * sigma * sigma)).exp();
+                (gaussian(x) - gaussian(r)).max(0.0)
+            }
+            FilterKind::Mitchell => {
+                const B: f32 = 1.0 / 3.0;
+                const C: f32 = 1.0 / 3.0;
+                // The cubic is defined over [0, 2]
+                let x = 2.0 * x / r;
+                let value = if x < 1.0 {
+                    (12.0 - 9.0 * B - 6.0 * C) * x * x * x
+                        + (-18.0 + 12.0 * B + 6.0 * C) * x * x
+                        + (6.0 - 2.0 * B)
+                } else {
+                    (-B - 6.0 * C) * x * x * x
+                        + (6.0 * B + 30.0 * C) * x * x
+                        + (-12.0 * B - 48.0 * C) * x
+                        + (8.0 * B + 24.0 * C)
+                };
+                value / 6.0
+            }
+            FilterKind::Lanczos => sinc(x) * sinc(x / r),
+        }
+    }
+}
+
+impl Default for Filter {
+    fn default() -> Self {
+        Filter::new(FilterKind::Box, FilterKind::Box.default_radius())
+    }
+}
+
+#[inline]
+fn sinc(x: f32) -> f32 {
+    if x.abs() < 1e-5 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const KINDS: [FilterKind; 5] = [
+        FilterKind::Box,
+        FilterKind::Tent,
+        FilterKind::Gaussian,
+        FilterKind::Mitchell,
+        FilterKind::Lanczos,
+    ];
+
+    #[test]
+    fn test_filters_peak_at_center_and_vanish_at_radius() {
+        for kind in KINDS {
+            let filter = Filter::new(kind, kind.default_radius());
+            let center = filter.evaluate(0.0, 0.0);
+            assert!(center > 0.0, "{:?}", kind);
+            for i in 1..20 {
+                let x = i as f32 * filter.radius / 20.0;
+                assert!(filter.evaluate(x, 0.0) <= center, "{:?} at {}", kind, x);
+                assert_eq!(filter.evaluate(x, 0.0), filter.evaluate(-x, 0.0));
+            }
+            assert_eq!(filter.evaluate(filter.radius, 0.0), 0.0, "{:?}", kind);
+            assert_eq!(filter.evaluate(0.0, filter.radius + 0.1), 0.0, "{:?}", kind);
+        }
+    }
+
+    #[test]
+    fn test_sharpening_filters_have_negative_lobes() {
+        let mitchell = Filter::new(FilterKind::Mitchell, 2.0);
+        assert!(mitchell.evaluate(1.5, 0.0) < 0.0);
+        let lanczos = Filter::new(FilterKind::Lanczos, 2.0);
+        assert!(lanczos.evaluate(1.5, 0.0) < 0.0);
+        assert!(Filter::new(FilterKind::Tent, 1.0).evaluate(0.5, 0.5) > 0.0);
+    }
+
+    #[test]
+    fn test_margin() {
+        assert_eq!(Filter::default().margin(), 0);
+        assert_eq!(Filter::new(FilterKind::Tent, 1.0).margin(), 1);
+        assert_eq!(Filter::new(FilterKind::Gaussian, 1.5).margin(), 1);
+        assert_eq!(Filter::new(FilterKind::Mitchell, 2.0).margin(), 2);
+    }
+}
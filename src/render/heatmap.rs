@@ -0,0 +1,38 @@
+use crate::{output::image::Image, vec3::color3::Color3, world::texture::ColorRamp};
+
+/// Visualises where the samples went: black for none, through blue, red and
+/// yellow to white for `max_samples`.
+pub fn sample_heatmap(
+    width: usize,
+    height: usize,
+    sample_counts: &[u32],
+    max_samples: u32,
+) -> Image {
+    assert_eq!(sample_counts.len(), width * height);
+    let ramp = ColorRamp::uniform(&[
+        Color3::new(0.0, 0.0, 0.0),
+        Color3::new(0.05, 0.0, 0.4),
+        Color3::new(0.8, 0.05, 0.05),
+        Color3::new(1.0, 0.6, 0.0),
+        Color3::new(1.0, 1.0, 1.0),
+    ]);
+    let scale = 1.0 / max_samples.max(1) as f32;
+    let pixels = sample_counts
+        .iter()
+        .map(|&count| ramp.sample(count as f32 * scale))
+        .collect();
+    Image::new(width, height, pixels)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_heatmap_ends() {
+        let heatmap = sample_heatmap(3, 1, &[0, 50, 100], 100);
+        assert_eq!(heatmap.pixels[0], Color3::new(0.0, 0.0, 0.0));
+        assert_eq!(heatmap.pixels[2], Color3::new(1.0, 1.0, 1.0));
+        assert_eq!(heatmap.pixels[1], Color3::new(0.8, 0.05, 0.05));
+    }
+}
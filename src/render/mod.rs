@@ -0,0 +1,8 @@
+pub mod checkpoint;
+pub mod film;
+pub mod filter;
+pub mod heatmap;
+pub mod progress;
+pub mod renderer;
+pub mod stats;
+pub mod tile;
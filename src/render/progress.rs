@@ -0,0 +1,142 @@
+use std::{
+    io::{self, IsTerminal, Write},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+const BAR_WIDTH: usize = 30;
+const REDRAW_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Progress shared by all render threads, drawn as a bar with an ETA on
+/// stderr.
+#[derive(Debug)]
+pub struct ProgressReporter {
+    total: u64,
+    done: AtomicU64,
+    start: Instant,
+    last_draw: Mutex<Option<Instant>>,
+    visible: bool,
+}
+
+impl ProgressReporter {
+    /// Tracks `total` units of work. Nothing is drawn unless `visible` is
+    /// set and stderr is a terminal, so logs don't fill up with redraws.
+    pub fn new(total: u64, visible: bool) -> Self {
+        ProgressReporter {
+            total,
+            done: AtomicU64::new(0),
+            start: Instant::now(),
+            last_draw: Mutex::new(None),
+            visible: visible && io::stderr().is_terminal(),
+        }
+    }
+
+    pub fn advance(&self, amount: u64) {
+        let done = self.done.fetch_add(amount, Ordering::Relaxed) + amount;
+        if !self.visible {
+            return;
+        }
+
+        // Only one thread redraws, and not more often than needed
+        let Ok(mut last_draw) = self.last_draw.try_lock() else {
+            return;
+        };
+        let now = Instant::now();
+        if last_draw.is_some_and(|t| now - t < REDRAW_INTERVAL) && done < self.total {
+            return;
+        }
+        *last_draw = Some(now);
+        eprint!("\r{}", progress_line(done, self.total, now - self.start));
+        let _ = io::stderr().flush();
+    }
+
+    /// Ends the bar's line so later output starts on a fresh one.
+    pub fn finish(&self) {
+        if self.visible {
+            let done = self.done.load(Ordering::Relaxed);
+            eprintln!(
+                "\r{}",
+                progress_line(done, self.total, self.start.elapsed())
+            );
+        }
+    }
+}
+
+/// `[=======>      ]  52.0%  ETA 1m 05s`, extrapolating the ETA from the
+/// average rate so far.
+fn progress_line(done: u64, total: u64, elapsed: Duration) -> String {
+    let fraction = if total > 0 {
+        (done as f64 / total as f64).min(1.0)
+    } else {
+        1.0
+    };
+    let filled = (fraction * BAR_WIDTH as f64) as usize;
+    let bar: String = (0..BAR_WIDTH)
+        .map(|i| match i.cmp(&filled) {
+            std::cmp::Ordering::Less => '=',
+            std::cmp::Ordering::Equal => '>',
+            std::cmp::Ordering::Greater => ' ',
+        })
+        .collect();
+
+    let eta = if fraction >= 1.0 {
+        format!("done in {}", format_duration(elapsed))
+    } else if done == 0 {
+        "ETA --".to_string()
+    } else {
+        let remaining = elapsed.as_secs_f64() * (1.0 - fraction) / fraction;
+        format!(
+            "ETA {}",
+            format_duration(Duration::from_secs_f64(remaining))
+        )
+    };
+    format!("[{}] {:5.1}%  {:<16}", bar, fraction * 100.0, eta)
+}
+
+fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    match seconds {
+        0..=59 => format!("{}s", seconds),
+        60..=3599 => format!("{}m {:02}s", seconds / 60, seconds % 60),
+        _ => format!("{}h {:02}m", seconds / 3600, seconds % 3600 / 60),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(Duration::from_secs(42)), "42s");
+        assert_eq!(format_duration(Duration::from_secs(65)), "1m 05s");
+        assert_eq!(format_duration(Duration::from_secs(7322)), "2h 02m");
+    }
+
+    #[test]
+    fn test_progress_line() {
+        let line = progress_line(25, 100, Duration::from_secs(10));
+        assert!(line.starts_with("[=======>      "), "{}", line);
+        assert!(line.contains(" 25.0%"), "{}", line);
+        assert!(line.contains("ETA 30s"), "{}", line);
+
+        let line = progress_line(0, 100, Duration::ZERO);
+        assert!(line.contains("ETA --"), "{}", line);
+        let line = progress_line(100, 100, Duration::from_secs(75));
+        assert!(line.contains("100.0%  done in 1m 15s"), "{}", line);
+    }
+
+    #[test]
+    fn test_counts_across_threads() {
+        let progress = ProgressReporter::new(400, false);
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| (0..100).for_each(|_| progress.advance(1)));
+            }
+        });
+        assert_eq!(progress.done.load(Ordering::Relaxed), 400);
+    }
+}
@@ -0,0 +1,333 @@
+use std::{
+    path::PathBuf,
+    sync::Mutex,
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    camera::camera::Camera,
+    output::image::Image,
+    render::{
+        checkpoint::Checkpoint,
+        film::FilmTile,
+        progress::ProgressReporter,
+        stats::{RayStats, RenderStats, ThreadStats},
+        tile::TileQueue,
+    },
+    world::scene_object_list::SceneObjectList,
+};
+
+/// Renders a camera's view of a world on a pool of threads pulling tiles
+/// from a shared queue, in passes that each add some samples to every
+/// pixel.
+pub struct Renderer<'a> {
+    camera: &'a Camera,
+    world: &'a SceneObjectList,
+    threads: usize,
+    tile_size: usize,
+    seed: u64,
+    progress: bool,
+    pass_samples: Option<u32>,
+    checkpoint: Option<(PathBuf, Duration)>,
+    resume: Option<Checkpoint>,
+}
+
+/// Passes a checkpointed render is split into unless told otherwise.
+const DEFAULT_CHECKPOINT_PASSES: u32 = 16;
+
+impl<'a> Renderer<'a> {
+    pub fn new(camera: &'a Camera, world: &'a SceneObjectList) -> Self {
+        Renderer {
+            camera,
+            world,
+            threads: 1,
+            tile_size: 32,
+            seed: 0,
+            progress: false,
+            pass_samples: None,
+            checkpoint: None,
+            resume: None,
+        }
+    }
+
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    pub fn with_tile_size(mut self, tile_size: usize) -> Self {
+        self.tile_size = tile_size.max(1);
+        self
+    }
+
+    /// Seed every random decision of the render derives from. The same seed
+    /// gives the same image for any thread count, and for any tile size
+    /// unless the filter reaches beyond the pixel.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Draw a live progress bar with an ETA on stderr while rendering.
+    pub fn with_progress(mut self, progress: bool) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    /// Samples per pixel each pass adds. By default a single pass takes
+    /// them all, or a sixteenth of them when checkpointing, so there are
+    /// passes to save in between.
+    pub fn with_pass_samples(mut self, pass_samples: u32) -> Self {
+        self.pass_samples = Some(pass_samples.max(1));
+        self
+    }
+
+    /// Saves the progress to `path` after every pass that ends at least
+    /// `interval` after the last save.
+    pub fn with_checkpoint(mut self, path: PathBuf, interval: Duration) -> Self {
+        self.checkpoint = Some((path, interval));
+        self
+    }
+
+    /// Continues the render saved in `checkpoint`, with its seed and pass
+    /// size. The result is bit-identical to a render that never stopped.
+    pub fn with_resume(mut self, checkpoint: Checkpoint) -> Self {
+        self.resume = Some(checkpoint);
+        self
+    }
+
+    pub fn render(self) -> (Image, RenderStats) {
+        let start = Instant::now();
+        let width = self.camera.image_width as usize;
+        let height = self.camera.image_height as usize;
+        let samples_per_pixel = self.camera.samples_per_pixel;
+        let mut state = self.resume.unwrap_or_else(|| {
+            let pass_samples = match (self.pass_samples, &self.checkpoint) {
+                (Some(pass_samples), _) => pass_samples.min(samples_per_pixel),
+                (None, Some(_)) => samples_per_pixel.div_ceil(DEFAULT_CHECKPOINT_PASSES),
+                (None, None) => samples_per_pixel,
+            };
+            Checkpoint::new(self.camera, self.seed, pass_samples, self.tile_size)
+        });
+        let pass_count = state.pass_count();
+
+        let tile_count = TileQueue::new(width, height, self.tile_size).len();
+        let remaining_passes = pass_count.saturating_sub(state.passes_done);
+        let progress = ProgressReporter::new(
+            (width * height) as u64 * remaining_passes as u64,
+            self.progress,
+        );
+        let mut last_save = Instant::now();
+
+        // More threads than tiles would only sit idle
+        let threads = self.threads.min(tile_count).max(1);
+        let mut thread_stats = vec![ThreadStats::default(); threads];
+        let mut rays = RayStats::default();
+        while state.passes_done < pass_count {
+            let sample_end = (state.passes_done + 1) * state.pass_samples;
+            let queue = TileQueue::new(width, height, self.tile_size);
+            let tiles = Mutex::new(vec![None; queue.len()]);
+
+            let results: Vec<(ThreadStats, RayStats)> = thread::scope(|scope| {
+                let workers: Vec<_> = (0..threads)
+                    .map(|_| {
+                        let (queue, tiles, progress) = (&queue, &tiles, &progress);
+                        let (film, seed) = (&state.film, state.seed);
+                        scope.spawn(move || {
+                            let mut thread_stats = ThreadStats::default();
+                            let mut ray_stats = RayStats::default();
+                            while let Some((index, tile)) = queue.next_tile() {
+                                let tile_start = Instant::now();
+                                let mut film_tile = film.tile(tile, self.camera.filter);
+                                self.camera.render_tile(
+                                    self.world,
+                                    &mut film_tile,
+                                    seed,
+                                    sample_end,
+                                    &mut ray_stats,
+                                );
+                                tiles.lock().unwrap()[index] = Some(film_tile);
+                                thread_stats.tiles += 1;
+                                thread_stats.busy += tile_start.elapsed();
+                                progress.advance(tile.pixel_count() as u64);
+                            }
+                            (thread_stats, ray_stats)
+                        })
+                    })
+                    .collect();
+                workers
+                    .into_iter()
+                    .map(|worker| worker.join().expect("render thread panicked"))
+                    .collect()
+            });
+            for (total, (pass, pass_rays)) in thread_stats.iter_mut().zip(results) {
+                total.tiles += pass.tiles;
+                total.busy += pass.busy;
+                rays.merge(&pass_rays);
+            }
+
+            // In queue order, whichever thread finished first
+            let tiles: Vec<FilmTile> = tiles.into_inner().unwrap().into_iter().flatten().collect();
+            state.film.merge(&tiles);
+            state.passes_done += 1;
+
+            if let Some((path, interval)) = &self.checkpoint {
+                if state.passes_done < pass_count && last_save.elapsed() >= *interval {
+                    // Losing a checkpoint is no reason to abandon the render
+                    if let Err(err) = state.write(path) {
+                        eprintln!("{}: failed to write checkpoint: {}", path.display(), err);
+                    }
+                    last_save = Instant::now();
+                }
+            }
+        }
+        progress.finish();
+
+        let stats = RenderStats {
+            wall_time: start.elapsed(),
+            rays,
+            threads: thread_stats,
+            sample_counts: state.film.sample_counts(),
+        };
+        (state.film.image(), stats)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::camera::camera::AdaptiveSampling;
+    use crate::render::filter::{Filter, FilterKind};
+    use crate::scene::random_spheres::random_spheres_scene;
+    use crate::vec3::vec3::{Point3, Vec3};
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn small_camera() -> Camera {
+        Camera::new(
+            24,
+            1.5,
+            4,
+            20.0,
+            8,
+            Point3::new(13.0, 2.0, 3.0),
+            Point3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            0.6,
+            10.0,
+        )
+    }
+
+    #[test]
+    fn test_same_seed_is_bit_identical_across_threads() {
+        let mut world = random_spheres_scene(&mut StdRng::seed_from_u64(5));
+        world.build_bvh();
+        let camera = small_camera();
+
+        let render = |threads, tile_size, seed| {
+            Renderer::new(&camera, &world)
+                .with_threads(threads)
+                .with_tile_size(tile_size)
+                .with_seed(seed)
+                .render()
+                .0
+                .pixels
+        };
+        let reference = render(1, 32, 9);
+        assert_eq!(render(3, 5, 9), reference);
+        assert_eq!(render(4, 1, 9), reference);
+        assert_ne!(render(1, 32, 10), reference);
+    }
+
+    #[test]
+    fn test_wide_filter_is_bit_identical_across_threads() {
+        let mut world = random_spheres_scene(&mut StdRng::seed_from_u64(5));
+        world.build_bvh();
+        let camera = small_camera().with_filter(Filter::new(FilterKind::Mitchell, 2.0));
+
+        // Tiles overlap, so only their size fixes the order of the sums
+        let render = |threads| {
+            Renderer::new(&camera, &world)
+                .with_threads(threads)
+                .with_tile_size(4)
+                .render()
+                .0
+                .pixels
+        };
+        let reference = render(1);
+        assert_eq!(render(3), reference);
+        assert_eq!(render(8), reference);
+    }
+
+    #[test]
+    fn test_stats_cover_every_sample() {
+        let mut world = random_spheres_scene(&mut StdRng::seed_from_u64(5));
+        world.build_bvh();
+        let camera = small_camera();
+
+        let (_, stats) = Renderer::new(&camera, &world)
+            .with_threads(3)
+            .with_tile_size(8)
+            .render();
+        let pixels = (camera.image_width * camera.image_height) as u64;
+        assert_eq!(
+            stats.rays.camera_rays,
+            pixels * camera.samples_per_pixel as u64
+        );
+        assert!(stats.rays.rays >= stats.rays.path_segments);
+        assert!(stats.rays.path_segments >= stats.rays.camera_rays);
+        assert!(stats.average_path_length() <= camera.max_depth as f64);
+        assert_eq!(stats.threads.len(), 3);
+        let tiles: usize = stats.threads.iter().map(|t| t.tiles).sum();
+        assert_eq!(tiles, TileQueue::new(24, 16, 8).len());
+    }
+
+    #[test]
+    fn test_resume_matches_uninterrupted_render() {
+        let mut world = random_spheres_scene(&mut StdRng::seed_from_u64(5));
+        world.build_bvh();
+        let camera = small_camera()
+            .with_filter(Filter::new(FilterKind::Gaussian, 1.5))
+            .with_adaptive_sampling(Some(AdaptiveSampling {
+                min_samples: 2,
+                threshold: 0.1,
+            }));
+        let path = std::env::temp_dir().join(format!("resume-{}.ckpt", std::process::id()));
+
+        // Saves after every pass but the last, as if it crashed right then
+        let (uninterrupted, stats) = Renderer::new(&camera, &world)
+            .with_seed(3)
+            .with_pass_samples(1)
+            .with_checkpoint(path.clone(), Duration::ZERO)
+            .render();
+        let checkpoint = Checkpoint::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(checkpoint.passes_done, 3);
+        assert_eq!(checkpoint.seed, 3);
+        checkpoint.check_compatible(&camera, 32).unwrap();
+
+        let (resumed, resumed_stats) = Renderer::new(&camera, &world)
+            .with_threads(2)
+            .with_resume(checkpoint)
+            .render();
+        assert_eq!(resumed.pixels, uninterrupted.pixels);
+        assert_eq!(resumed_stats.sample_counts, stats.sample_counts);
+        // Only the last pass was left to render
+        assert!(resumed_stats.rays.camera_rays < stats.rays.camera_rays / 2);
+
+        // Checkpointing without a pass size still leaves passes to save
+        Renderer::new(&camera, &world)
+            .with_checkpoint(path.clone(), Duration::ZERO)
+            .render();
+        let checkpoint = Checkpoint::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(checkpoint.pass_samples, 1);
+        assert_eq!(checkpoint.passes_done, 3);
+
+        // The same samples taken in one go give the same image up to rounding
+        let (single_pass, _) = Renderer::new(&camera, &world).with_seed(3).render();
+        for (a, b) in single_pass.pixels.iter().zip(&uninterrupted.pixels) {
+            assert!((*a - *b).length() < 1e-4);
+        }
+    }
+}
@@ -0,0 +1,145 @@
+use std::{fmt, time::Duration};
+
+/// Counts of the rays traced while rendering.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct RayStats {
+    /// Paths started, one per pixel sample.
+    pub camera_rays: u64,
+    /// Every ray intersected with the scene, shadow rays included.
+    pub rays: u64,
+    /// Path segments, i.e. camera rays plus scattered rays.
+    pub path_segments: u64,
+}
+
+impl RayStats {
+    pub fn merge(&mut self, other: &RayStats) {
+        self.camera_rays += other.camera_rays;
+        self.rays += other.rays;
+        self.path_segments += other.path_segments;
+    }
+}
+
+/// Work done by one render thread.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct ThreadStats {
+    pub tiles: usize,
+    /// Time spent rendering tiles, as opposed to waiting to finish.
+    pub busy: Duration,
+}
+
+/// Summary of a finished render.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RenderStats {
+    pub wall_time: Duration,
+    pub rays: RayStats,
+    pub threads: Vec<ThreadStats>,
+    /// Samples each pixel took, row by row.
+    pub sample_counts: Vec<u32>,
+}
+
+impl RenderStats {
+    pub fn rays_per_second(&self) -> f64 {
+        let seconds = self.wall_time.as_secs_f64();
+        if seconds > 0.0 {
+            self.rays.rays as f64 / seconds
+        } else {
+            0.0
+        }
+    }
+
+    /// Mean number of segments per camera path.
+    pub fn average_path_length(&self) -> f64 {
+        if self.rays.camera_rays > 0 {
+            self.rays.path_segments as f64 / self.rays.camera_rays as f64
+        } else {
+            0.0
+        }
+    }
+
+    pub fn average_samples(&self) -> f64 {
+        if self.sample_counts.is_empty() {
+            return 0.0;
+        }
+        let total: u64 = self.sample_counts.iter().map(|&n| n as u64).sum();
+        total as f64 / self.sample_counts.len() as f64
+    }
+
+    /// Fraction of the wall time each thread spent rendering.
+    pub fn utilisation(&self) -> Vec<f64> {
+        let wall = self.wall_time.as_secs_f64();
+        self.threads
+            .iter()
+            .map(|t| {
+                if wall > 0.0 {
+                    (t.busy.as_secs_f64() / wall).min(1.0)
+                } else {
+                    0.0
+                }
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for RenderStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Rendered in {:.2} s", self.wall_time.as_secs_f64())?;
+        writeln!(
+            f,
+            "  {} rays traced, {:.2} M rays/s",
+            self.rays.rays,
+            self.rays_per_second() / 1e6
+        )?;
+        write!(
+            f,
+            "  {:.1} samples per pixel, {:.2} segments per path on average",
+            self.average_samples(),
+            self.average_path_length()
+        )?;
+        for (i, (thread, utilisation)) in self.threads.iter().zip(self.utilisation()).enumerate() {
+            write!(
+                f,
+                "\n  thread {}: {} tiles, {:.1}% busy",
+                i,
+                thread.tiles,
+                utilisation * 100.0
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_derived_numbers() {
+        let stats = RenderStats {
+            wall_time: Duration::from_secs(2),
+            rays: RayStats {
+                camera_rays: 100,
+                rays: 1000,
+                path_segments: 350,
+            },
+            threads: vec![
+                ThreadStats {
+                    tiles: 3,
+                    busy: Duration::from_secs(2),
+                },
+                ThreadStats {
+                    tiles: 1,
+                    busy: Duration::from_millis(500),
+                },
+            ],
+            sample_counts: vec![4, 4, 16, 8],
+        };
+        assert_eq!(stats.rays_per_second(), 500.0);
+        assert_eq!(stats.average_path_length(), 3.5);
+        assert_eq!(stats.utilisation(), vec![1.0, 0.25]);
+        assert_eq!(stats.average_samples(), 8.0);
+
+        let text = stats.to_string();
+        assert!(text.contains("1000 rays traced"), "{}", text);
+        assert!(text.contains("thread 1: 1 tiles, 25.0% busy"), "{}", text);
+    }
+}
@@ -0,0 +1,113 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Rectangle of pixels `[x0, x1) x [y0, y1)` rendered as one unit of work.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Tile {
+    pub x0: usize,
+    pub y0: usize,
+    pub x1: usize,
+    pub y1: usize,
+}
+
+impl Tile {
+    pub fn width(&self) -> usize {
+        self.x1 - self.x0
+    }
+
+    pub fn height(&self) -> usize {
+        self.y1 - self.y0
+    }
+
+    pub fn pixel_count(&self) -> usize {
+        self.width() * self.height()
+    }
+}
+
+/// Shared queue of the tiles covering an image. Threads pull the next tile
+/// as soon as they finish one, so cheap regions like the sky don't leave
+/// cores idle while others are still busy.
+#[derive(Debug)]
+pub struct TileQueue {
+    tiles: Vec<Tile>,
+    next: AtomicUsize,
+}
+
+impl TileQueue {
+    /// Splits a `width` x `height` image into `tile_size` squares in
+    /// row-major order, clipping the ones along the right and bottom edges.
+    pub fn new(width: usize, height: usize, tile_size: usize) -> Self {
+        assert!(tile_size > 0, "tile size must be positive");
+        let mut tiles = Vec::new();
+        for y0 in (0..height).step_by(tile_size) {
+            for x0 in (0..width).step_by(tile_size) {
+                tiles.push(Tile {
+                    x0,
+                    y0,
+                    x1: (x0 + tile_size).min(width),
+                    y1: (y0 + tile_size).min(height),
+                });
+            }
+        }
+        TileQueue {
+            tiles,
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.tiles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tiles.is_empty()
+    }
+
+    /// Hands out each tile exactly once across all threads, along with its
+    /// index in row-major order.
+    pub fn next_tile(&self) -> Option<(usize, Tile)> {
+        let index = self.next.fetch_add(1, Ordering::Relaxed);
+        self.tiles.get(index).map(|&tile| (index, tile))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::{sync::Mutex, thread};
+
+    #[test]
+    fn test_tiles_cover_image_once() {
+        let queue = TileQueue::new(70, 33, 32);
+        assert_eq!(queue.len(), 6);
+
+        let mut covered = vec![0; 70 * 33];
+        while let Some((_, tile)) = queue.next_tile() {
+            for y in tile.y0..tile.y1 {
+                for x in tile.x0..tile.x1 {
+                    covered[y * 70 + x] += 1;
+                }
+            }
+        }
+        assert!(covered.iter().all(|&c| c == 1));
+        assert_eq!(queue.next_tile(), None);
+    }
+
+    #[test]
+    fn test_concurrent_pulls_are_disjoint() {
+        let queue = TileQueue::new(256, 256, 8);
+        let taken = Mutex::new(Vec::new());
+        thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    while let Some((index, tile)) = queue.next_tile() {
+                        taken.lock().unwrap().push((index, tile.x0, tile.y0));
+                    }
+                });
+            }
+        });
+        let mut taken = taken.into_inner().unwrap();
+        taken.sort();
+        taken.dedup();
+        assert_eq!(taken.len(), queue.len());
+    }
+}
@@ -0,0 +1,218 @@
+use crate::{
+    sampler::sequence::{
+        bits_to_unit, owen_scramble, owen_scrambled_radical_inverse, permutation_element, sobol_0,
+        sobol_1, ONE_MINUS_EPSILON, PRIMES,
+    },
+    utils::helpers::{derive_seed, random_f32},
+};
+
+/// Sequence the sample points of a pixel are drawn from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SamplerKind {
+    /// Uniform random numbers, each independent of the others.
+    #[default]
+    Independent,
+    /// Jittered grid: each sample takes a random point in its own cell, with
+    /// the cells shuffled independently for every dimension or pair.
+    Stratified,
+    /// Owen-scrambled Halton sequence with a prime base per dimension.
+    /// Dimensions past the table of bases fall back to independent numbers.
+    Halton,
+    /// Owen-scrambled Sobol (0, 2)-sequence. Every dimension or pair reuses
+    /// the first two Sobol dimensions with its own scramble and shuffled
+    /// sample order, so paths of any length stay well distributed.
+    Sobol,
+}
+
+/// Sample points of one pixel. Within a sample, every `get_1d` and `get_2d`
+/// call moves on to the next dimension, so the camera, lens and each bounce
+/// draw from their own part of the sequence.
+#[derive(Debug, Clone)]
+pub struct Sampler {
+    kind: SamplerKind,
+    samples_per_pixel: u32,
+    pixel_seed: u64,
+    sample: u32,
+    dimension: u32,
+}
+
+impl Sampler {
+    pub fn new(kind: SamplerKind, samples_per_pixel: u32) -> Self {
+        Sampler {
+            kind,
+            samples_per_pixel: samples_per_pixel.max(1),
+            pixel_seed: 0,
+            sample: 0,
+            dimension: 0,
+        }
+    }
+
+    /// Starts sample `sample` of the pixel whose scrambles derive from
+    /// `pixel_seed`, which should differ between pixels to decorrelate them.
+    pub fn start_sample(&mut self, pixel_seed: u64, sample: u32) {
+        self.pixel_seed = pixel_seed;
+        self.sample = sample;
+        self.dimension = 0;
+    }
+
+    pub fn get_1d(&mut self) -> f32 {
+        let dimension = self.dimension;
+        self.dimension += 1;
+        let hash = self.hash(dimension);
+        match self.kind {
+            SamplerKind::Independent => random_f32(),
+            SamplerKind::Stratified => {
+                let n = self.samples_per_pixel;
+                let stratum = permutation_element(self.sample, n, hash as u32);
+                ((stratum as f32 + random_f32()) / n as f32).min(ONE_MINUS_EPSILON)
+            }
+            SamplerKind::Halton => self.halton(dimension, hash),
+            SamplerKind::Sobol => {
+                let index = self.shuffled_index(hash);
+                bits_to_unit(owen_scramble(sobol_0(index), (hash >> 32) as u32))
+            }
+        }
+    }
+
+    pub fn get_2d(&mut self) -> (f32, f32) {
+        let dimension = self.dimension;
+        self.dimension += 2;
+        let hash = self.hash(dimension);
+        match self.kind {
+            SamplerKind::Independent => (random_f32(), random_f32()),
+            SamplerKind::Stratified => {
+                // The smallest grid with a cell for every sample
+                let columns = (self.samples_per_pixel as f32).sqrt().ceil() as u32;
+                let rows = self.samples_per_pixel.div_ceil(columns);
+                let cell = permutation_element(self.sample, columns * rows, hash as u32);
+                let x = (cell % columns) as f32 + random_f32();
+                let y = (cell / columns) as f32 + random_f32();
+                (
+                    (x / columns as f32).min(ONE_MINUS_EPSILON),
+                    (y / rows as f32).min(ONE_MINUS_EPSILON),
+                )
+            }
+            SamplerKind::Halton => (
+                self.halton(dimension, hash),
+                self.halton(dimension + 1, self.hash(dimension + 1)),
+            ),
+            SamplerKind::Sobol => {
+                let index = self.shuffled_index(hash);
+                let seed = (hash >> 32) as u32;
+                (
+                    bits_to_unit(owen_scramble(sobol_0(index), seed)),
+                    bits_to_unit(owen_scramble(sobol_1(index), seed ^ 0x9e37_79b9)),
+                )
+            }
+        }
+    }
+
+    #[inline]
+    fn hash(&self, dimension: u32) -> u64 {
+        derive_seed(self.pixel_seed, &[dimension as u64])
+    }
+
+    #[inline]
+    fn halton(&self, dimension: u32, hash: u64) -> f32 {
+        match PRIMES.get(dimension as usize) {
+            Some(&base) => owen_scrambled_radical_inverse(self.sample, base, hash as u32),
+            None => random_f32(),
+        }
+    }
+
+    /// Sample order of the current dimension. Shuffling it per dimension
+    /// keeps the dimensions from being correlated with each other.
+    #[inline]
+    fn shuffled_index(&self, hash: u64) -> u32 {
+        permutation_element(self.sample, self.samples_per_pixel, hash as u32)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils::helpers::seed_random;
+
+    const KINDS: [SamplerKind; 4] = [
+        SamplerKind::Independent,
+        SamplerKind::Stratified,
+        SamplerKind::Halton,
+        SamplerKind::Sobol,
+    ];
+
+    /// Root mean square error of estimating the area below the diagonal of
+    /// the unit square, an edge like a silhouette, over many pixels.
+    fn edge_rmse(kind: SamplerKind, samples: u32) -> f32 {
+        let mut sampler = Sampler::new(kind, samples);
+        let pixels = 256;
+        let mut squared_error = 0.0;
+        for pixel in 0..pixels {
+            let mut inside = 0;
+            for sample in 0..samples {
+                seed_random(derive_seed(pixel, &[sample as u64]));
+                sampler.start_sample(pixel, sample);
+                // Skip ahead to check deeper dimensions too
+                sampler.get_1d();
+                let (x, y) = sampler.get_2d();
+                if x + y < 1.0 {
+                    inside += 1;
+                }
+            }
+            let estimate = inside as f32 / samples as f32;
+            squared_error += (estimate - 0.5) * (estimate - 0.5);
+        }
+        (squared_error / pixels as f32).sqrt()
+    }
+
+    #[test]
+    fn test_samples_are_in_unit_interval() {
+        for kind in KINDS {
+            let mut sampler = Sampler::new(kind, 37);
+            for sample in 0..37 {
+                sampler.start_sample(3, sample);
+                for _ in 0..100 {
+                    let u = sampler.get_1d();
+                    let (v, w) = sampler.get_2d();
+                    for x in [u, v, w] {
+                        assert!((0.0..1.0).contains(&x), "{:?}: {}", kind, x);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_low_discrepancy_beats_independent() {
+        let independent = edge_rmse(SamplerKind::Independent, 64);
+        for kind in [
+            SamplerKind::Stratified,
+            SamplerKind::Halton,
+            SamplerKind::Sobol,
+        ] {
+            let rmse = edge_rmse(kind, 64);
+            assert!(
+                rmse < 0.5 * independent,
+                "{:?}: {} vs {}",
+                kind,
+                rmse,
+                independent
+            );
+        }
+    }
+
+    #[test]
+    fn test_sobol_stratifies_every_dimension() {
+        let mut sampler = Sampler::new(SamplerKind::Sobol, 16);
+        for dimension in 0..8 {
+            let mut seen = [false; 16];
+            for sample in 0..16 {
+                sampler.start_sample(42, sample);
+                for _ in 0..dimension {
+                    sampler.get_1d();
+                }
+                seen[(sampler.get_1d() * 16.0) as usize] = true;
+            }
+            assert!(seen.iter().all(|&s| s), "dimension {}", dimension);
+        }
+    }
+}
@@ -0,0 +1,200 @@
+//! Building blocks of the low-discrepancy samplers: radical inverses, the
+//! first two Sobol dimensions, Owen scrambling and hashed permutations.
+
+/// Largest `f32` below 1, so scaled integers never round up to 1.
+pub const ONE_MINUS_EPSILON: f32 = 1.0 - f32::EPSILON / 2.0;
+
+/// Bases of the Halton dimensions.
+pub const PRIMES: [u32; 64] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131, 137, 139, 149, 151, 157, 163, 167, 173, 179, 181, 191, 193,
+    197, 199, 211, 223, 227, 229, 233, 239, 241, 251, 257, 263, 269, 271, 277, 281, 283, 293, 307,
+    311,
+];
+
+/// Maps the top bits of `bits` to [0, 1).
+#[inline]
+pub fn bits_to_unit(bits: u32) -> f32 {
+    (bits as f32 * (1.0 / 4_294_967_296.0)).min(ONE_MINUS_EPSILON)
+}
+
+/// `index` with its base `base` digits mirrored around the radix point,
+/// each digit shuffled by a permutation that depends on `seed` and the
+/// digits before it (Owen scrambling).
+pub fn owen_scrambled_radical_inverse(mut index: u32, base: u32, seed: u32) -> f32 {
+    let inv_base = 1.0 / base as f64;
+    let mut inv_base_m = 1.0;
+    let mut reversed: u64 = 0;
+    // Leading zeros get scrambled too, down to where they no longer change
+    // the f32 result
+    while inv_base_m > 1e-9 {
+        let next = index / base;
+        let digit = index - next * base;
+        let digit_seed = mix_bits(seed as u64 ^ reversed) as u32;
+        let digit = permutation_element(digit, base, digit_seed);
+        reversed = reversed * base as u64 + digit as u64;
+        inv_base_m *= inv_base;
+        index = next;
+    }
+    ((reversed as f64 * inv_base_m) as f32).min(ONE_MINUS_EPSILON)
+}
+
+/// `index` with its base `base` digits mirrored around the radix point.
+pub fn radical_inverse(mut index: u32, base: u32) -> f32 {
+    let inv_base = 1.0 / base as f64;
+    let mut inv_base_m = 1.0;
+    let mut reversed: u64 = 0;
+    while index > 0 {
+        let next = index / base;
+        reversed = reversed * base as u64 + (index - next * base) as u64;
+        inv_base_m *= inv_base;
+        index = next;
+    }
+    ((reversed as f64 * inv_base_m) as f32).min(ONE_MINUS_EPSILON)
+}
+
+/// Bits of the first Sobol dimension, the base 2 van der Corput sequence.
+#[inline]
+pub fn sobol_0(index: u32) -> u32 {
+    index.reverse_bits()
+}
+
+/// Bits of the second Sobol dimension, whose direction numbers follow from
+/// the primitive polynomial `x + 1`.
+#[inline]
+pub fn sobol_1(mut index: u32) -> u32 {
+    let mut direction = 1u32 << 31;
+    let mut bits = 0;
+    while index != 0 {
+        if index & 1 != 0 {
+            bits ^= direction;
+        }
+        direction ^= direction >> 1;
+        index >>= 1;
+    }
+    bits
+}
+
+/// Nested uniform scramble of the bits of a base 2 sample, equivalent to
+/// Owen scrambling (Burley 2020, after Laine and Karras).
+#[inline]
+pub fn owen_scramble(bits: u32, seed: u32) -> u32 {
+    let mut x = bits.reverse_bits();
+    x = x.wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50_b47c);
+    x ^= x.wrapping_mul(0xb82f_1e52);
+    x ^= x.wrapping_mul(0xc7af_e638);
+    x ^= x.wrapping_mul(0x8d22_f6e6);
+    x.reverse_bits()
+}
+
+/// Element `i` of a pseudo-random permutation of `0..n` picked by `seed`,
+/// without storing the permutation (Kensler 2013).
+pub fn permutation_element(mut i: u32, n: u32, seed: u32) -> u32 {
+    if n <= 1 {
+        return 0;
+    }
+    let mut w = n - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+    let p = seed;
+    // Cycle-walk until the hashed value lands in 0..n
+    loop {
+        i ^= p;
+        i = i.wrapping_mul(0xe170_893d);
+        i ^= p >> 16;
+        i ^= (i & w) >> 4;
+        i ^= p >> 8;
+        i = i.wrapping_mul(0x0929_eb3f);
+        i ^= p >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | p >> 27);
+        i = i.wrapping_mul(0x6935_fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dc_b303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e50_1cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860_a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < n {
+            break;
+        }
+    }
+    (i.wrapping_add(p)) % n
+}
+
+#[inline]
+fn mix_bits(mut v: u64) -> u64 {
+    v ^= v >> 31;
+    v = v.wrapping_mul(0x7fb5_d329_728e_a185);
+    v ^= v >> 27;
+    v = v.wrapping_mul(0x81da_def4_bc2d_d44d);
+    v ^ (v >> 33)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Whether every one of `n` equal intervals of [0, 1) holds one value.
+    fn one_per_interval(values: &[f32]) -> bool {
+        let n = values.len();
+        let mut seen = vec![false; n];
+        for &v in values {
+            seen[(v * n as f32) as usize] = true;
+        }
+        seen.iter().all(|&s| s)
+    }
+
+    #[test]
+    fn test_sobol_points() {
+        let points: Vec<(f32, f32)> = (0..4)
+            .map(|i| (bits_to_unit(sobol_0(i)), bits_to_unit(sobol_1(i))))
+            .collect();
+        assert_eq!(
+            points,
+            vec![(0.0, 0.0), (0.5, 0.5), (0.25, 0.75), (0.75, 0.25)]
+        );
+    }
+
+    #[test]
+    fn test_radical_inverse() {
+        let values: Vec<f32> = (0..4).map(|i| radical_inverse(i, 3)).collect();
+        let expected = [0.0, 1.0 / 3.0, 2.0 / 3.0, 1.0 / 9.0];
+        for (v, e) in values.iter().zip(expected) {
+            assert!((v - e).abs() < 1e-6, "{:?}", values);
+        }
+    }
+
+    #[test]
+    fn test_scrambling_keeps_stratification() {
+        for seed in [1, 77, 0xdead_beef] {
+            let sobol: Vec<f32> = (0..64)
+                .map(|i| bits_to_unit(owen_scramble(sobol_1(i), seed)))
+                .collect();
+            assert!(one_per_interval(&sobol));
+            let halton: Vec<f32> = (0..27)
+                .map(|i| owen_scrambled_radical_inverse(i, 3, seed))
+                .collect();
+            assert!(one_per_interval(&halton));
+        }
+        assert_ne!(owen_scramble(sobol_0(3), 1), owen_scramble(sobol_0(3), 2));
+    }
+
+    #[test]
+    fn test_permutation_element() {
+        for n in [1, 2, 7, 64, 100] {
+            let mut elements: Vec<u32> = (0..n).map(|i| permutation_element(i, n, 12345)).collect();
+            elements.sort_unstable();
+            assert_eq!(elements, (0..n).collect::<Vec<_>>());
+        }
+        let a: Vec<u32> = (0..16).map(|i| permutation_element(i, 16, 1)).collect();
+        let b: Vec<u32> = (0..16).map(|i| permutation_element(i, 16, 2)).collect();
+        assert_ne!(a, b);
+    }
+}
@@ -0,0 +1,1891 @@
+//! Loader for TOML scene descriptions.
+//!
+//! A scene file has an optional `[camera]` table, a `[textures]` table of
+//! named textures, a `[materials]` table of named materials and an
+//! `[[objects]]` array whose entries refer to those materials by name.
+//! Objects in a `[shapes]` table are not part of the scene by themselves
+//! but are placed by `instance` objects, any number of times, or bound a
+//! `constant_medium`. An optional `[atmosphere]` fills the scene within a
+//! `radius` of the camera, or of a given `center`, with a thin medium. An
+//! `albedo` is either an `[r, g, b]` color or the name of a texture:
+//!
+//! ```toml
+//! [camera]
+//! image_width = 400
+//! position = [13.0, 2.0, 3.0]
+//! background = "sky"  # or an [r, g, b] color; [0, 0, 0] turns it off
+//! tone_mapping = "aces"  # clamp, reinhard, reinhard_extended or uncharted2
+//! exposure = 0.5  # in stops
+//! sampler = "sobol"  # independent, stratified or halton
+//! light_sampling = "balance"  # bsdf or power
+//! filter = "mitchell"  # box, tent, gaussian or lanczos
+//! filter_radius = 2.0  # in pixels, defaults to the filter's usual radius
+//! adaptive_threshold = 0.02  # stop sampling a pixel at 2% relative error
+//! shutter_open = 0.0  # spread rays over [0, 1] to blur moving objects
+//! shutter_close = 1.0
+//!
+//! [textures.tiles]
+//! type = "checker"
+//! scale = 0.5
+//! even = [0.2, 0.3, 0.1]
+//! odd = [0.9, 0.9, 0.9]
+//!
+//! [materials.ground]
+//! type = "lambertian"
+//! albedo = "tiles"
+//!
+//! [[objects]]
+//! type = "sphere"
+//! center = [0.0, -1000.0, 0.0]
+//! radius = 1000.0
+//! material = "ground"
+//! ```
+
+use std::{
+    collections::HashMap,
+    fmt, fs, io,
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use serde::Deserialize;
+use toml::Spanned;
+
+use crate::{
+    camera::camera::{AdaptiveSampling, Atmosphere, Background, Camera, LightSampling},
+    output::tonemap::ToneMapper,
+    render::filter::{Filter, FilterKind},
+    sampler::sampler::SamplerKind,
+    scene::obj::load_obj,
+    vec3::{
+        transform::{AnimatedTransform, Keyframe, Quaternion},
+        vec3::Vec3,
+    },
+    world::{
+        material::Material,
+        mesh::TriangleMesh,
+        perlin::Perlin,
+        scene_object::SceneObject,
+        scene_object_list::SceneObjectList,
+        texture::{ColorRamp, ImageTexture, Texture, WrapMode},
+    },
+};
+
+pub struct Scene {
+    pub world: SceneObjectList,
+    pub camera: Camera,
+}
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io(io::Error),
+    /// Syntax or type error reported by the TOML parser; its message already
+    /// carries the line, column and offending key.
+    Parse(toml::de::Error),
+    /// The file parsed but describes an invalid scene.
+    Invalid {
+        line: usize,
+        column: usize,
+        field: String,
+        message: String,
+    },
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "failed to read scene file: {}", err),
+            Self::Parse(err) => write!(f, "{}", err),
+            Self::Invalid {
+                line,
+                column,
+                field,
+                message,
+            } => write!(
+                f,
+                "line {}, column {}: `{}`: {}",
+                line, column, field, message
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+impl From<io::Error> for SceneError {
+    fn from(err: io::Error) -> Self {
+        SceneError::Io(err)
+    }
+}
+
+impl From<toml::de::Error> for SceneError {
+    fn from(err: toml::de::Error) -> Self {
+        SceneError::Parse(err)
+    }
+}
+
+type Triple = [f32; 3];
+
+fn vec3(t: Triple) -> Vec3 {
+    Vec3::new(t[0], t[1], t[2])
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SceneFile {
+    camera: Option<Spanned<CameraDesc>>,
+    #[serde(default)]
+    textures: HashMap<String, Spanned<TextureDesc>>,
+    #[serde(default)]
+    materials: HashMap<String, Spanned<MaterialDesc>>,
+    #[serde(default)]
+    shapes: HashMap<String, Spanned<ObjectDesc>>,
+    #[serde(default)]
+    objects: Vec<Spanned<ObjectDesc>>,
+    atmosphere: Option<Spanned<AtmosphereDesc>>,
+}
+
+/// Medium filling the scene within `radius` of `center`, by default the
+/// camera position, scattering by a phase material.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AtmosphereDesc {
+    density: f32,
+    material: String,
+    radius: f32,
+    center: Option<Triple>,
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct CameraDesc {
+    image_width: u32,
+    aspect_ratio: f32,
+    samples_per_pixel: u32,
+    vfov: f32,
+    max_depth: u32,
+    position: Triple,
+    lookat: Triple,
+    up: Triple,
+    defocus_angle: f32,
+    focus_dist: f32,
+    background: BackgroundDesc,
+    tone_mapping: ToneMappingDesc,
+    white_point: f32,
+    exposure: f32,
+    gamma: f32,
+    adaptive_threshold: Option<f32>,
+    min_samples: Option<u32>,
+    sampler: SamplerDesc,
+    light_sampling: LightSamplingDesc,
+    filter: FilterDesc,
+    filter_radius: Option<f32>,
+    shutter_open: f32,
+    shutter_close: f32,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum FilterDesc {
+    Box,
+    Tent,
+    Gaussian,
+    Mitchell,
+    Lanczos,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum SamplerDesc {
+    Independent,
+    Stratified,
+    Halton,
+    Sobol,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum LightSamplingDesc {
+    Bsdf,
+    Balance,
+    Power,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ToneMappingDesc {
+    Clamp,
+    Reinhard,
+    ReinhardExtended,
+    Aces,
+    Uncharted2,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum BackgroundDesc {
+    Named(String),
+    Color(Triple),
+}
+
+impl Default for CameraDesc {
+    fn default() -> Self {
+        CameraDesc {
+            image_width: 1200,
+            aspect_ratio: 16.0 / 9.0,
+            samples_per_pixel: 500,
+            vfov: 25.0,
+            max_depth: 20,
+            position: [13.0, 2.0, 3.0],
+            lookat: [0.0, 0.0, 0.0],
+            up: [0.0, 1.0, 0.0],
+            defocus_angle: 0.6,
+            focus_dist: 10.0,
+            background: BackgroundDesc::Named("sky".to_string()),
+            tone_mapping: ToneMappingDesc::Clamp,
+            white_point: 4.0,
+            exposure: 0.0,
+            gamma: 0.0,
+            adaptive_threshold: None,
+            min_samples: None,
+            sampler: SamplerDesc::Independent,
+            light_sampling: LightSamplingDesc::Power,
+            filter: FilterDesc::Box,
+            filter_radius: None,
+            shutter_open: 0.0,
+            shutter_close: 0.0,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum TextureDesc {
+    Solid {
+        color: Triple,
+    },
+    Checker {
+        scale: f32,
+        even: Triple,
+        odd: Triple,
+    },
+    UvChecker {
+        columns: f32,
+        rows: f32,
+        even: Triple,
+        odd: Triple,
+    },
+    /// PNG or PPM file, relative to the scene file.
+    Image {
+        path: String,
+        #[serde(default)]
+        wrap: WrapDesc,
+    },
+    Marble {
+        #[serde(default)]
+        seed: u64,
+        #[serde(default = "default_scale")]
+        scale: f32,
+        #[serde(default = "default_marble_turbulence")]
+        turbulence: f32,
+        colors: Option<Vec<Triple>>,
+    },
+    Wood {
+        #[serde(default)]
+        seed: u64,
+        #[serde(default = "default_scale")]
+        scale: f32,
+        #[serde(default = "default_wood_rings")]
+        rings: f32,
+        #[serde(default = "default_wood_turbulence")]
+        turbulence: f32,
+        colors: Option<Vec<Triple>>,
+    },
+    Clouds {
+        #[serde(default)]
+        seed: u64,
+        #[serde(default = "default_scale")]
+        scale: f32,
+        #[serde(default = "default_cloud_octaves")]
+        octaves: u32,
+        colors: Option<Vec<Triple>>,
+    },
+}
+
+fn default_scale() -> f32 {
+    1.0
+}
+
+fn default_marble_turbulence() -> f32 {
+    10.0
+}
+
+fn default_wood_rings() -> f32 {
+    8.0
+}
+
+fn default_wood_turbulence() -> f32 {
+    0.5
+}
+
+fn default_cloud_octaves() -> u32 {
+    6
+}
+
+#[derive(Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+enum WrapDesc {
+    #[default]
+    Repeat,
+    Clamp,
+    Mirror,
+}
+
+impl From<WrapDesc> for WrapMode {
+    fn from(wrap: WrapDesc) -> Self {
+        match wrap {
+            WrapDesc::Repeat => WrapMode::Repeat,
+            WrapDesc::Clamp => WrapMode::Clamp,
+            WrapDesc::Mirror => WrapMode::Mirror,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum AlbedoDesc {
+    Color(Triple),
+    Texture(String),
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum MaterialDesc {
+    Lambertian {
+        albedo: AlbedoDesc,
+    },
+    Metal {
+        albedo: AlbedoDesc,
+        fuzz: f32,
+    },
+    Dielectric {
+        refraction_index: f32,
+    },
+    DiffuseLight {
+        emit: Triple,
+    },
+    /// Phase functions, only for media.
+    Isotropic {
+        albedo: AlbedoDesc,
+    },
+    HenyeyGreenstein {
+        albedo: AlbedoDesc,
+        g: f32,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum ObjectDesc {
+    /// Moves from `center` at time 0 to `end_center` at time 1 if given.
+    Sphere {
+        center: Triple,
+        end_center: Option<Triple>,
+        radius: f32,
+        material: String,
+    },
+    /// Either `base` and `top`, or an upright cylinder's `center` and
+    /// `height`. Both ends are capped unless turned off.
+    Cylinder {
+        base: Option<Triple>,
+        top: Option<Triple>,
+        center: Option<Triple>,
+        height: Option<f32>,
+        radius: f32,
+        #[serde(default = "default_capped")]
+        base_cap: bool,
+        #[serde(default = "default_capped")]
+        top_cap: bool,
+        material: String,
+    },
+    /// A `top_radius` of zero, the default, gives a pointed cone.
+    Cone {
+        base: Triple,
+        top: Triple,
+        base_radius: f32,
+        #[serde(default)]
+        top_radius: f32,
+        #[serde(default = "default_capped")]
+        base_cap: bool,
+        #[serde(default = "default_capped")]
+        top_cap: bool,
+        material: String,
+    },
+    Disk {
+        center: Triple,
+        normal: Triple,
+        radius: f32,
+        material: String,
+    },
+    Torus {
+        center: Triple,
+        #[serde(default = "default_torus_axis")]
+        axis: Triple,
+        major_radius: f32,
+        minor_radius: f32,
+        material: String,
+    },
+    Capsule {
+        base: Triple,
+        top: Triple,
+        radius: f32,
+        material: String,
+    },
+    Plane {
+        point: Triple,
+        normal: Triple,
+        material: String,
+    },
+    Quad {
+        q: Triple,
+        u: Triple,
+        v: Triple,
+        material: String,
+    },
+    Triangle {
+        v0: Triple,
+        v1: Triple,
+        v2: Triple,
+        material: String,
+    },
+    /// Wavefront OBJ file, relative to the scene file.
+    Mesh {
+        path: String,
+        #[serde(default = "default_smooth")]
+        smooth: bool,
+        material: String,
+    },
+    /// A named entry of `[shapes]`, scaled, then rotated about x, y and z
+    /// in turn (in degrees), then translated. Moving instances give these
+    /// per keyframe instead.
+    Instance {
+        shape: String,
+        scale: Option<Triple>,
+        rotate: Option<Triple>,
+        translate: Option<Triple>,
+        keyframes: Option<Vec<KeyframeDesc>>,
+    },
+    /// Smoke or fog filling a named entry of `[shapes]`, scattering by a
+    /// phase `material`.
+    ConstantMedium {
+        boundary: String,
+        density: f32,
+        material: String,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct KeyframeDesc {
+    time: f32,
+    scale: Option<Triple>,
+    rotate: Option<Triple>,
+    translate: Option<Triple>,
+}
+
+fn default_smooth() -> bool {
+    true
+}
+
+fn default_capped() -> bool {
+    true
+}
+
+fn default_torus_axis() -> Triple {
+    [0.0, 1.0, 0.0]
+}
+
+/// The scene text, for mapping byte offsets to 1-based line/column pairs,
+/// and the directory its relative paths start from.
+struct Source<'a> {
+    text: &'a str,
+    base_dir: &'a Path,
+}
+
+impl Source<'_> {
+    /// Builds an error for `field` of the table spanning `span`. When the
+    /// field's key is written inside that table the error points at it,
+    /// otherwise at the start of the table.
+    fn error(&self, span: Range<usize>, field: &str, message: impl Into<String>) -> SceneError {
+        let key = field.rsplit('.').next().unwrap_or(field);
+        let offset = self.find_key(span.clone(), key).unwrap_or(span.start);
+
+        let before = &self.text[..offset.min(self.text.len())];
+        let line = before.matches('\n').count() + 1;
+        let column = before.len() - before.rfind('\n').map_or(0, |i| i + 1) + 1;
+        SceneError::Invalid {
+            line,
+            column,
+            field: field.to_string(),
+            message: message.into(),
+        }
+    }
+
+    fn find_key(&self, span: Range<usize>, key: &str) -> Option<usize> {
+        let table = self.text.get(span.clone())?;
+        let mut offset = span.start;
+        for line in table.split_inclusive('\n') {
+            let trimmed = line.trim_start();
+            if let Some(rest) = trimmed.strip_prefix(key) {
+                if rest.trim_start().starts_with('=') {
+                    return Some(offset + line.len() - trimmed.len());
+                }
+            }
+            offset += line.len();
+        }
+        None
+    }
+}
+
+pub fn load_scene(path: impl AsRef<Path>) -> Result<Scene, SceneError> {
+    let path = path.as_ref();
+    let text = fs::read_to_string(path)?;
+    parse_scene_in(&text, path.parent().unwrap_or(Path::new("")))
+}
+
+/// Parses a scene whose relative paths are resolved against the working
+/// directory.
+pub fn parse_scene(text: &str) -> Result<Scene, SceneError> {
+    parse_scene_in(text, Path::new(""))
+}
+
+fn parse_scene_in(text: &str, base_dir: &Path) -> Result<Scene, SceneError> {
+    let file: SceneFile = toml::from_str(text)?;
+    let source = Source { text, base_dir };
+
+    let camera = match &file.camera {
+        Some(desc) => build_camera(&source, desc.get_ref(), desc.span())?,
+        None => build_camera(&source, &CameraDesc::default(), 0..0)?,
+    };
+
+    let mut textures = HashMap::new();
+    for (name, desc) in &file.textures {
+        let texture = build_texture(&source, name, desc)?;
+        textures.insert(name.as_str(), texture);
+    }
+
+    let mut materials = HashMap::new();
+    for (name, desc) in &file.materials {
+        let material = build_material(&source, name, desc, &textures)?;
+        materials.insert(name.as_str(), Arc::new(material));
+    }
+
+    let mut meshes = HashMap::new();
+    let mut shapes = HashMap::new();
+    for (name, desc) in &file.shapes {
+        let path = format!("shapes.{}", name);
+        // Shapes are built in no particular order, so they cannot refer to
+        // each other
+        let field = match desc.get_ref() {
+            ObjectDesc::Instance { .. } => Some("shape"),
+            ObjectDesc::ConstantMedium { .. } => Some("boundary"),
+            _ => None,
+        };
+        if let Some(field) = field {
+            let field = format!("{}.{}", path, field);
+            return Err(source.error(desc.span(), &field, "shapes cannot refer to other shapes"));
+        }
+        let shape = build_object(&source, &path, desc, &materials, &shapes, &mut meshes)?;
+        shapes.insert(name.as_str(), Arc::new(shape));
+    }
+
+    let mut world = SceneObjectList::new();
+    for (index, desc) in file.objects.iter().enumerate() {
+        let path = format!("objects[{}]", index);
+        world.add(build_object(
+            &source,
+            &path,
+            desc,
+            &materials,
+            &shapes,
+            &mut meshes,
+        )?);
+    }
+
+    let camera = match &file.atmosphere {
+        Some(desc) => {
+            let camera_position = file
+                .camera
+                .as_ref()
+                .map_or(CameraDesc::default().position, |camera| {
+                    camera.get_ref().position
+                });
+            let atmosphere = build_atmosphere(&source, desc, &materials, camera_position)?;
+            camera.with_atmosphere(Some(atmosphere))
+        }
+        None => camera,
+    };
+
+    Ok(Scene { world, camera })
+}
+
+fn build_atmosphere(
+    source: &Source,
+    desc: &Spanned<AtmosphereDesc>,
+    materials: &HashMap<&str, Arc<Material>>,
+    camera_position: Triple,
+) -> Result<Atmosphere, SceneError> {
+    let err = |field: &str, message: &str| {
+        source.error(desc.span(), &format!("atmosphere.{}", field), message)
+    };
+    let AtmosphereDesc {
+        density,
+        material,
+        radius,
+        center,
+    } = desc.get_ref();
+    if *density <= 0.0 {
+        return Err(err("density", "must be positive"));
+    }
+    if *radius <= 0.0 {
+        return Err(err("radius", "must be positive"));
+    }
+    let phase = materials
+        .get(material.as_str())
+        .cloned()
+        .ok_or_else(|| err("material", &format!("unknown material `{}`", material)))?;
+    if !phase.is_phase_function() {
+        return Err(err(
+            "material",
+            "must be an isotropic or henyey_greenstein material",
+        ));
+    }
+    Ok(Atmosphere {
+        density: *density,
+        phase,
+        center: vec3(center.unwrap_or(camera_position)),
+        radius: *radius,
+    })
+}
+
+fn build_camera(
+    source: &Source,
+    desc: &CameraDesc,
+    span: Range<usize>,
+) -> Result<Camera, SceneError> {
+    let err = |field: &str, message: &str| source.error(span.clone(), field, message);
+
+    if desc.image_width == 0 {
+        return Err(err("camera.image_width", "must be at least 1"));
+    }
+    if desc.aspect_ratio <= 0.0 {
+        return Err(err("camera.aspect_ratio", "must be positive"));
+    }
+    if desc.samples_per_pixel == 0 {
+        return Err(err("camera.samples_per_pixel", "must be at least 1"));
+    }
+    if desc.vfov <= 0.0 || desc.vfov >= 180.0 {
+        return Err(err("camera.vfov", "must be between 0 and 180 degrees"));
+    }
+    if desc.focus_dist <= 0.0 {
+        return Err(err("camera.focus_dist", "must be positive"));
+    }
+    if desc.position == desc.lookat {
+        return Err(err("camera.lookat", "must differ from `camera.position`"));
+    }
+    if desc.white_point <= 0.0 {
+        return Err(err("camera.white_point", "must be positive"));
+    }
+    if desc.gamma < 0.0 {
+        return Err(err(
+            "camera.gamma",
+            "must not be negative, 0 selects the sRGB transfer function",
+        ));
+    }
+    if desc.filter_radius.is_some_and(|r| r <= 0.0) {
+        return Err(err("camera.filter_radius", "must be positive"));
+    }
+    if desc.adaptive_threshold.is_some_and(|t| t <= 0.0) {
+        return Err(err("camera.adaptive_threshold", "must be positive"));
+    }
+    if desc.shutter_close < desc.shutter_open {
+        return Err(err(
+            "camera.shutter_close",
+            "must not be before `camera.shutter_open`",
+        ));
+    }
+    // Only adaptive sampling stops early, so the minimum matters only with
+    // a threshold, and the default is cut down to small sample counts
+    let adaptive_sampling = match (desc.adaptive_threshold, desc.min_samples) {
+        (None, _) => None,
+        (Some(_), Some(min_samples))
+            if min_samples == 0 || min_samples > desc.samples_per_pixel =>
+        {
+            return Err(err(
+                "camera.min_samples",
+                "must be between 1 and `camera.samples_per_pixel`",
+            ));
+        }
+        (Some(threshold), min_samples) => Some(AdaptiveSampling {
+            min_samples: min_samples.unwrap_or(16.min(desc.samples_per_pixel)),
+            threshold,
+        }),
+    };
+
+    let filter_kind = match desc.filter {
+        FilterDesc::Box => FilterKind::Box,
+        FilterDesc::Tent => FilterKind::Tent,
+        FilterDesc::Gaussian => FilterKind::Gaussian,
+        FilterDesc::Mitchell => FilterKind::Mitchell,
+        FilterDesc::Lanczos => FilterKind::Lanczos,
+    };
+    let filter = Filter::new(
+        filter_kind,
+        desc.filter_radius
+            .unwrap_or_else(|| filter_kind.default_radius()),
+    );
+
+    let background = match &desc.background {
+        BackgroundDesc::Named(name) if name == "sky" => Background::Sky,
+        BackgroundDesc::Named(name) => {
+            return Err(err(
+                "camera.background",
+                &format!("expected \"sky\" or an [r, g, b] color, got \"{}\"", name),
+            ))
+        }
+        BackgroundDesc::Color(color) => Background::Color(vec3(*color)),
+    };
+    let tone_mapper = match desc.tone_mapping {
+        ToneMappingDesc::Clamp => ToneMapper::Clamp,
+        ToneMappingDesc::Reinhard => ToneMapper::Reinhard,
+        ToneMappingDesc::ReinhardExtended => ToneMapper::ExtendedReinhard {
+            white: desc.white_point,
+        },
+        ToneMappingDesc::Aces => ToneMapper::Aces,
+        ToneMappingDesc::Uncharted2 => ToneMapper::Uncharted2,
+    };
+
+    Ok(Camera::new(
+        desc.image_width,
+        desc.aspect_ratio,
+        desc.samples_per_pixel,
+        desc.vfov,
+        desc.max_depth,
+        vec3(desc.position),
+        vec3(desc.lookat),
+        vec3(desc.up),
+        desc.defocus_angle,
+        desc.focus_dist,
+    )
+    .with_background(background)
+    .with_tone_mapping(tone_mapper, desc.exposure)
+    .with_gamma_correction(desc.gamma)
+    .with_sampler(match desc.sampler {
+        SamplerDesc::Independent => SamplerKind::Independent,
+        SamplerDesc::Stratified => SamplerKind::Stratified,
+        SamplerDesc::Halton => SamplerKind::Halton,
+        SamplerDesc::Sobol => SamplerKind::Sobol,
+    })
+    .with_light_sampling(match desc.light_sampling {
+        LightSamplingDesc::Bsdf => LightSampling::Bsdf,
+        LightSamplingDesc::Balance => LightSampling::Balance,
+        LightSamplingDesc::Power => LightSampling::Power,
+    })
+    .with_filter(filter)
+    .with_shutter(desc.shutter_open, desc.shutter_close)
+    .with_adaptive_sampling(adaptive_sampling))
+}
+
+fn build_texture(
+    source: &Source,
+    name: &str,
+    desc: &Spanned<TextureDesc>,
+) -> Result<Texture, SceneError> {
+    let err = |field: &str, message: &str| {
+        source.error(
+            desc.span(),
+            &format!("textures.{}.{}", name, field),
+            message,
+        )
+    };
+    let solid = |color: &Triple| Arc::new(Texture::Solid(vec3(*color)));
+    let positive_scale = |scale: f32| {
+        if scale > 0.0 {
+            Ok(scale)
+        } else {
+            Err(err("scale", "must be positive"))
+        }
+    };
+    // Evenly spaced colors, or the texture's own default ramp
+    let ramp = |colors: &Option<Vec<Triple>>, default: &[Triple]| {
+        let colors = colors.as_deref().unwrap_or(default);
+        if colors.is_empty() {
+            return Err(err("colors", "must list at least one color"));
+        }
+        let colors: Vec<Vec3> = colors.iter().map(|c| vec3(*c)).collect();
+        Ok(Arc::new(ColorRamp::uniform(&colors)))
+    };
+
+    match desc.get_ref() {
+        TextureDesc::Solid { color } => Ok(Texture::Solid(vec3(*color))),
+        TextureDesc::Checker { scale, even, odd } => {
+            if *scale <= 0.0 {
+                return Err(err("scale", "must be positive"));
+            }
+            Ok(Texture::Checker {
+                scale: *scale,
+                even: solid(even),
+                odd: solid(odd),
+            })
+        }
+        TextureDesc::UvChecker {
+            columns,
+            rows,
+            even,
+            odd,
+        } => {
+            if *columns <= 0.0 {
+                return Err(err("columns", "must be positive"));
+            }
+            if *rows <= 0.0 {
+                return Err(err("rows", "must be positive"));
+            }
+            Ok(Texture::UvChecker {
+                columns: *columns,
+                rows: *rows,
+                even: solid(even),
+                odd: solid(odd),
+            })
+        }
+        TextureDesc::Image { path, wrap } => {
+            let full_path = source.base_dir.join(path);
+            let image = ImageTexture::load(&full_path, (*wrap).into()).map_err(|e| {
+                err(
+                    "path",
+                    &format!("cannot load `{}`: {}", full_path.display(), e),
+                )
+            })?;
+            Ok(Texture::Image(Arc::new(image)))
+        }
+        TextureDesc::Marble {
+            seed,
+            scale,
+            turbulence,
+            colors,
+        } => Ok(Texture::Marble {
+            noise: Arc::new(Perlin::new(*seed)),
+            scale: positive_scale(*scale)?,
+            turbulence: *turbulence,
+            ramp: ramp(colors, &[[0.25, 0.25, 0.28], [0.95, 0.95, 0.95]])?,
+        }),
+        TextureDesc::Wood {
+            seed,
+            scale,
+            rings,
+            turbulence,
+            colors,
+        } => {
+            if *rings <= 0.0 {
+                return Err(err("rings", "must be positive"));
+            }
+            Ok(Texture::Wood {
+                noise: Arc::new(Perlin::new(*seed)),
+                scale: positive_scale(*scale)?,
+                rings: *rings,
+                turbulence: *turbulence,
+                ramp: ramp(colors, &[[0.55, 0.35, 0.17], [0.35, 0.2, 0.08]])?,
+            })
+        }
+        TextureDesc::Clouds {
+            seed,
+            scale,
+            octaves,
+            colors,
+        } => {
+            if *octaves == 0 {
+                return Err(err("octaves", "must be at least 1"));
+            }
+            Ok(Texture::Clouds {
+                noise: Arc::new(Perlin::new(*seed)),
+                scale: positive_scale(*scale)?,
+                octaves: *octaves,
+                ramp: ramp(colors, &[[0.3, 0.5, 0.9], [1.0, 1.0, 1.0]])?,
+            })
+        }
+    }
+}
+
+fn build_material(
+    source: &Source,
+    name: &str,
+    desc: &Spanned<MaterialDesc>,
+    textures: &HashMap<&str, Texture>,
+) -> Result<Material, SceneError> {
+    let err = |field: &str, message: &str| {
+        source.error(
+            desc.span(),
+            &format!("materials.{}.{}", name, field),
+            message,
+        )
+    };
+    let texture = |albedo: &AlbedoDesc| match albedo {
+        AlbedoDesc::Color(color) => Ok(Texture::Solid(vec3(*color))),
+        AlbedoDesc::Texture(name) => textures
+            .get(name.as_str())
+            .cloned()
+            .ok_or_else(|| err("albedo", &format!("unknown texture `{}`", name))),
+    };
+
+    match *desc.get_ref() {
+        MaterialDesc::Lambertian { ref albedo } => Ok(Material::Lambertian {
+            albedo: texture(albedo)?,
+        }),
+        MaterialDesc::Metal { ref albedo, fuzz } => {
+            if !(0.0..=1.0).contains(&fuzz) {
+                return Err(err("fuzz", "must be between 0 and 1"));
+            }
+            Ok(Material::Metal {
+                albedo: texture(albedo)?,
+                fuzz,
+            })
+        }
+        MaterialDesc::Dielectric { refraction_index } => {
+            if refraction_index <= 0.0 {
+                return Err(err("refraction_index", "must be positive"));
+            }
+            Ok(Material::Dielectric { refraction_index })
+        }
+        MaterialDesc::DiffuseLight { emit } => {
+            if emit.iter().any(|c| *c < 0.0) {
+                return Err(err("emit", "must not be negative"));
+            }
+            Ok(Material::DiffuseLight { emit: vec3(emit) })
+        }
+        MaterialDesc::Isotropic { ref albedo } => Ok(Material::Isotropic {
+            albedo: texture(albedo)?,
+        }),
+        MaterialDesc::HenyeyGreenstein { ref albedo, g } => {
+            if g <= -1.0 || g >= 1.0 {
+                return Err(err("g", "must be between -1 and 1, exclusive"));
+            }
+            Ok(Material::HenyeyGreenstein {
+                albedo: texture(albedo)?,
+                g,
+            })
+        }
+    }
+}
+
+/// Builds the object at `path`, e.g. `objects[3]`, which names it in errors.
+fn build_object(
+    source: &Source,
+    path: &str,
+    desc: &Spanned<ObjectDesc>,
+    materials: &HashMap<&str, Arc<Material>>,
+    shapes: &HashMap<&str, Arc<SceneObject>>,
+    meshes: &mut HashMap<(PathBuf, bool), Arc<TriangleMesh>>,
+) -> Result<SceneObject, SceneError> {
+    let err = |field: &str, message: &str| {
+        source.error(desc.span(), &format!("{}.{}", path, field), message)
+    };
+    let material = |name: &str| {
+        materials
+            .get(name)
+            .cloned()
+            .ok_or_else(|| err("material", &format!("unknown material `{}`", name)))
+    };
+
+    match desc.get_ref() {
+        ObjectDesc::Sphere {
+            center,
+            end_center,
+            radius,
+            material: name,
+        } => {
+            if *radius <= 0.0 {
+                return Err(err("radius", "must be positive"));
+            }
+            Ok(match end_center {
+                Some(end_center) => SceneObject::MovingSphere {
+                    center: vec3(*center),
+                    end_center: vec3(*end_center),
+                    radius: *radius,
+                    material: material(name)?,
+                },
+                None => SceneObject::Sphere {
+                    center: vec3(*center),
+                    radius: *radius,
+                    material: material(name)?,
+                },
+            })
+        }
+        ObjectDesc::Cylinder {
+            base,
+            top,
+            center,
+            height,
+            radius,
+            base_cap,
+            top_cap,
+            material: name,
+        } => {
+            if *radius <= 0.0 {
+                return Err(err("radius", "must be positive"));
+            }
+            let (base, top) = match (base, top, center, height) {
+                (Some(base), Some(top), None, None) => (vec3(*base), vec3(*top)),
+                (None, None, Some(center), Some(height)) => {
+                    if *height <= 0.0 {
+                        return Err(err("height", "must be positive"));
+                    }
+                    let half_height = Vec3::new(0.0, height / 2.0, 0.0);
+                    (vec3(*center) - half_height, vec3(*center) + half_height)
+                }
+                _ => {
+                    return Err(err(
+                        "base",
+                        "give either `base` and `top`, or `center` and `height`",
+                    ))
+                }
+            };
+            if (top - base).length_squared() == 0.0 {
+                return Err(err("top", "must differ from `base`"));
+            }
+            Ok(SceneObject::Cylinder {
+                radius: *radius,
+                base,
+                top,
+                base_cap: *base_cap,
+                top_cap: *top_cap,
+                material: material(name)?,
+            })
+        }
+        ObjectDesc::Cone {
+            base,
+            top,
+            base_radius,
+            top_radius,
+            base_cap,
+            top_cap,
+            material: name,
+        } => {
+            if *base_radius < 0.0 {
+                return Err(err("base_radius", "must not be negative"));
+            }
+            if *top_radius < 0.0 {
+                return Err(err("top_radius", "must not be negative"));
+            }
+            if *base_radius == 0.0 && *top_radius == 0.0 {
+                return Err(err("base_radius", "one of the radii must be positive"));
+            }
+            if (vec3(*top) - vec3(*base)).length_squared() == 0.0 {
+                return Err(err("top", "must differ from `base`"));
+            }
+            Ok(SceneObject::Cone {
+                base: vec3(*base),
+                top: vec3(*top),
+                base_radius: *base_radius,
+                top_radius: *top_radius,
+                base_cap: *base_cap,
+                top_cap: *top_cap,
+                material: material(name)?,
+            })
+        }
+        ObjectDesc::Disk {
+            center,
+            normal,
+            radius,
+            material: name,
+        } => {
+            if *radius <= 0.0 {
+                return Err(err("radius", "must be positive"));
+            }
+            if vec3(*normal).length_squared() == 0.0 {
+                return Err(err("normal", "must not be zero"));
+            }
+            Ok(SceneObject::Disk {
+                center: vec3(*center),
+                normal: vec3(*normal),
+                radius: *radius,
+                material: material(name)?,
+            })
+        }
+        ObjectDesc::Torus {
+            center,
+            axis,
+            major_radius,
+            minor_radius,
+            material: name,
+        } => {
+            if *major_radius <= 0.0 {
+                return Err(err("major_radius", "must be positive"));
+            }
+            if *minor_radius <= 0.0 {
+                return Err(err("minor_radius", "must be positive"));
+            }
+            if vec3(*axis).length_squared() == 0.0 {
+                return Err(err("axis", "must not be zero"));
+            }
+            Ok(SceneObject::Torus {
+                center: vec3(*center),
+                axis: vec3(*axis),
+                major_radius: *major_radius,
+                minor_radius: *minor_radius,
+                material: material(name)?,
+            })
+        }
+        ObjectDesc::Capsule {
+            base,
+            top,
+            radius,
+            material: name,
+        } => {
+            if *radius <= 0.0 {
+                return Err(err("radius", "must be positive"));
+            }
+            if (vec3(*top) - vec3(*base)).length_squared() == 0.0 {
+                return Err(err("top", "must differ from `base`"));
+            }
+            Ok(SceneObject::Capsule {
+                radius: *radius,
+                base: vec3(*base),
+                top: vec3(*top),
+                material: material(name)?,
+            })
+        }
+        ObjectDesc::Plane {
+            point,
+            normal,
+            material: name,
+        } => {
+            if vec3(*normal).length_squared() == 0.0 {
+                return Err(err("normal", "must not be zero"));
+            }
+            Ok(SceneObject::Plane {
+                point: vec3(*point),
+                normal: vec3(*normal),
+                material: material(name)?,
+            })
+        }
+        ObjectDesc::Quad {
+            q,
+            u,
+            v,
+            material: name,
+        } => {
+            if Vec3::cross_product(vec3(*u), vec3(*v)).length_squared() == 0.0 {
+                return Err(err("v", "edges `u` and `v` must not be parallel"));
+            }
+            Ok(SceneObject::Quad {
+                q: vec3(*q),
+                u: vec3(*u),
+                v: vec3(*v),
+                material: material(name)?,
+            })
+        }
+        ObjectDesc::Triangle {
+            v0,
+            v1,
+            v2,
+            material: name,
+        } => {
+            let (v0, v1, v2) = (vec3(*v0), vec3(*v1), vec3(*v2));
+            if Vec3::cross_product(v1 - v0, v2 - v0).length_squared() == 0.0 {
+                return Err(err("v2", "triangle is degenerate"));
+            }
+            Ok(SceneObject::Triangle {
+                v0,
+                v1,
+                v2,
+                material: material(name)?,
+            })
+        }
+        ObjectDesc::Mesh {
+            path,
+            smooth,
+            material: name,
+        } => {
+            let path = source.base_dir.join(path);
+            let key = (path.clone(), *smooth);
+            let mesh = match meshes.get(&key) {
+                Some(mesh) => Arc::clone(mesh),
+                None => {
+                    let mut mesh = load_obj(&path)
+                        .map_err(|e| err("path", &format!("{}: {}", path.display(), e)))?;
+                    if !smooth {
+                        mesh.clear_vertex_normals();
+                    } else if mesh.normals.is_empty() {
+                        mesh.compute_vertex_normals();
+                    }
+                    let mesh = Arc::new(mesh);
+                    meshes.insert(key, Arc::clone(&mesh));
+                    mesh
+                }
+            };
+            Ok(SceneObject::Mesh {
+                mesh,
+                material: material(name)?,
+            })
+        }
+        ObjectDesc::Instance {
+            shape,
+            scale,
+            rotate,
+            translate,
+            keyframes,
+        } => {
+            let object = shapes
+                .get(shape.as_str())
+                .cloned()
+                .ok_or_else(|| err("shape", &format!("unknown shape `{}`", shape)))?;
+            let Some(keyframes) = keyframes else {
+                let transform = keyframe(0.0, scale, rotate, translate)
+                    .map_err(|message| err("scale", message))?
+                    .transform();
+                return Ok(SceneObject::Instance { object, transform });
+            };
+
+            if scale.is_some() || rotate.is_some() || translate.is_some() {
+                return Err(err(
+                    "keyframes",
+                    "give either `keyframes` or `scale`, `rotate` and `translate`",
+                ));
+            }
+            if keyframes.is_empty() {
+                return Err(err("keyframes", "must not be empty"));
+            }
+            let mut frames: Vec<Keyframe> = Vec::with_capacity(keyframes.len());
+            for (i, desc) in keyframes.iter().enumerate() {
+                let frame = keyframe(desc.time, &desc.scale, &desc.rotate, &desc.translate)
+                    .map_err(|message| err(&format!("keyframes[{}].scale", i), message))?;
+                if let Some(previous) = frames.last() {
+                    if frame.time <= previous.time {
+                        return Err(err(
+                            &format!("keyframes[{}].time", i),
+                            "must be after the previous keyframe's",
+                        ));
+                    }
+                    // A factor changing sign would pass through zero
+                    let signs = |v: Vec3| [v.x > 0.0, v.y > 0.0, v.z > 0.0];
+                    if signs(frame.scale) != signs(previous.scale) {
+                        return Err(err(
+                            &format!("keyframes[{}].scale", i),
+                            "factors must keep their signs between keyframes",
+                        ));
+                    }
+                }
+                frames.push(frame);
+            }
+            Ok(SceneObject::MovingInstance {
+                object,
+                motion: AnimatedTransform::new(frames),
+            })
+        }
+        ObjectDesc::ConstantMedium {
+            boundary,
+            density,
+            material: name,
+        } => {
+            let boundary = shapes
+                .get(boundary.as_str())
+                .cloned()
+                .ok_or_else(|| err("boundary", &format!("unknown shape `{}`", boundary)))?;
+            if *density <= 0.0 {
+                return Err(err("density", "must be positive"));
+            }
+            let phase = material(name)?;
+            if !phase.is_phase_function() {
+                return Err(err(
+                    "material",
+                    "must be an isotropic or henyey_greenstein material",
+                ));
+            }
+            Ok(SceneObject::ConstantMedium {
+                boundary,
+                density: *density,
+                phase,
+            })
+        }
+    }
+}
+
+/// Placement from an instance's optional `scale`, `rotate` and `translate`,
+/// or a message about its scale.
+fn keyframe(
+    time: f32,
+    scale: &Option<Triple>,
+    rotate: &Option<Triple>,
+    translate: &Option<Triple>,
+) -> Result<Keyframe, &'static str> {
+    let scale = scale.unwrap_or([1.0, 1.0, 1.0]);
+    if scale.contains(&0.0) {
+        return Err("factors must not be zero");
+    }
+    let axes = [
+        Vec3::new(1.0, 0.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        Vec3::new(0.0, 0.0, 1.0),
+    ];
+    let rotation = axes
+        .into_iter()
+        .zip(rotate.unwrap_or_default())
+        .fold(Quaternion::IDENTITY, |rotation, (axis, degrees)| {
+            rotation.then(&Quaternion::from_axis_angle(axis, degrees))
+        });
+    Ok(Keyframe {
+        time,
+        scale: vec3(scale),
+        rotation,
+        translate: vec3(translate.unwrap_or_default()),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const THREE_SPHERES: &str = r#"
+[camera]
+image_width = 400
+
+[materials.ground]
+type = "lambertian"
+albedo = [0.5, 0.5, 0.5]
+
+[materials.glass]
+type = "dielectric"
+refraction_index = 1.5
+
+[[objects]]
+type = "sphere"
+center = [0.0, -1000.0, 0.0]
+radius = 1000.0
+material = "ground"
+
+[[objects]]
+type = "cylinder"
+center = [0.0, 1.0, 0.0]
+radius = 1.0
+height = 2.0
+material = "glass"
+"#;
+
+    #[test]
+    fn test_example_scenes_load() {
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/scenes");
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_none_or(|ext| ext != "toml") {
+                continue;
+            }
+            if let Err(err) = load_scene(&path) {
+                panic!("{}: {}", path.display(), err);
+            }
+        }
+    }
+
+    #[test]
+    fn test_missing_mesh_reports_path() {
+        let text = THREE_SPHERES.replace(
+            "type = \"cylinder\"\ncenter = [0.0, 1.0, 0.0]\nradius = 1.0\nheight = 2.0",
+            "type = \"mesh\"\npath = \"does/not/exist.obj\"",
+        );
+        let err = parse_scene(&text).err().unwrap().to_string();
+        assert!(err.contains("objects[1].path"), "{}", err);
+        assert!(err.contains("exist.obj"), "{}", err);
+    }
+
+    #[test]
+    fn test_degenerate_quad() {
+        let text = THREE_SPHERES.replace(
+            "type = \"cylinder\"\ncenter = [0.0, 1.0, 0.0]\nradius = 1.0\nheight = 2.0",
+            "type = \"quad\"\nq = [0.0, 0.0, 0.0]\nu = [1.0, 0.0, 0.0]\nv = [2.0, 0.0, 0.0]",
+        );
+        let err = parse_scene(&text).err().unwrap().to_string();
+        assert!(err.contains("objects[1].v"), "{}", err);
+    }
+
+    #[test]
+    fn test_cylinder_forms() {
+        let scene = parse_scene(THREE_SPHERES).unwrap();
+        match &scene.world.objects[1] {
+            SceneObject::Cylinder {
+                base,
+                top,
+                base_cap,
+                top_cap,
+                ..
+            } => {
+                assert_eq!(*base, Vec3::new(0.0, 0.0, 0.0));
+                assert_eq!(*top, Vec3::new(0.0, 2.0, 0.0));
+                assert!(*base_cap && *top_cap);
+            }
+            _ => panic!("expected a cylinder"),
+        }
+
+        let text = THREE_SPHERES.replace(
+            "center = [0.0, 1.0, 0.0]\nradius = 1.0\nheight = 2.0",
+            "base = [0.0, 0.0, 0.0]\ntop = [1.0, 0.0, 0.0]\nradius = 1.0\ntop_cap = false",
+        );
+        match &parse_scene(&text).unwrap().world.objects[1] {
+            SceneObject::Cylinder { top, top_cap, .. } => {
+                assert_eq!(*top, Vec3::new(1.0, 0.0, 0.0));
+                assert!(!*top_cap);
+            }
+            _ => panic!("expected a cylinder"),
+        }
+
+        let text = THREE_SPHERES.replace("height = 2.0", "top = [0.0, 2.0, 0.0]");
+        let err = parse_scene(&text).err().unwrap().to_string();
+        assert!(err.contains("objects[1].base"), "{}", err);
+    }
+
+    #[test]
+    fn test_analytic_shapes() {
+        let text = format!(
+            "{}{}",
+            THREE_SPHERES,
+            r#"
+[[objects]]
+type = "cone"
+base = [0.0, 0.0, 0.0]
+top = [0.0, 1.0, 0.0]
+base_radius = 0.5
+material = "glass"
+
+[[objects]]
+type = "disk"
+center = [0.0, 0.0, 0.0]
+normal = [0.0, 1.0, 0.0]
+radius = 2.0
+material = "glass"
+
+[[objects]]
+type = "torus"
+center = [0.0, 0.5, 0.0]
+major_radius = 1.0
+minor_radius = 0.2
+material = "glass"
+
+[[objects]]
+type = "capsule"
+base = [1.0, 0.0, 0.0]
+top = [1.0, 1.0, 0.0]
+radius = 0.3
+material = "glass"
+"#
+        );
+        let scene = parse_scene(&text).unwrap();
+        assert_eq!(scene.world.objects.len(), 6);
+        assert!(matches!(
+            scene.world.objects[2],
+            SceneObject::Cone {
+                top_radius: 0.0,
+                base_cap: true,
+                ..
+            }
+        ));
+        assert!(matches!(
+            scene.world.objects[4],
+            SceneObject::Torus { axis, .. } if axis == Vec3::new(0.0, 1.0, 0.0)
+        ));
+
+        let text = text.replace("minor_radius = 0.2", "minor_radius = 0.0");
+        let err = parse_scene(&text).err().unwrap().to_string();
+        assert!(err.contains("objects[4].minor_radius"), "{}", err);
+    }
+
+    #[test]
+    fn test_instances_share_shapes() {
+        let text = format!(
+            "{}{}",
+            THREE_SPHERES,
+            r#"
+[shapes.ball]
+type = "sphere"
+center = [0.0, 0.0, 0.0]
+radius = 1.0
+material = "glass"
+
+[[objects]]
+type = "instance"
+shape = "ball"
+translate = [3.0, 1.0, 0.0]
+
+[[objects]]
+type = "instance"
+shape = "ball"
+scale = [1.0, 0.5, 1.0]
+rotate = [0.0, 45.0, 0.0]
+"#
+        );
+        let scene = parse_scene(&text).unwrap();
+        let objects: Vec<&Arc<SceneObject>> = scene.world.objects[2..]
+            .iter()
+            .map(|object| match object {
+                SceneObject::Instance { object, .. } => object,
+                _ => panic!("expected an instance"),
+            })
+            .collect();
+        assert!(Arc::ptr_eq(objects[0], objects[1]));
+        let bbox = scene.world.objects[2].bounding_box();
+        assert_eq!((bbox.x.min, bbox.x.max, bbox.y.max), (2.0, 4.0, 2.0));
+
+        let text = text.replace("shape = \"ball\"\nscale", "shape = \"bal\"\nscale");
+        let err = parse_scene(&text).err().unwrap().to_string();
+        assert!(err.contains("objects[3].shape"), "{}", err);
+        assert!(err.contains("unknown shape `bal`"), "{}", err);
+    }
+
+    #[test]
+    fn test_motion() {
+        let text = format!(
+            "{}{}",
+            THREE_SPHERES.replace(
+                "image_width = 400",
+                "image_width = 400\nshutter_open = 0.0\nshutter_close = 1.0"
+            ),
+            r#"
+[shapes.ball]
+type = "sphere"
+center = [0.0, 0.0, 0.0]
+radius = 1.0
+material = "glass"
+
+[[objects]]
+type = "sphere"
+center = [0.0, 1.0, 0.0]
+end_center = [2.0, 1.0, 0.0]
+radius = 0.5
+material = "glass"
+
+[[objects]]
+type = "instance"
+shape = "ball"
+keyframes = [
+    { time = 0.0, translate = [0.0, 0.0, 4.0] },
+    { time = 1.0, translate = [0.0, 3.0, 4.0], scale = [2.0, 2.0, 2.0] },
+]
+"#
+        );
+        let scene = parse_scene(&text).unwrap();
+        assert_eq!(
+            (scene.camera.shutter_open, scene.camera.shutter_close),
+            (0.0, 1.0)
+        );
+        let bbox = scene.world.objects[2].bounding_box();
+        assert_eq!((bbox.x.min, bbox.x.max), (-0.5, 2.5));
+        match &scene.world.objects[3] {
+            SceneObject::MovingInstance { motion, .. } => {
+                assert_eq!(motion.keyframes().len(), 2);
+                let bbox = scene.world.objects[3].bounding_box();
+                assert_eq!((bbox.y.min, bbox.y.max), (-1.0, 5.0));
+            }
+            _ => panic!("expected a moving instance"),
+        }
+
+        let err = parse_scene(&text.replace("time = 1.0", "time = 0.0"))
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(err.contains("objects[3].keyframes[1].time"), "{}", err);
+        let err = parse_scene(&text.replace("scale = [2.0, 2.0, 2.0]", "scale = [-2.0, 2.0, 2.0]"))
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(err.contains("keep their signs"), "{}", err);
+        let err = parse_scene(&text.replace(
+            "shape = \"ball\"\n",
+            "shape = \"ball\"\nscale = [2.0, 2.0, 2.0]\n",
+        ))
+        .err()
+        .unwrap()
+        .to_string();
+        assert!(err.contains("objects[3].keyframes"), "{}", err);
+        let err = parse_scene(&text.replace("shutter_close = 1.0", "shutter_close = -1.0"))
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(err.contains("camera.shutter_close"), "{}", err);
+    }
+
+    #[test]
+    fn test_media() {
+        let text = format!(
+            "{}{}",
+            THREE_SPHERES,
+            r#"
+[materials.smoke]
+type = "isotropic"
+albedo = [0.2, 0.2, 0.2]
+
+[materials.haze]
+type = "henyey_greenstein"
+albedo = [0.9, 0.9, 0.9]
+g = 0.5
+
+[shapes.ball]
+type = "sphere"
+center = [0.0, 1.0, 0.0]
+radius = 1.0
+material = "glass"
+
+[[objects]]
+type = "constant_medium"
+boundary = "ball"
+density = 2.0
+material = "smoke"
+
+[atmosphere]
+density = 0.01
+material = "haze"
+radius = 50.0
+"#
+        );
+        let scene = parse_scene(&text).unwrap();
+        match &scene.world.objects[2] {
+            SceneObject::ConstantMedium { density, phase, .. } => {
+                assert_eq!(*density, 2.0);
+                assert!(matches!(phase.as_ref(), Material::Isotropic { .. }));
+            }
+            _ => panic!("expected a constant medium"),
+        }
+        let atmosphere = scene.camera.atmosphere.as_ref().unwrap();
+        assert_eq!(atmosphere.density, 0.01);
+        assert_eq!(atmosphere.radius, 50.0);
+        assert_eq!(atmosphere.center, Vec3::new(13.0, 2.0, 3.0));
+        assert!(matches!(
+            atmosphere.phase.as_ref(),
+            Material::HenyeyGreenstein { g, .. } if *g == 0.5
+        ));
+
+        let err = parse_scene(&text.replace("material = \"smoke\"", "material = \"glass\""))
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(err.contains("objects[2].material"), "{}", err);
+        assert!(err.contains("isotropic or henyey_greenstein"), "{}", err);
+        let err = parse_scene(&text.replace("density = 0.01", "density = 0.0"))
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(err.contains("atmosphere.density"), "{}", err);
+        let err = parse_scene(&text.replace("radius = 50.0", "radius = -1.0"))
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(err.contains("atmosphere.radius"), "{}", err);
+        let centered = text.replace("radius = 50.0", "radius = 50.0\ncenter = [0.0, 1.0, 0.0]");
+        let atmosphere = parse_scene(&centered).unwrap().camera.atmosphere.unwrap();
+        assert_eq!(atmosphere.center, Vec3::new(0.0, 1.0, 0.0));
+        let err = parse_scene(&text.replace("g = 0.5", "g = 1.0"))
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(err.contains("materials.haze.g"), "{}", err);
+        let err = parse_scene(&text.replace("boundary = \"ball\"", "boundary = \"box\""))
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(err.contains("unknown shape `box`"), "{}", err);
+    }
+
+    #[test]
+    fn test_parse_scene() {
+        let scene = parse_scene(THREE_SPHERES).unwrap();
+        assert_eq!(scene.world.objects.len(), 2);
+        assert_eq!(scene.camera.image_width, 400);
+        assert_eq!(scene.camera.samples_per_pixel, 500);
+    }
+
+    #[test]
+    fn test_unknown_material_reports_line() {
+        let text = THREE_SPHERES.replace("material = \"glass\"", "material = \"steel\"");
+        match parse_scene(&text) {
+            Err(SceneError::Invalid {
+                line,
+                column,
+                field,
+                ..
+            }) => {
+                assert_eq!(line, 24);
+                assert_eq!(column, 1);
+                assert_eq!(field, "objects[1].material");
+            }
+            _ => panic!("expected an invalid scene error"),
+        }
+    }
+
+    #[test]
+    fn test_textured_albedo() {
+        let text = THREE_SPHERES
+            .replace(
+                "[materials.ground]",
+                "[textures.tiles]\ntype = \"checker\"\nscale = 1.0\neven = [1.0, 1.0, 1.0]\nodd = [0.0, 0.0, 0.0]\n\n[materials.ground]",
+            )
+            .replace("albedo = [0.5, 0.5, 0.5]", "albedo = \"tiles\"");
+        let scene = parse_scene(&text).unwrap();
+        match scene.world.objects[0].material().as_ref() {
+            Material::Lambertian {
+                albedo: Texture::Checker { scale, .. },
+            } => assert_eq!(*scale, 1.0),
+            _ => panic!("expected a checker albedo"),
+        }
+
+        let text = text.replace("albedo = \"tiles\"", "albedo = \"bricks\"");
+        let err = parse_scene(&text).err().unwrap().to_string();
+        assert!(err.contains("materials.ground.albedo"), "{}", err);
+        assert!(err.contains("unknown texture `bricks`"), "{}", err);
+    }
+
+    #[test]
+    fn test_procedural_texture() {
+        let text = THREE_SPHERES
+            .replace(
+                "[materials.ground]",
+                "[textures.stone]\ntype = \"marble\"\nseed = 3\nscale = 4.0\ncolors = [[0.1, 0.1, 0.1], [0.5, 0.5, 0.5], [1.0, 1.0, 1.0]]\n\n[materials.ground]",
+            )
+            .replace("albedo = [0.5, 0.5, 0.5]", "albedo = \"stone\"");
+        let scene = parse_scene(&text).unwrap();
+        match scene.world.objects[0].material().as_ref() {
+            Material::Lambertian {
+                albedo: Texture::Marble { scale, ramp, .. },
+            } => {
+                assert_eq!(*scale, 4.0);
+                assert_eq!(ramp.sample(0.5), Vec3::new(0.5, 0.5, 0.5));
+            }
+            _ => panic!("expected a marble albedo"),
+        }
+
+        let text = text.replace(
+            "colors = [[0.1, 0.1, 0.1], [0.5, 0.5, 0.5], [1.0, 1.0, 1.0]]",
+            "colors = []",
+        );
+        let err = parse_scene(&text).err().unwrap().to_string();
+        assert!(err.contains("textures.stone.colors"), "{}", err);
+    }
+
+    #[test]
+    fn test_tone_mapping_settings() {
+        let text = THREE_SPHERES.replace(
+            "image_width = 400",
+            "image_width = 400\ntone_mapping = \"reinhard_extended\"\nwhite_point = 8.0\nexposure = -1.5\ngamma = 2.2",
+        );
+        let camera = parse_scene(&text).unwrap().camera;
+        assert_eq!(
+            camera.tone_mapper,
+            ToneMapper::ExtendedReinhard { white: 8.0 }
+        );
+        assert_eq!(camera.exposure, -1.5);
+        assert_eq!(camera.gamma_correction, 2.2);
+
+        let text = text.replace("gamma = 2.2", "gamma = -1.0");
+        let err = parse_scene(&text).err().unwrap().to_string();
+        assert!(err.contains("camera.gamma"), "{}", err);
+    }
+
+    #[test]
+    fn test_sampling_settings() {
+        let camera = parse_scene(THREE_SPHERES).unwrap().camera;
+        assert_eq!(camera.adaptive_sampling, None);
+        assert_eq!(camera.sampler, SamplerKind::Independent);
+        assert_eq!(camera.light_sampling, LightSampling::Power);
+
+        let text = THREE_SPHERES.replace(
+            "image_width = 400",
+            "image_width = 400\nadaptive_threshold = 0.05\nmin_samples = 32\nsampler = \"sobol\"",
+        );
+        let camera = parse_scene(&text).unwrap().camera;
+        assert_eq!(camera.sampler, SamplerKind::Sobol);
+        let lit = text.replace("sampler =", "light_sampling = \"bsdf\"\nsampler =");
+        let camera = parse_scene(&lit).unwrap().camera;
+        assert_eq!(camera.light_sampling, LightSampling::Bsdf);
+        assert_eq!(camera.filter, Filter::default());
+
+        let filtered = text.replace("sampler =", "filter = \"gaussian\"\nsampler =");
+        let camera = parse_scene(&filtered).unwrap().camera;
+        assert_eq!(camera.filter, Filter::new(FilterKind::Gaussian, 1.5));
+        let filtered = filtered.replace("sampler =", "filter_radius = 0.0\nsampler =");
+        let err = parse_scene(&filtered).err().unwrap().to_string();
+        assert!(err.contains("camera.filter_radius"), "{}", err);
+        assert_eq!(
+            camera.adaptive_sampling,
+            Some(AdaptiveSampling {
+                min_samples: 32,
+                threshold: 0.05
+            })
+        );
+
+        let text = text.replace("min_samples = 32", "min_samples = 1000");
+        let err = parse_scene(&text).err().unwrap().to_string();
+        assert!(err.contains("camera.min_samples"), "{}", err);
+    }
+
+    #[test]
+    fn test_min_samples_only_applies_to_adaptive_sampling() {
+        let text = THREE_SPHERES.replace(
+            "image_width = 400",
+            "image_width = 400\nsamples_per_pixel = 8",
+        );
+        let camera = parse_scene(&text).unwrap().camera;
+        assert_eq!(camera.samples_per_pixel, 8);
+        assert_eq!(camera.adaptive_sampling, None);
+
+        // The default minimum is clamped to the sample count
+        let text = text.replace(
+            "samples_per_pixel = 8",
+            "samples_per_pixel = 8\nadaptive_threshold = 0.05",
+        );
+        let camera = parse_scene(&text).unwrap().camera;
+        assert_eq!(
+            camera.adaptive_sampling,
+            Some(AdaptiveSampling {
+                min_samples: 8,
+                threshold: 0.05
+            })
+        );
+    }
+
+    #[test]
+    fn test_invalid_value_reports_field() {
+        let text = THREE_SPHERES.replace("radius = 1000.0", "radius = -1.0");
+        let err = parse_scene(&text).err().unwrap().to_string();
+        assert!(err.contains("objects[0].radius"), "{}", err);
+    }
+
+    #[test]
+    fn test_background_and_light() {
+        let text = THREE_SPHERES
+            .replace(
+                "image_width = 400",
+                "image_width = 400\nbackground = [0.0, 0.0, 0.0]",
+            )
+            .replace(
+                "type = \"dielectric\"\nrefraction_index = 1.5",
+                "type = \"diffuse_light\"\nemit = [4.0, 4.0, 4.0]",
+            );
+        let scene = parse_scene(&text).unwrap();
+        assert_eq!(
+            scene.camera.background,
+            Background::Color(Vec3::new(0.0, 0.0, 0.0))
+        );
+        assert!(scene.world.objects[1].material().is_emissive());
+
+        let text = THREE_SPHERES.replace(
+            "image_width = 400",
+            "image_width = 400\nbackground = \"night\"",
+        );
+        let err = parse_scene(&text).err().unwrap().to_string();
+        assert!(err.contains("camera.background"), "{}", err);
+    }
+
+    #[test]
+    fn test_unknown_field_is_a_parse_error() {
+        let text = THREE_SPHERES.replace("height = 2.0", "height = 2.0\nheigth = 3.0");
+        match parse_scene(&text) {
+            Err(SceneError::Parse(err)) => assert!(err.to_string().contains("heigth")),
+            _ => panic!("expected a parse error"),
+        }
+    }
+}
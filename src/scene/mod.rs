@@ -0,0 +1,3 @@
+pub mod loader;
+pub mod obj;
+pub mod random_spheres;
@@ -0,0 +1,327 @@
+//! Wavefront OBJ import.
+//!
+//! Supports `v`, `vn`, `vt`, `f` (any of the `v`, `v/vt`, `v//vn` and
+//! `v/vt/vn` corner forms, with negative relative indices) and `g`/`o`
+//! groups. Polygons are fan-triangulated, so they are expected to be convex.
+//! Material libraries, smoothing groups, points, lines and free-form
+//! curves and surfaces are ignored.
+
+use std::{fmt, fs, io, path::Path};
+
+use crate::{
+    vec3::vec3::{Point3, Vec3},
+    world::mesh::{MeshVertex, TriangleMesh},
+};
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io(io::Error),
+    Parse { line: usize, message: String },
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "failed to read OBJ file: {}", err),
+            Self::Parse { line, message } => write!(f, "line {}: {}", line, message),
+        }
+    }
+}
+
+impl std::error::Error for ObjError {}
+
+impl From<io::Error> for ObjError {
+    fn from(err: io::Error) -> Self {
+        ObjError::Io(err)
+    }
+}
+
+pub fn load_obj(path: impl AsRef<Path>) -> Result<TriangleMesh, ObjError> {
+    let text = fs::read_to_string(path)?;
+    parse_obj(&text)
+}
+
+pub fn parse_obj(text: &str) -> Result<TriangleMesh, ObjError> {
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut texcoords = Vec::new();
+    let mut triangles = Vec::new();
+    let mut groups = Vec::new();
+    let mut group: Option<(String, usize)> = None;
+
+    for (index, raw_line) in text.lines().enumerate() {
+        let line = index + 1;
+        let err = |message: String| ObjError::Parse { line, message };
+
+        let content = raw_line.split('#').next().unwrap_or("");
+        let mut tokens = content.split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+        let args: Vec<&str> = tokens.collect();
+
+        match keyword {
+            "v" => {
+                let [x, y, z] = parse_floats::<3>(&args, 3, 4).map_err(err)?;
+                positions.push(Point3::new(x, y, z));
+            }
+            "vn" => {
+                let [x, y, z] = parse_floats::<3>(&args, 3, 3).map_err(err)?;
+                normals.push(Vec3::new(x, y, z));
+            }
+            "vt" => {
+                let [u, v] = parse_floats::<2>(&args, 1, 3).map_err(err)?;
+                texcoords.push((u, v));
+            }
+            "f" => {
+                if args.len() < 3 {
+                    return Err(err(format!(
+                        "face needs at least 3 vertices, got {}",
+                        args.len()
+                    )));
+                }
+                let corners = args
+                    .iter()
+                    .map(|corner| {
+                        parse_corner(corner, positions.len(), texcoords.len(), normals.len())
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(err)?;
+
+                for i in 1..corners.len() - 1 {
+                    triangles.push([corners[0], corners[i], corners[i + 1]]);
+                }
+            }
+            "g" | "o" => {
+                if let Some((name, start)) = group.take() {
+                    if start < triangles.len() {
+                        groups.push((name, start..triangles.len()));
+                    }
+                }
+                let name = if args.is_empty() {
+                    "default".to_string()
+                } else {
+                    args.join(" ")
+                };
+                group = Some((name, triangles.len()));
+            }
+            // Materials and smoothing groups don't affect the geometry
+            "mtllib" | "usemtl" | "s" => {}
+            // Points, lines and free-form geometry have no faces to trace
+            "p" | "l" | "vp" | "cstype" | "deg" | "bmat" | "step" | "curv" | "curv2" | "surf"
+            | "parm" | "trim" | "hole" | "scrv" | "sp" | "end" | "con" => {}
+            _ => return Err(err(format!("unsupported statement `{}`", keyword))),
+        }
+    }
+
+    if let Some((name, start)) = group {
+        if start < triangles.len() {
+            groups.push((name, start..triangles.len()));
+        }
+    }
+    if triangles.is_empty() {
+        return Err(ObjError::Parse {
+            line: text.lines().count(),
+            message: "file contains no faces".to_string(),
+        });
+    }
+
+    Ok(TriangleMesh::new(
+        positions, normals, texcoords, triangles, groups,
+    ))
+}
+
+/// Parses between `min` and `max` numbers, keeping the first `N` and filling
+/// missing optional ones with zero.
+fn parse_floats<const N: usize>(args: &[&str], min: usize, max: usize) -> Result<[f32; N], String> {
+    if args.len() < min || args.len() > max {
+        let count = args.len();
+        return Err(format!(
+            "expected {} to {} numbers, got {}",
+            min, max, count
+        ));
+    }
+
+    let mut values = [0.0; N];
+    for (i, arg) in args.iter().enumerate() {
+        let value: f32 = arg
+            .parse()
+            .map_err(|_| format!("invalid number `{}`", arg))?;
+        if !value.is_finite() {
+            return Err(format!("invalid number `{}`", arg));
+        }
+        if i < N {
+            values[i] = value;
+        }
+    }
+    Ok(values)
+}
+
+/// Parses a `v`, `v/vt`, `v//vn` or `v/vt/vn` face corner.
+fn parse_corner(
+    corner: &str,
+    position_count: usize,
+    texcoord_count: usize,
+    normal_count: usize,
+) -> Result<MeshVertex, String> {
+    let mut parts = corner.split('/');
+    let position = resolve_index(parts.next().unwrap_or(""), position_count, "vertex")?;
+    let texcoord = match parts.next() {
+        None | Some("") => None,
+        Some(s) => Some(resolve_index(s, texcoord_count, "texture coordinate")?),
+    };
+    let normal = match parts.next() {
+        None | Some("") => None,
+        Some(s) => Some(resolve_index(s, normal_count, "normal")?),
+    };
+    if parts.next().is_some() {
+        return Err(format!("malformed face vertex `{}`", corner));
+    }
+
+    Ok(MeshVertex {
+        position,
+        normal,
+        texcoord,
+    })
+}
+
+/// Turns a 1-based (or negative, relative to the end) OBJ index into a
+/// 0-based one.
+fn resolve_index(s: &str, count: usize, kind: &str) -> Result<usize, String> {
+    let index: i64 = s
+        .parse()
+        .map_err(|_| format!("invalid {} index `{}`", kind, s))?;
+    let resolved = if index > 0 {
+        index - 1
+    } else {
+        count as i64 + index
+    };
+
+    if index == 0 || resolved < 0 || resolved >= count as i64 {
+        return Err(format!(
+            "{} index {} out of range ({} defined so far)",
+            kind, index, count
+        ));
+    }
+    Ok(resolved as usize)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        ray::ray::Ray,
+        utils::{constants::INFINITY, interval::Interval},
+        world::hit_record::HitRecord,
+    };
+
+    const QUAD: &str = "
+# unit square in the z = 0 plane
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+vt 0 0
+vt 1 0
+vt 1 1
+vt 0 1
+vn 0 0 1
+g square
+f 1/1/1 2/2/1 3/3/1 4/4/1
+";
+
+    #[test]
+    fn test_parse_quad() {
+        let mesh = parse_obj(QUAD).unwrap();
+        assert_eq!(mesh.positions.len(), 4);
+        assert_eq!(mesh.triangles.len(), 2);
+        assert_eq!(mesh.groups, vec![("square".to_string(), 0..2)]);
+        assert_eq!(mesh.triangles[1][2].texcoord, Some(3));
+    }
+
+    #[test]
+    fn test_hit_interpolates_texcoords() {
+        let mesh = parse_obj(QUAD).unwrap();
+        let ray = Ray::new(Point3::new(0.25, 0.75, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        let mut rec = HitRecord::default();
+        assert!(mesh.hit(&ray, Interval::new(0.001, INFINITY), &mut rec));
+        assert!((rec.u - 0.25).abs() < 1e-6);
+        assert!((rec.v - 0.75).abs() < 1e-6);
+        assert_eq!(rec.normal, Vec3::new(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn test_negative_indices_and_corner_forms() {
+        let text = "v 0 0 0\nv 1 0 0\nv 0 1 0\nvn 0 0 1\nf -3//1 -2//1 -1//1\nf 1 2 3\n";
+        let mesh = parse_obj(text).unwrap();
+        assert_eq!(mesh.triangles.len(), 2);
+        assert_eq!(mesh.triangles[0][0].position, 0);
+        assert_eq!(mesh.triangles[0][0].normal, Some(0));
+        assert_eq!(mesh.triangles[1][0].normal, None);
+    }
+
+    #[test]
+    fn test_smooth_normals() {
+        // Two faces folded along the x axis
+        let text = "v 0 0 0\nv 1 0 0\nv 0 1 0\nv 0 0 1\nf 1 2 3\nf 2 1 4\n";
+        let mut mesh = parse_obj(text).unwrap();
+        mesh.compute_vertex_normals();
+        let shared = mesh.normals[0];
+        assert!((shared.length() - 1.0).abs() < 1e-6);
+        assert!((shared.y - shared.z).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_skips_lines_and_free_form_geometry() {
+        let text = format!(
+            "{}l 1 2 3\np 4\ncstype bspline\ndeg 3\ncurv 0 1 1 2 3 4\nparm u 0 0 0 0 1 1 1 1\nend\n",
+            QUAD
+        );
+        let mesh = parse_obj(&text).unwrap();
+        assert_eq!(mesh.triangles.len(), 2);
+    }
+
+    #[test]
+    fn test_cancelling_normals_fall_back_to_the_face() {
+        let ray = Ray::new(Point3::new(0.25, 0.25, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        let mut rec = HitRecord::default();
+
+        // Opposing normals at the corners of one face
+        let text = "v 0 0 0\nv 1 0 0\nv 0 1 0\nvn 0 0 1\nvn 0 0 -1\nf 1//1 2//2 3//2\n";
+        let mesh = parse_obj(text).unwrap();
+        assert!(mesh.hit(&ray, Interval::new(0.001, INFINITY), &mut rec));
+        assert_eq!(rec.normal, Vec3::new(0.0, 0.0, 1.0));
+
+        // A double-sided face, whose two sides cancel at every vertex
+        let mut mesh = parse_obj("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\nf 1 3 2\n").unwrap();
+        mesh.compute_vertex_normals();
+        assert!(mesh.hit(&ray, Interval::new(0.001, INFINITY), &mut rec));
+        assert_eq!(rec.normal, Vec3::new(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn test_errors_report_line() {
+        let cases = [
+            ("v 0 0\n", 1, "expected 3 to 4 numbers"),
+            ("v 0 0 0\nv 1 0 0\nf 1 2\n", 3, "at least 3 vertices"),
+            ("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 4\n", 4, "out of range"),
+            (
+                "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1/x 2 3\n",
+                4,
+                "invalid texture",
+            ),
+            ("v 0 0 zero\n", 1, "invalid number"),
+            ("v 0 0 0\nbogus 0 1\n", 2, "unsupported"),
+            ("v 0 0 0\n", 1, "no faces"),
+        ];
+        for (text, expected_line, expected_message) in cases {
+            match parse_obj(text) {
+                Err(ObjError::Parse { line, message }) => {
+                    assert_eq!(line, expected_line, "{}", text);
+                    assert!(message.contains(expected_message), "{}", message);
+                }
+                _ => panic!("expected a parse error for {:?}", text),
+            }
+        }
+    }
+}
@@ -0,0 +1,134 @@
+use std::sync::Arc;
+
+use rand::Rng;
+
+use crate::{
+    camera::camera::Camera,
+    vec3::{
+        color3::Color3,
+        vec3::{Point3, Vec3},
+    },
+    world::{material::Material, scene_object::SceneObject, scene_object_list::SceneObjectList},
+};
+
+/// The cover scene of the book: a large ground sphere, a 22x22 grid of small
+/// random spheres and cylinders, and three hero spheres. All randomness
+/// comes from `rng`, so a seeded generator always gives the same scene.
+pub fn random_spheres_scene(rng: &mut impl Rng) -> SceneObjectList {
+    let mut world = SceneObjectList::new();
+
+    // Ground
+    let ground_material = Arc::new(Material::Lambertian {
+        albedo: Color3::new(0.5, 0.5, 0.5).into(),
+    });
+    world.add(SceneObject::Sphere {
+        center: Vec3::new(0.0, -1000.0, 0.0),
+        radius: 1000.0,
+        material: ground_material.clone(),
+    });
+
+    for a in -11..11 {
+        for b in -11..11 {
+            let choose_mat: f64 = rng.gen();
+            let center = Vec3::new(
+                a as f32 + 0.9 * rng.gen::<f32>(),
+                0.2,
+                b as f32 + 0.9 * rng.gen::<f32>(),
+            );
+
+            if (center - Vec3::new(4.0, 0.2, 0.0)).length() > 0.9 {
+                let material = if choose_mat < 0.8 {
+                    // diffuse
+                    let albedo = random_color(rng, 0.0, 1.0) * random_color(rng, 0.0, 1.0);
+                    Arc::new(Material::Lambertian {
+                        albedo: albedo.into(),
+                    })
+                } else if choose_mat < 0.95 {
+                    // metal
+                    let albedo = random_color(rng, 0.5, 1.0);
+                    let fuzz = rng.gen_range(0.0..0.5);
+                    Arc::new(Material::Metal {
+                        albedo: albedo.into(),
+                        fuzz,
+                    })
+                } else {
+                    // glass
+                    Arc::new(Material::Dielectric {
+                        refraction_index: 1.5,
+                    })
+                };
+
+                if rng.gen::<f32>() > 0.5 {
+                    world.add(SceneObject::Sphere {
+                        center,
+                        radius: 0.2,
+                        material,
+                    });
+                } else {
+                    let half_height = Vec3::new(0.0, 0.25, 0.0);
+                    world.add(SceneObject::Cylinder {
+                        radius: 0.2,
+                        base: center - half_height,
+                        top: center + half_height,
+                        base_cap: true,
+                        top_cap: true,
+                        material,
+                    });
+                }
+            }
+        }
+    }
+
+    // Three main spheres
+    let material1 = Arc::new(Material::Dielectric {
+        refraction_index: 1.5,
+    });
+    let material2 = Arc::new(Material::Lambertian {
+        albedo: Color3::new(0.4, 0.2, 0.1).into(),
+    });
+    let material3 = Arc::new(Material::Metal {
+        albedo: Color3::new(0.7, 0.6, 0.5).into(),
+        fuzz: 0.0,
+    });
+
+    world.add(SceneObject::Sphere {
+        center: Vec3::new(0.0, 1.0, 0.0),
+        radius: 1.0,
+        material: material1,
+    });
+    world.add(SceneObject::Sphere {
+        center: Vec3::new(-4.0, 1.0, 0.0),
+        radius: 1.0,
+        material: material2,
+    });
+    world.add(SceneObject::Sphere {
+        center: Vec3::new(4.0, 1.0, 0.0),
+        radius: 1.0,
+        material: material3,
+    });
+
+    world
+}
+
+fn random_color(rng: &mut impl Rng, min: f32, max: f32) -> Color3 {
+    Color3::new(
+        rng.gen_range(min..max),
+        rng.gen_range(min..max),
+        rng.gen_range(min..max),
+    )
+}
+
+pub fn random_spheres_camera() -> Camera {
+    Camera::new(
+        1200,
+        16.0 / 9.0,
+        500,
+        25.0,
+        20,
+        Point3::new(13.0, 2.0, 3.0),
+        Point3::new(0.0, 0.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        0.6,
+        10.0,
+    )
+}
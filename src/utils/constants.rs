@@ -1,6 +1,6 @@
 use super::interval::Interval;
 
-pub const PI: f32 = 3.141_592_7;
+pub const PI: f32 = std::f32::consts::PI;
 pub const INFINITY: f32 = f32::MAX;
 pub const MAX_OBJECTS_ONSCREEN: u32 = 100;
 pub const EPSILON: f32 = 1e-8;
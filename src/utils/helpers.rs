@@ -5,6 +5,29 @@ pub fn degress_to_radians(degress: f32) -> f32 {
     degress * PI / 180.0
 }
 
+/// Seeds the calling thread's generator behind `random_f32`.
+pub fn seed_random(seed: u64) {
+    fastrand::seed(seed);
+}
+
+/// Mixes `values` into `seed`, giving independent seeds for every
+/// combination, e.g. one per pixel and sample of a render.
+pub fn derive_seed(seed: u64, values: &[u64]) -> u64 {
+    values
+        .iter()
+        .fold(splitmix64(seed), |hash, &value| splitmix64(hash ^ value))
+}
+
+/// Finalizer of the SplitMix64 generator, a cheap bijective hash with good
+/// avalanche behaviour.
+#[inline]
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
 pub fn random_f32() -> f32 {
     fastrand::f32()
 }
@@ -12,3 +35,25 @@ pub fn random_f32() -> f32 {
 pub fn random_f32_with_range(min: f32, max: f32) -> f32 {
     min + (max - min) * random_f32()
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_derive_seed_separates_streams() {
+        assert_eq!(derive_seed(1, &[2, 3]), derive_seed(1, &[2, 3]));
+        assert_ne!(derive_seed(1, &[2, 3]), derive_seed(1, &[3, 2]));
+        assert_ne!(derive_seed(1, &[2, 3]), derive_seed(2, &[2, 3]));
+        assert_ne!(derive_seed(0, &[0]), derive_seed(0, &[]));
+    }
+
+    #[test]
+    fn test_seeded_sequence_repeats() {
+        seed_random(42);
+        let first: Vec<f32> = (0..4).map(|_| random_f32()).collect();
+        seed_random(42);
+        let second: Vec<f32> = (0..4).map(|_| random_f32()).collect();
+        assert_eq!(first, second);
+    }
+}
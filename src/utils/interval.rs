@@ -16,6 +16,23 @@ impl Interval {
         }
     }
 
+    /// Smallest interval containing both `a` and `b`.
+    pub fn enclosing(a: &Interval, b: &Interval) -> Self {
+        Interval {
+            min: a.min.min(b.min),
+            max: a.max.max(b.max),
+        }
+    }
+
+    /// Widens the interval by `delta / 2` on each side.
+    pub fn expand(&self, delta: f32) -> Self {
+        let padding = delta / 2.0;
+        Interval {
+            min: self.min - padding,
+            max: self.max + padding,
+        }
+    }
+
     pub fn size(&self) -> f32 {
         self.max - self.min
     }
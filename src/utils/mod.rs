@@ -1,3 +1,4 @@
 pub mod constants;
 pub mod helpers;
 pub mod interval;
+pub mod polynomial;
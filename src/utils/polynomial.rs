@@ -0,0 +1,173 @@
+//! Real roots of polynomials up to degree four, for shapes such as the torus
+//! whose intersections have no well-conditioned closed form.
+
+const MAX_DEGREE: usize = 4;
+const MAX_ITERATIONS: usize = 100;
+// Relative step size at which refinement stops
+const TOLERANCE: f64 = 1e-12;
+
+/// Real roots in ascending order.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Roots {
+    values: [f64; MAX_DEGREE],
+    len: usize,
+}
+
+impl Roots {
+    pub fn as_slice(&self) -> &[f64] {
+        &self.values[..self.len]
+    }
+
+    fn push(&mut self, root: f64) {
+        if self.len < MAX_DEGREE {
+            self.values[self.len] = root;
+            self.len += 1;
+        }
+    }
+}
+
+/// Evaluates the polynomial with `coeffs` (constant term first) at `x`.
+#[inline]
+pub fn evaluate(coeffs: &[f64], x: f64) -> f64 {
+    coeffs.iter().rev().fold(0.0, |acc, &c| acc * x + c)
+}
+
+/// Real roots in `[lo, hi]` of the polynomial with `coeffs` (constant term
+/// first, at most degree four).
+///
+/// Rather than the closed-form cubic and quartic formulas, which lose most
+/// of their digits in `f32` and many in `f64`, each root is isolated between
+/// consecutive roots of the derivative, where the polynomial is monotone,
+/// and refined by Newton steps safeguarded with bisection. Roots of even
+/// multiplicity, where the polynomial touches zero without crossing it, are
+/// only found if they evaluate to exactly zero.
+pub fn solve_in(coeffs: &[f64], lo: f64, hi: f64) -> Roots {
+    assert!(coeffs.len() <= MAX_DEGREE + 1);
+    let mut roots = Roots::default();
+    let degree = match coeffs.iter().rposition(|&c| c != 0.0) {
+        Some(degree) if degree > 0 && lo <= hi => degree,
+        _ => return roots,
+    };
+    let coeffs = &coeffs[..=degree];
+
+    let mut derivative = [0.0; MAX_DEGREE];
+    for i in 1..=degree {
+        derivative[i - 1] = i as f64 * coeffs[i];
+    }
+    let critical = solve_in(&derivative[..degree], lo, hi);
+
+    let mut a = lo;
+    let mut fa = evaluate(coeffs, lo);
+    if fa == 0.0 {
+        roots.push(lo);
+    }
+    for &b in critical.as_slice().iter().chain(std::iter::once(&hi)) {
+        if b <= a {
+            continue;
+        }
+        let fb = evaluate(coeffs, b);
+        if fb == 0.0 {
+            roots.push(b);
+        } else if fa * fb < 0.0 {
+            roots.push(refine(coeffs, &derivative[..degree], a, b, fa));
+        }
+        a = b;
+        fa = fb;
+    }
+    roots
+}
+
+/// Root of a polynomial that is monotone on `[lo, hi]` and changes sign.
+fn refine(coeffs: &[f64], derivative: &[f64], mut lo: f64, mut hi: f64, f_lo: f64) -> f64 {
+    let mut x = 0.5 * (lo + hi);
+    for _ in 0..MAX_ITERATIONS {
+        let f = evaluate(coeffs, x);
+        if f == 0.0 {
+            return x;
+        }
+        if (f < 0.0) == (f_lo < 0.0) {
+            lo = x;
+        } else {
+            hi = x;
+        }
+
+        // Newton step, falling back to bisection when it leaves the bracket
+        let newton = x - f / evaluate(derivative, x);
+        let next = if newton > lo && newton < hi {
+            newton
+        } else {
+            0.5 * (lo + hi)
+        };
+        if (next - x).abs() <= TOLERANCE * x.abs().max(1.0) {
+            return next;
+        }
+        x = next;
+    }
+    x
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // Coefficients of the monic polynomial with the given roots
+    fn from_roots(roots: &[f64]) -> Vec<f64> {
+        let mut coeffs = vec![1.0];
+        for &root in roots {
+            let mut next = vec![0.0; coeffs.len() + 1];
+            for (i, &c) in coeffs.iter().enumerate() {
+                next[i + 1] += c;
+                next[i] -= root * c;
+            }
+            coeffs = next;
+        }
+        coeffs
+    }
+
+    fn assert_roots(found: Roots, expected: &[f64]) {
+        assert_eq!(found.as_slice().len(), expected.len(), "{:?}", found);
+        for (root, expected) in found.as_slice().iter().zip(expected) {
+            assert!(
+                (root - expected).abs() <= 1e-9 * expected.abs().max(1.0),
+                "{} != {}",
+                root,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_low_degrees() {
+        assert_roots(solve_in(&[-2.0, 4.0], -10.0, 10.0), &[0.5]);
+        assert_roots(
+            solve_in(&from_roots(&[-3.0, 2.0]), -10.0, 10.0),
+            &[-3.0, 2.0],
+        );
+        assert_roots(solve_in(&[1.0, 0.0, 1.0], -10.0, 10.0), &[]);
+        assert_roots(solve_in(&[5.0], -10.0, 10.0), &[]);
+        assert_roots(
+            solve_in(&from_roots(&[-1.0, 0.25, 7.0]), -10.0, 10.0),
+            &[-1.0, 0.25, 7.0],
+        );
+    }
+
+    #[test]
+    fn test_quartic_roots_in_range() {
+        let coeffs = from_roots(&[-2.0, 0.5, 1.5, 4.0]);
+        assert_roots(solve_in(&coeffs, -10.0, 10.0), &[-2.0, 0.5, 1.5, 4.0]);
+        assert_roots(solve_in(&coeffs, 0.0, 2.0), &[0.5, 1.5]);
+        assert_roots(solve_in(&coeffs, 1.5, 3.0), &[1.5]);
+        // Leading zeros lower the degree
+        let mut padded = from_roots(&[1.0, 2.0]);
+        padded.extend([0.0, 0.0]);
+        assert_roots(solve_in(&padded, -10.0, 10.0), &[1.0, 2.0]);
+    }
+
+    #[test]
+    fn test_ill_conditioned_quartic() {
+        // Widely spread and nearly coincident roots, as for a ray grazing a
+        // thin torus far from the camera
+        let roots = [1e-3, 1.0, 1.0 + 1e-6, 1e3];
+        assert_roots(solve_in(&from_roots(&roots), -1e4, 1e4), &roots);
+    }
+}
@@ -1,2 +1,4 @@
 pub mod color3;
+pub mod onb;
+pub mod transform;
 pub mod vec3;
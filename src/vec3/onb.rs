@@ -0,0 +1,29 @@
+use super::vec3::Vec3;
+
+/// Orthonormal basis whose `w` axis is aligned with a given direction, used
+/// to turn samples generated around +Z into world space.
+#[derive(Debug, Copy, Clone)]
+pub struct Onb {
+    pub u: Vec3,
+    pub v: Vec3,
+    pub w: Vec3,
+}
+
+impl Onb {
+    pub fn new(n: Vec3) -> Self {
+        let w = Vec3::unit(n);
+        let a = if w.x.abs() > 0.9 {
+            Vec3::new(0.0, 1.0, 0.0)
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let v = Vec3::unit(Vec3::cross_product(w, a));
+        let u = Vec3::cross_product(w, v);
+        Onb { u, v, w }
+    }
+
+    #[inline]
+    pub fn transform(&self, v: Vec3) -> Vec3 {
+        v.x * self.u + v.y * self.v + v.z * self.w
+    }
+}
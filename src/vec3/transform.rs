@@ -0,0 +1,723 @@
+use std::f32::consts::PI;
+use std::ops::Mul;
+
+use super::vec3::{Point3, Vec3};
+use crate::utils::helpers::degress_to_radians;
+
+/// Row-major 4x4 matrix acting on column vectors, with points taking 1 and
+/// directions 0 as their fourth coordinate.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Mat4 {
+    pub m: [[f32; 4]; 4],
+}
+
+impl Mat4 {
+    pub const IDENTITY: Mat4 = Mat4 {
+        m: [
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ],
+    };
+
+    pub fn new(m: [[f32; 4]; 4]) -> Self {
+        Mat4 { m }
+    }
+
+    pub fn translation(offset: Vec3) -> Self {
+        Mat4::new([
+            [1.0, 0.0, 0.0, offset.x],
+            [0.0, 1.0, 0.0, offset.y],
+            [0.0, 0.0, 1.0, offset.z],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn scaling(factors: Vec3) -> Self {
+        Mat4::new([
+            [factors.x, 0.0, 0.0, 0.0],
+            [0.0, factors.y, 0.0, 0.0],
+            [0.0, 0.0, factors.z, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    /// Counter-clockwise rotation by `degrees` when looking down `axis`
+    /// towards the origin (Rodrigues' formula).
+    pub fn rotation(axis: Vec3, degrees: f32) -> Self {
+        let Vec3 { x, y, z } = Vec3::unit(axis);
+        let (s, c) = degress_to_radians(degrees).sin_cos();
+        let t = 1.0 - c;
+        Mat4::new([
+            [t * x * x + c, t * x * y - s * z, t * x * z + s * y, 0.0],
+            [t * x * y + s * z, t * y * y + c, t * y * z - s * x, 0.0],
+            [t * x * z - s * y, t * y * z + s * x, t * z * z + c, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn transpose(&self) -> Self {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = self.m[j][i];
+            }
+        }
+        Mat4 { m }
+    }
+
+    /// Inverse by cofactor expansion over 2x2 sub-determinants, or `None`
+    /// for a singular matrix.
+    pub fn inverse(&self) -> Option<Self> {
+        let m = &self.m;
+        let s0 = m[0][0] * m[1][1] - m[1][0] * m[0][1];
+        let s1 = m[0][0] * m[1][2] - m[1][0] * m[0][2];
+        let s2 = m[0][0] * m[1][3] - m[1][0] * m[0][3];
+        let s3 = m[0][1] * m[1][2] - m[1][1] * m[0][2];
+        let s4 = m[0][1] * m[1][3] - m[1][1] * m[0][3];
+        let s5 = m[0][2] * m[1][3] - m[1][2] * m[0][3];
+        let c5 = m[2][2] * m[3][3] - m[3][2] * m[2][3];
+        let c4 = m[2][1] * m[3][3] - m[3][1] * m[2][3];
+        let c3 = m[2][1] * m[3][2] - m[3][1] * m[2][2];
+        let c2 = m[2][0] * m[3][3] - m[3][0] * m[2][3];
+        let c1 = m[2][0] * m[3][2] - m[3][0] * m[2][2];
+        let c0 = m[2][0] * m[3][1] - m[3][0] * m[2][1];
+
+        let det = s0 * c5 - s1 * c4 + s2 * c3 + s3 * c2 - s4 * c1 + s5 * c0;
+        if det == 0.0 || !det.is_finite() {
+            return None;
+        }
+        let inv = 1.0 / det;
+
+        Some(Mat4::new([
+            [
+                (m[1][1] * c5 - m[1][2] * c4 + m[1][3] * c3) * inv,
+                (-m[0][1] * c5 + m[0][2] * c4 - m[0][3] * c3) * inv,
+                (m[3][1] * s5 - m[3][2] * s4 + m[3][3] * s3) * inv,
+                (-m[2][1] * s5 + m[2][2] * s4 - m[2][3] * s3) * inv,
+            ],
+            [
+                (-m[1][0] * c5 + m[1][2] * c2 - m[1][3] * c1) * inv,
+                (m[0][0] * c5 - m[0][2] * c2 + m[0][3] * c1) * inv,
+                (-m[3][0] * s5 + m[3][2] * s2 - m[3][3] * s1) * inv,
+                (m[2][0] * s5 - m[2][2] * s2 + m[2][3] * s1) * inv,
+            ],
+            [
+                (m[1][0] * c4 - m[1][1] * c2 + m[1][3] * c0) * inv,
+                (-m[0][0] * c4 + m[0][1] * c2 - m[0][3] * c0) * inv,
+                (m[3][0] * s4 - m[3][1] * s2 + m[3][3] * s0) * inv,
+                (-m[2][0] * s4 + m[2][1] * s2 - m[2][3] * s0) * inv,
+            ],
+            [
+                (-m[1][0] * c3 + m[1][1] * c1 - m[1][2] * c0) * inv,
+                (m[0][0] * c3 - m[0][1] * c1 + m[0][2] * c0) * inv,
+                (-m[3][0] * s3 + m[3][1] * s1 - m[3][2] * s0) * inv,
+                (m[2][0] * s3 - m[2][1] * s1 + m[2][2] * s0) * inv,
+            ],
+        ]))
+    }
+
+    /// Applies the matrix to a point, dividing by the fourth coordinate for
+    /// projective matrices.
+    #[inline]
+    pub fn transform_point(&self, p: Point3) -> Point3 {
+        let m = &self.m;
+        let x = m[0][0] * p.x + m[0][1] * p.y + m[0][2] * p.z + m[0][3];
+        let y = m[1][0] * p.x + m[1][1] * p.y + m[1][2] * p.z + m[1][3];
+        let z = m[2][0] * p.x + m[2][1] * p.y + m[2][2] * p.z + m[2][3];
+        let w = m[3][0] * p.x + m[3][1] * p.y + m[3][2] * p.z + m[3][3];
+        if w == 1.0 {
+            Point3::new(x, y, z)
+        } else {
+            Point3::new(x, y, z) / w
+        }
+    }
+
+    /// Applies the upper 3x3 part of the matrix, ignoring the translation.
+    #[inline]
+    pub fn transform_vector(&self, v: Vec3) -> Vec3 {
+        let m = &self.m;
+        Vec3::new(
+            m[0][0] * v.x + m[0][1] * v.y + m[0][2] * v.z,
+            m[1][0] * v.x + m[1][1] * v.y + m[1][2] * v.z,
+            m[2][0] * v.x + m[2][1] * v.y + m[2][2] * v.z,
+        )
+    }
+}
+
+impl Default for Mat4 {
+    fn default() -> Self {
+        Mat4::IDENTITY
+    }
+}
+
+impl Mul for Mat4 {
+    type Output = Mat4;
+
+    fn mul(self, rhs: Mat4) -> Mat4 {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..4).map(|k| self.m[i][k] * rhs.m[k][j]).sum();
+            }
+        }
+        Mat4 { m }
+    }
+}
+
+/// An invertible transform, keeping its inverse alongside so that rays can
+/// be taken into object space and normals brought back out without
+/// inverting per hit.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct Transform {
+    matrix: Mat4,
+    inverse: Mat4,
+}
+
+impl Transform {
+    pub const IDENTITY: Transform = Transform {
+        matrix: Mat4::IDENTITY,
+        inverse: Mat4::IDENTITY,
+    };
+
+    /// `None` if `matrix` is singular.
+    pub fn new(matrix: Mat4) -> Option<Self> {
+        matrix
+            .inverse()
+            .map(|inverse| Transform { matrix, inverse })
+    }
+
+    pub fn translate(offset: Vec3) -> Self {
+        Transform {
+            matrix: Mat4::translation(offset),
+            inverse: Mat4::translation(-offset),
+        }
+    }
+
+    pub fn scale(factors: Vec3) -> Self {
+        assert!(
+            factors.x != 0.0 && factors.y != 0.0 && factors.z != 0.0,
+            "scale factors must not be zero"
+        );
+        Transform {
+            matrix: Mat4::scaling(factors),
+            inverse: Mat4::scaling(Vec3::new(1.0 / factors.x, 1.0 / factors.y, 1.0 / factors.z)),
+        }
+    }
+
+    pub fn rotate(axis: Vec3, degrees: f32) -> Self {
+        Transform::orthonormal(Mat4::rotation(axis, degrees))
+    }
+
+    pub fn from_quaternion(rotation: Quaternion) -> Self {
+        Transform::orthonormal(rotation.to_mat4())
+    }
+
+    fn orthonormal(matrix: Mat4) -> Self {
+        Transform {
+            matrix,
+            inverse: matrix.transpose(),
+        }
+    }
+
+    /// Scaling by `scale`, then `rotation`, then translation by
+    /// `translate`, with both matrices written out directly instead of
+    /// multiplied together, since animations build one for every hit test.
+    fn scale_rotate_translate(scale: Vec3, rotation: &Quaternion, translate: Vec3) -> Self {
+        assert!(
+            scale.x != 0.0 && scale.y != 0.0 && scale.z != 0.0,
+            "scale factors must not be zero"
+        );
+        let r = rotation.to_mat4().m;
+        let s = [scale.x, scale.y, scale.z];
+        let inverse_s = s.map(|factor| 1.0 / factor);
+        let t = [translate.x, translate.y, translate.z];
+
+        let mut matrix = Mat4::IDENTITY;
+        let mut inverse = Mat4::IDENTITY;
+        for i in 0..3 {
+            for j in 0..3 {
+                matrix.m[i][j] = r[i][j] * s[j];
+                inverse.m[i][j] = r[j][i] * inverse_s[i];
+            }
+            matrix.m[i][3] = t[i];
+        }
+        for i in 0..3 {
+            inverse.m[i][3] = -(0..3).map(|j| inverse.m[i][j] * t[j]).sum::<f32>();
+        }
+        Transform { matrix, inverse }
+    }
+
+    /// This transform followed by `next`.
+    pub fn then(&self, next: &Transform) -> Self {
+        Transform {
+            matrix: next.matrix * self.matrix,
+            inverse: self.inverse * next.inverse,
+        }
+    }
+
+    pub fn inverse(&self) -> Self {
+        Transform {
+            matrix: self.inverse,
+            inverse: self.matrix,
+        }
+    }
+
+    pub fn matrix(&self) -> &Mat4 {
+        &self.matrix
+    }
+
+    /// Factor by which the transform scales volumes: the determinant of its
+    /// linear part.
+    pub fn volume_scale(&self) -> f32 {
+        let m = &self.matrix.m;
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    }
+
+    #[inline]
+    pub fn point(&self, p: Point3) -> Point3 {
+        self.matrix.transform_point(p)
+    }
+
+    #[inline]
+    pub fn vector(&self, v: Vec3) -> Vec3 {
+        self.matrix.transform_vector(v)
+    }
+
+    /// Transforms a surface normal by the inverse transpose, which keeps it
+    /// perpendicular to the transformed surface under non-uniform scaling.
+    /// The result is not normalized.
+    #[inline]
+    pub fn normal(&self, n: Vec3) -> Vec3 {
+        let m = &self.inverse.m;
+        Vec3::new(
+            m[0][0] * n.x + m[1][0] * n.y + m[2][0] * n.z,
+            m[0][1] * n.x + m[1][1] * n.y + m[2][1] * n.z,
+            m[0][2] * n.x + m[1][2] * n.y + m[2][2] * n.z,
+        )
+    }
+
+    #[inline]
+    pub fn inverse_point(&self, p: Point3) -> Point3 {
+        self.inverse.transform_point(p)
+    }
+
+    #[inline]
+    pub fn inverse_vector(&self, v: Vec3) -> Vec3 {
+        self.inverse.transform_vector(v)
+    }
+}
+
+/// Unit quaternion for a rotation. Unlike rotation matrices, quaternions
+/// can be interpolated without the result shearing or shrinking.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Quaternion {
+    w: f32,
+    v: Vec3,
+}
+
+impl Quaternion {
+    pub const IDENTITY: Quaternion = Quaternion {
+        w: 1.0,
+        v: Vec3 {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+        },
+    };
+
+    /// Same rotation as `Mat4::rotation(axis, degrees)`.
+    pub fn from_axis_angle(axis: Vec3, degrees: f32) -> Self {
+        let (s, c) = (0.5 * degress_to_radians(degrees)).sin_cos();
+        Quaternion {
+            w: c,
+            v: s * Vec3::unit(axis),
+        }
+    }
+
+    /// This rotation followed by `next`.
+    pub fn then(&self, next: &Quaternion) -> Self {
+        Quaternion {
+            w: next.w * self.w - Vec3::dot_product(next.v, self.v),
+            v: next.w * self.v + self.w * next.v + Vec3::cross_product(next.v, self.v),
+        }
+        .normalized()
+    }
+
+    fn dot(&self, other: &Quaternion) -> f32 {
+        self.w * other.w + Vec3::dot_product(self.v, other.v)
+    }
+
+    fn normalized(&self) -> Self {
+        let length = self.dot(self).sqrt();
+        Quaternion {
+            w: self.w / length,
+            v: self.v / length,
+        }
+    }
+
+    /// Angle in radians of the shortest rotation taking `self` to `other`.
+    pub fn angle_to(&self, other: &Quaternion) -> f32 {
+        2.0 * self.dot(other).abs().min(1.0).acos()
+    }
+
+    /// Rotation a fraction `t` of the way from `self` to `other` along the
+    /// shortest arc, turning at a constant rate.
+    pub fn slerp(&self, other: &Quaternion, t: f32) -> Self {
+        Slerp::new(self, other).at(t)
+    }
+
+    pub fn to_mat4(&self) -> Mat4 {
+        let (w, Vec3 { x, y, z }) = (self.w, self.v);
+        Mat4::new([
+            [
+                1.0 - 2.0 * (y * y + z * z),
+                2.0 * (x * y - w * z),
+                2.0 * (x * z + w * y),
+                0.0,
+            ],
+            [
+                2.0 * (x * y + w * z),
+                1.0 - 2.0 * (x * x + z * z),
+                2.0 * (y * z - w * x),
+                0.0,
+            ],
+            [
+                2.0 * (x * z - w * y),
+                2.0 * (y * z + w * x),
+                1.0 - 2.0 * (x * x + y * y),
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+}
+
+impl Default for Quaternion {
+    fn default() -> Self {
+        Quaternion::IDENTITY
+    }
+}
+
+/// Shortest arc between two rotations, with the angle between them worked
+/// out once for all the points taken along it.
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct Slerp {
+    from: Quaternion,
+    to: Quaternion,
+    /// Angle between the quaternions, or 0 when they are close enough to
+    /// interpolate linearly.
+    theta: f32,
+    sin_theta: f32,
+}
+
+impl Slerp {
+    fn new(from: &Quaternion, to: &Quaternion) -> Self {
+        // `q` and `-q` are the same rotation; pick the nearer one
+        let mut cos_theta = from.dot(to);
+        let to = if cos_theta < 0.0 {
+            cos_theta = -cos_theta;
+            Quaternion { w: -to.w, v: -to.v }
+        } else {
+            *to
+        };
+
+        // Nearly equal: linear interpolation is accurate and avoids
+        // dividing by a vanishing sine
+        let theta = if cos_theta > 0.9995 {
+            0.0
+        } else {
+            cos_theta.acos()
+        };
+        Slerp {
+            from: *from,
+            to,
+            theta,
+            sin_theta: theta.sin(),
+        }
+    }
+
+    fn at(&self, t: f32) -> Quaternion {
+        let (a, b) = if self.theta == 0.0 {
+            (1.0 - t, t)
+        } else {
+            (
+                ((1.0 - t) * self.theta).sin() / self.sin_theta,
+                (t * self.theta).sin() / self.sin_theta,
+            )
+        };
+        Quaternion {
+            w: a * self.from.w + b * self.to.w,
+            v: a * self.from.v + b * self.to.v,
+        }
+        .normalized()
+    }
+}
+
+/// Placement of an object at one moment: scaled, then rotated, then
+/// translated.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Keyframe {
+    pub time: f32,
+    pub scale: Vec3,
+    pub rotation: Quaternion,
+    pub translate: Vec3,
+}
+
+impl Keyframe {
+    pub fn transform(&self) -> Transform {
+        Transform::scale_rotate_translate(self.scale, &self.rotation, self.translate)
+    }
+}
+
+// Largest rotation between the transforms `AnimatedTransform::sweep`
+// returns, in radians
+const SWEEP_STEP: f32 = PI / 180.0;
+
+/// Transform moving through keyframes. Between two keyframes the scale and
+/// translation are interpolated linearly and the rotation along the
+/// shortest arc, so turns of half a revolution or more need keyframes in
+/// between; before the first and after the last the object holds still.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnimatedTransform {
+    keyframes: Vec<Keyframe>,
+    /// Rotation between each keyframe and the next.
+    arcs: Vec<Slerp>,
+}
+
+impl AnimatedTransform {
+    /// `keyframes` must be non-empty and in strictly increasing time, and
+    /// each scale factor must keep its sign throughout so that no
+    /// interpolated transform is singular.
+    pub fn new(keyframes: Vec<Keyframe>) -> Self {
+        assert!(!keyframes.is_empty(), "an animation needs keyframes");
+        assert!(
+            keyframes.windows(2).all(|pair| pair[0].time < pair[1].time),
+            "keyframes must be in increasing time"
+        );
+        let arcs = keyframes
+            .windows(2)
+            .map(|pair| Slerp::new(&pair[0].rotation, &pair[1].rotation))
+            .collect();
+        AnimatedTransform { keyframes, arcs }
+    }
+
+    pub fn keyframes(&self) -> &[Keyframe] {
+        &self.keyframes
+    }
+
+    pub fn at(&self, time: f32) -> Transform {
+        let next = self.keyframes.partition_point(|key| key.time <= time);
+        if next == 0 {
+            return self.keyframes[0].transform();
+        }
+        if next == self.keyframes.len() {
+            return self.keyframes[next - 1].transform();
+        }
+
+        let (a, b) = (&self.keyframes[next - 1], &self.keyframes[next]);
+        let t = (time - a.time) / (b.time - a.time);
+        Transform::scale_rotate_translate(
+            (1.0 - t) * a.scale + t * b.scale,
+            &self.arcs[next - 1].at(t),
+            (1.0 - t) * a.translate + t * b.translate,
+        )
+    }
+
+    /// Transforms along the whole motion, close enough together that each
+    /// turns at most `SWEEP_STEP` from the previous one. A box around a
+    /// shape's corners under all of them, grown by `sweep_padding`, bounds
+    /// the shape over the whole motion.
+    pub fn sweep(&self) -> Vec<Transform> {
+        let mut transforms = vec![self.keyframes[0].transform()];
+        for pair in self.keyframes.windows(2) {
+            let (a, b) = (&pair[0], &pair[1]);
+            let angle = a.rotation.angle_to(&b.rotation);
+            let steps = (angle / SWEEP_STEP).ceil().max(1.0) as u32;
+            for i in 1..=steps {
+                let t = i as f32 / steps as f32;
+                transforms.push(self.at(a.time + t * (b.time - a.time)));
+            }
+        }
+        transforms
+    }
+
+    /// How far a point within `radius` of the object space origin can stray
+    /// from the segment between its positions under consecutive `sweep`
+    /// transforms. Without rotation points move in straight lines and this
+    /// is 0; otherwise the arc bulges by less than `SWEEP_STEP` times the
+    /// largest scaled distance from the origin.
+    pub fn sweep_padding(&self, radius: f32) -> f32 {
+        let first = &self.keyframes[0];
+        if self
+            .keyframes
+            .iter()
+            .all(|key| key.rotation == first.rotation)
+        {
+            return 0.0;
+        }
+        let max_scale = self
+            .keyframes
+            .iter()
+            .map(|key| {
+                key.scale
+                    .x
+                    .abs()
+                    .max(key.scale.y.abs())
+                    .max(key.scale.z.abs())
+            })
+            .fold(0.0, f32::max);
+        radius * max_scale * SWEEP_STEP
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn assert_near(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 1e-5, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn test_inverse() {
+        let m = Mat4::new([
+            [2.0, 0.5, -1.0, 3.0],
+            [0.0, 1.5, 0.25, -2.0],
+            [1.0, -0.5, 3.0, 0.5],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+        let product = m * m.inverse().unwrap();
+        for i in 0..4 {
+            for j in 0..4 {
+                let expected = if i == j { 1.0 } else { 0.0 };
+                assert!((product.m[i][j] - expected).abs() < 1e-5, "{:?}", product);
+            }
+        }
+        assert!(Mat4::scaling(Vec3::new(1.0, 0.0, 1.0)).inverse().is_none());
+    }
+
+    #[test]
+    fn test_rotation_and_composition() {
+        let rotate = Transform::rotate(Vec3::new(0.0, 0.0, 2.0), 90.0);
+        assert_near(
+            rotate.vector(Vec3::new(1.0, 0.0, 0.0)),
+            Vec3::new(0.0, 1.0, 0.0),
+        );
+
+        // Scale, then rotate, then translate
+        let transform = Transform::scale(Vec3::new(2.0, 2.0, 2.0))
+            .then(&rotate)
+            .then(&Transform::translate(Vec3::new(0.0, 0.0, 5.0)));
+        let p = Point3::new(1.0, 0.0, 0.0);
+        assert_near(transform.point(p), Point3::new(0.0, 2.0, 5.0));
+        assert_near(transform.inverse_point(transform.point(p)), p);
+        assert_near(
+            transform.inverse().point(Point3::new(0.0, 2.0, 5.0)),
+            Point3::new(1.0, 0.0, 0.0),
+        );
+        // Translations do not move directions
+        assert_near(
+            transform.vector(Vec3::new(0.0, 0.0, 1.0)),
+            Vec3::new(0.0, 0.0, 2.0),
+        );
+    }
+
+    #[test]
+    fn test_normals_stay_perpendicular() {
+        // Squashing a 45 degree slope tilts its normal further up
+        let transform = Transform::scale(Vec3::new(1.0, 0.5, 1.0));
+        let tangent = Vec3::new(1.0, 1.0, 0.0);
+        let normal = Vec3::new(-1.0, 1.0, 0.0);
+        let new_tangent = transform.vector(tangent);
+        let new_normal = transform.normal(normal);
+        assert!(Vec3::dot_product(new_tangent, new_normal).abs() < 1e-6);
+        assert_near(
+            Vec3::unit(new_normal),
+            Vec3::unit(Vec3::new(-1.0, 2.0, 0.0)),
+        );
+    }
+
+    #[test]
+    fn test_quaternions_match_matrices() {
+        let axis = Vec3::new(1.0, 2.0, -0.5);
+        let q = Quaternion::from_axis_angle(axis, 70.0);
+        let p = Point3::new(0.3, -1.0, 2.0);
+        assert_near(
+            Transform::from_quaternion(q).point(p),
+            Transform::rotate(axis, 70.0).point(p),
+        );
+
+        let x90 = Quaternion::from_axis_angle(Vec3::new(1.0, 0.0, 0.0), 90.0);
+        let y90 = Quaternion::from_axis_angle(Vec3::new(0.0, 1.0, 0.0), 90.0);
+        let composed = Transform::rotate(Vec3::new(1.0, 0.0, 0.0), 90.0)
+            .then(&Transform::rotate(Vec3::new(0.0, 1.0, 0.0), 90.0));
+        assert_near(
+            Transform::from_quaternion(x90.then(&y90)).point(p),
+            composed.point(p),
+        );
+        assert!((x90.angle_to(&Quaternion::IDENTITY) - PI / 2.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_keyframe_matches_composed_transforms() {
+        let keyframe = Keyframe {
+            time: 0.0,
+            scale: Vec3::new(2.0, -0.5, 3.0),
+            rotation: Quaternion::from_axis_angle(Vec3::new(1.0, 2.0, -1.0), 75.0),
+            translate: Vec3::new(-4.0, 1.0, 2.5),
+        };
+        let composed = Transform::scale(keyframe.scale)
+            .then(&Transform::from_quaternion(keyframe.rotation))
+            .then(&Transform::translate(keyframe.translate));
+        let direct = keyframe.transform();
+        for (matrix, expected) in [
+            (direct.matrix(), composed.matrix()),
+            (direct.inverse().matrix(), composed.inverse().matrix()),
+        ] {
+            for (row, expected_row) in matrix.m.iter().zip(&expected.m) {
+                for (value, expected_value) in row.iter().zip(expected_row) {
+                    assert!((value - expected_value).abs() < 1e-5);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_keyframe_interpolation() {
+        let keyframe = |time, degrees, x| Keyframe {
+            time,
+            scale: Vec3::new(1.0, 1.0, 1.0),
+            rotation: Quaternion::from_axis_angle(Vec3::new(0.0, 0.0, 1.0), degrees),
+            translate: Vec3::new(x, 0.0, 0.0),
+        };
+        let motion =
+            AnimatedTransform::new(vec![keyframe(0.0, 0.0, 0.0), keyframe(1.0, 90.0, 4.0)]);
+        let p = Point3::new(1.0, 0.0, 0.0);
+
+        // Halfway the point has turned 45 degrees, staying at unit distance
+        // from the moving center
+        let half = 0.5f32.sqrt();
+        assert_near(motion.at(0.5).point(p), Point3::new(2.0 + half, half, 0.0));
+        assert_near(
+            motion
+                .at(0.5)
+                .inverse_point(Point3::new(2.0 + half, half, 0.0)),
+            p,
+        );
+        // Held still outside the keyframes
+        assert_near(motion.at(-1.0).point(p), p);
+        assert_near(motion.at(2.0).point(p), Point3::new(4.0, 1.0, 0.0));
+
+        let sweep = motion.sweep();
+        assert_eq!(sweep.len(), 91);
+        assert_eq!(motion.sweep_padding(1.0), SWEEP_STEP);
+        let still =
+            AnimatedTransform::new(vec![keyframe(0.0, 30.0, 0.0), keyframe(1.0, 30.0, 4.0)]);
+        assert_eq!(still.sweep().len(), 2);
+        assert_eq!(still.sweep_padding(1.0), 0.0);
+    }
+}
@@ -1,5 +1,8 @@
 use crate::utils::helpers::random_f32;
-use crate::utils::{constants::EPSILON, helpers::random_f32_with_range};
+use crate::utils::{
+    constants::{EPSILON, PI},
+    helpers::random_f32_with_range,
+};
 use std::{
     fmt,
     ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign},
@@ -92,6 +95,48 @@ impl Vec3 {
         }
     }
 
+    /// Direction around +Z distributed proportionally to the cosine of its
+    /// angle with the axis (pdf `cos(theta) / PI`).
+    #[inline]
+    pub fn random_cosine_direction() -> Vec3 {
+        Vec3::sample_cosine_direction(random_f32(), random_f32())
+    }
+
+    /// Maps a point of the unit square to a cosine-distributed direction
+    /// around +Z.
+    #[inline]
+    pub fn sample_cosine_direction(u1: f32, u2: f32) -> Vec3 {
+        let phi = 2.0 * PI * u1;
+        let sqrt_u2 = u2.sqrt();
+        Vec3::new(phi.cos() * sqrt_u2, phi.sin() * sqrt_u2, (1.0 - u2).sqrt())
+    }
+
+    /// Maps a point of the unit square to a uniformly distributed unit
+    /// vector.
+    #[inline]
+    pub fn sample_unit_vector(u1: f32, u2: f32) -> Vec3 {
+        let z = 1.0 - 2.0 * u1;
+        let r = (1.0 - z * z).max(0.0).sqrt();
+        let phi = 2.0 * PI * u2;
+        Vec3::new(r * phi.cos(), r * phi.sin(), z)
+    }
+
+    /// Maps a point of the unit square to the unit disk in the XY plane with
+    /// Shirley and Chiu's concentric mapping, which keeps strata compact.
+    #[inline]
+    pub fn sample_unit_disk(u1: f32, u2: f32) -> Vec3 {
+        let (a, b) = (2.0 * u1 - 1.0, 2.0 * u2 - 1.0);
+        if a == 0.0 && b == 0.0 {
+            return Vec3::new(0.0, 0.0, 0.0);
+        }
+        let (r, theta) = if a.abs() > b.abs() {
+            (a, PI / 4.0 * (b / a))
+        } else {
+            (b, PI / 2.0 - PI / 4.0 * (a / b))
+        };
+        Vec3::new(r * theta.cos(), r * theta.sin(), 0.0)
+    }
+
     #[inline(always)]
     pub fn reflect(v: Vec3, n: Vec3) -> Vec3 {
         v - (2.0 * Vec3::dot_product(v, n)) * n
@@ -107,22 +152,13 @@ impl Vec3 {
         let cos_theta: f32 = f32::min(Vec3::dot_product(-uv, n), 1.0);
         let perp_component =
             (source_medium_ref_index / dest_medium_ref_index) * (uv + cos_theta * n);
-        let parallel_component = -1.0 * ((1.0 - perp_component.length_squared()).abs()).sqrt() * n;
+        let parallel_component = -((1.0 - perp_component.length_squared()).abs()).sqrt() * n;
         perp_component + parallel_component
     }
 
     #[inline]
     pub fn random_in_unit_disk() -> Vec3 {
-        loop {
-            let p = Vec3::new(
-                random_f32_with_range(-1.0, 1.0),
-                random_f32_with_range(-1.0, 1.0),
-                0.0,
-            );
-            if p.length_squared() < 1.0 {
-                return p;
-            }
-        }
+        Vec3::sample_unit_disk(random_f32(), random_f32())
     }
 
     #[inline(always)]
@@ -285,6 +321,7 @@ impl Neg for Vec3 {
     }
 }
 
+#[cfg(test)]
 mod test {
     use super::*;
 
@@ -366,4 +403,20 @@ mod test {
         let first_vec = Vec3::new(0.1, 0.2, 0.0);
         assert_eq!(first_vec.length_squared(), 0.050000004); // meh
     }
+
+    #[test]
+    fn test_sample_mappings() {
+        for i in 0..=16 {
+            for j in 0..=16 {
+                let (u1, u2) = (i as f32 / 16.0, j as f32 / 16.0);
+                assert!(Vec3::sample_unit_disk(u1, u2).length() <= 1.0 + 1e-6);
+                assert!((Vec3::sample_unit_vector(u1, u2).length() - 1.0).abs() < 1e-5);
+                let cosine = Vec3::sample_cosine_direction(u1, u2);
+                assert!((cosine.length() - 1.0).abs() < 1e-5);
+                assert!(cosine.z >= 0.0);
+            }
+        }
+        // Corners of the square land on the rim of the disk
+        assert!((Vec3::sample_unit_disk(1.0, 1.0).length() - 1.0).abs() < 1e-6);
+    }
 }
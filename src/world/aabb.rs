@@ -0,0 +1,205 @@
+use crate::{
+    ray::ray::Ray,
+    utils::{constants::EMPTY, interval::Interval},
+    vec3::vec3::Point3,
+};
+
+#[derive(Copy, Clone, Debug)]
+pub struct Aabb {
+    pub x: Interval,
+    pub y: Interval,
+    pub z: Interval,
+}
+
+impl Aabb {
+    pub const EMPTY: Aabb = Aabb {
+        x: EMPTY,
+        y: EMPTY,
+        z: EMPTY,
+    };
+
+    pub fn new(x: Interval, y: Interval, z: Interval) -> Self {
+        Aabb { x, y, z }
+    }
+
+    /// Box spanning the two corner points, in any order.
+    pub fn from_points(a: Point3, b: Point3) -> Self {
+        Aabb {
+            x: Interval::new(a.x.min(b.x), a.x.max(b.x)),
+            y: Interval::new(a.y.min(b.y), a.y.max(b.y)),
+            z: Interval::new(a.z.min(b.z), a.z.max(b.z)),
+        }
+    }
+
+    pub fn surrounding(a: &Aabb, b: &Aabb) -> Self {
+        Aabb {
+            x: Interval::enclosing(&a.x, &b.x),
+            y: Interval::enclosing(&a.y, &b.y),
+            z: Interval::enclosing(&a.z, &b.z),
+        }
+    }
+
+    /// Grows the box so that it also contains `p`.
+    pub fn expand(&mut self, p: Point3) {
+        self.x = Interval::new(self.x.min.min(p.x), self.x.max.max(p.x));
+        self.y = Interval::new(self.y.min.min(p.y), self.y.max.max(p.y));
+        self.z = Interval::new(self.z.min.min(p.z), self.z.max.max(p.z));
+    }
+
+    /// Gives every axis a minimum thickness, so that flat boxes (e.g. around
+    /// an axis-aligned quad) still register slab hits.
+    pub fn pad(&self, min_size: f32) -> Self {
+        let pad_axis = |i: Interval| {
+            if i.size() < min_size {
+                i.expand(min_size)
+            } else {
+                i
+            }
+        };
+        Aabb {
+            x: pad_axis(self.x),
+            y: pad_axis(self.y),
+            z: pad_axis(self.z),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.x.size() < 0.0 || self.y.size() < 0.0 || self.z.size() < 0.0
+    }
+
+    pub fn contains(&self, p: Point3) -> bool {
+        self.x.contains(p.x) && self.y.contains(p.y) && self.z.contains(p.z)
+    }
+
+    #[inline]
+    pub fn axis(&self, n: usize) -> Interval {
+        match n {
+            0 => self.x,
+            1 => self.y,
+            _ => self.z,
+        }
+    }
+
+    pub fn longest_axis(&self) -> usize {
+        let (dx, dy, dz) = (self.x.size(), self.y.size(), self.z.size());
+        if dx > dy && dx > dz {
+            0
+        } else if dy > dz {
+            1
+        } else {
+            2
+        }
+    }
+
+    pub fn centroid(&self) -> Point3 {
+        Point3::new(
+            0.5 * (self.x.min + self.x.max),
+            0.5 * (self.y.min + self.y.max),
+            0.5 * (self.z.min + self.z.max),
+        )
+    }
+
+    pub fn surface_area(&self) -> f32 {
+        if self.is_empty() {
+            return 0.0;
+        }
+        let (dx, dy, dz) = (self.x.size(), self.y.size(), self.z.size());
+        2.0 * (dx * dy + dy * dz + dz * dx)
+    }
+
+    /// Slab test: intersects the ray with each pair of axis planes and keeps
+    /// the overlap of the three parametric ranges.
+    #[inline]
+    pub fn hit(&self, ray: &Ray, mut ray_t: Interval) -> bool {
+        let origin = ray.origin();
+        let direction = ray.direction();
+        let components = [
+            (self.x, origin.x, direction.x),
+            (self.y, origin.y, direction.y),
+            (self.z, origin.z, direction.z),
+        ];
+
+        for (slab, orig, dir) in components {
+            let inv_dir = 1.0 / dir;
+            let t0 = (slab.min - orig) * inv_dir;
+            let t1 = (slab.max - orig) * inv_dir;
+
+            let (t_near, t_far) = if t0 < t1 { (t0, t1) } else { (t1, t0) };
+            if t_near > ray_t.min {
+                ray_t.min = t_near;
+            }
+            if t_far < ray_t.max {
+                ray_t.max = t_far;
+            }
+            if ray_t.max < ray_t.min {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+impl Default for Aabb {
+    fn default() -> Self {
+        Aabb::EMPTY
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{utils::constants::INFINITY, vec3::vec3::Vec3};
+
+    fn unit_box() -> Aabb {
+        Aabb::from_points(Point3::new(-1.0, -1.0, -1.0), Point3::new(1.0, 1.0, 1.0))
+    }
+
+    #[test]
+    fn test_hit_through_center() {
+        let ray = Ray::new(Point3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        assert!(unit_box().hit(&ray, Interval::new(0.001, INFINITY)));
+    }
+
+    #[test]
+    fn test_miss_beside_box() {
+        let ray = Ray::new(Point3::new(2.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        assert!(!unit_box().hit(&ray, Interval::new(0.001, INFINITY)));
+    }
+
+    #[test]
+    fn test_miss_outside_interval() {
+        let ray = Ray::new(Point3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        assert!(!unit_box().hit(&ray, Interval::new(0.001, 3.0)));
+        assert!(!unit_box().hit(&ray, Interval::new(7.0, INFINITY)));
+    }
+
+    #[test]
+    fn test_hit_from_inside() {
+        let ray = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 1.0, 0.0));
+        assert!(unit_box().hit(&ray, Interval::new(0.001, INFINITY)));
+    }
+
+    #[test]
+    fn test_surrounding_and_expand() {
+        let other = Aabb::from_points(Point3::new(2.0, 0.0, 0.0), Point3::new(3.0, 0.5, 0.5));
+        let union = Aabb::surrounding(&unit_box(), &other);
+        assert_eq!(union.x.min, -1.0);
+        assert_eq!(union.x.max, 3.0);
+
+        let mut b = Aabb::EMPTY;
+        assert!(b.is_empty());
+        b.expand(Point3::new(1.0, 2.0, 3.0));
+        b.expand(Point3::new(-1.0, 0.0, 0.0));
+        assert!(!b.is_empty());
+        assert!(b.contains(Point3::new(0.0, 1.0, 1.5)));
+        assert_eq!(b.longest_axis(), 2);
+    }
+
+    #[test]
+    fn test_pad_flat_box() {
+        let flat = Aabb::from_points(Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 1.0, 0.0));
+        let padded = flat.pad(0.0001);
+        assert!(padded.z.size() > 0.0);
+        assert_eq!(padded.x.size(), 1.0);
+    }
+}
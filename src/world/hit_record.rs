@@ -1,4 +1,4 @@
-use std::{rc::Rc, sync::Arc};
+use std::sync::Arc;
 
 use crate::{
     ray::ray::Ray,
@@ -11,6 +11,10 @@ pub struct HitRecord {
     pub p: Point3,
     pub normal: Vec3,
     pub t: f32,
+    /// Surface coordinates of the hit; barycentric weights of the second
+    /// and third vertex for triangles.
+    pub u: f32,
+    pub v: f32,
     pub front_face: bool,
     pub material: Arc<Material>,
 }
@@ -24,4 +28,17 @@ impl HitRecord {
             -outward_normal
         };
     }
+
+    /// Makes this the point at `t` along `ray` where it scatters in a
+    /// medium. The normal is the ray's unit direction, which phase
+    /// functions measure scattering angles from.
+    pub fn set_medium_point(&mut self, ray: &Ray, t: f32, phase: &Arc<Material>) {
+        self.t = t;
+        self.p = ray.at(t);
+        self.normal = Vec3::unit(ray.direction());
+        self.front_face = true;
+        self.u = 0.0;
+        self.v = 0.0;
+        self.material = Arc::clone(phase);
+    }
 }
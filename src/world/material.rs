@@ -1,45 +1,207 @@
 use crate::ray::ray::Ray;
-use crate::utils::helpers::random_f32;
+use crate::sampler::sampler::Sampler;
+use crate::utils::constants::PI;
 use crate::vec3::color3::Color3;
+use crate::vec3::onb::Onb;
 use crate::vec3::vec3::*;
 use crate::world::hit_record::*;
+use crate::world::texture::Texture;
 
 #[derive(Debug, Clone)]
 pub enum Material {
-    Lambertian { albedo: Color3 },
-    Metal { albedo: Color3, fuzz: f32 },
-    Dielectric { refraction_index: f32 },
+    Lambertian {
+        albedo: Texture,
+    },
+    Metal {
+        albedo: Texture,
+        fuzz: f32,
+    },
+    Dielectric {
+        refraction_index: f32,
+    },
+    DiffuseLight {
+        emit: Color3,
+    },
+    /// Phase function scattering equally in every direction, for the
+    /// particles of a participating medium rather than a surface.
+    Isotropic {
+        albedo: Texture,
+    },
+    /// Phase function favouring forward (`g > 0`) or backward (`g < 0`)
+    /// scattering, with `g` the mean cosine of the scattering angle.
+    HenyeyGreenstein {
+        albedo: Texture,
+        g: f32,
+    },
+}
+
+/// Henyey-Greenstein density of turning by an angle with cosine
+/// `cos_theta`, per steradian.
+#[inline]
+fn henyey_greenstein(g: f32, cos_theta: f32) -> f32 {
+    let denominator = 1.0 + g * g - 2.0 * g * cos_theta;
+    (1.0 - g * g) / (4.0 * PI * denominator * denominator.max(0.0).sqrt())
+}
+
+/// Outcome of sampling a material's BSDF at a hit point.
+#[derive(Debug, Clone, Default)]
+pub struct ScatterRecord {
+    /// BSDF times cosine divided by `pdf`, i.e. the path throughput weight.
+    pub attenuation: Color3,
+    pub scattered: Ray,
+    /// Solid angle density `scattered` was drawn with. Meaningless when
+    /// `is_specular` is set.
+    pub pdf: f32,
+    /// Delta distributions (mirrors, glass) cannot be evaluated for an
+    /// arbitrary direction, so they never take part in light sampling.
+    pub is_specular: bool,
 }
 
 impl Material {
+    /// Samples a scattered direction, drawing its random numbers from
+    /// `sampler`.
     #[inline]
     pub fn scatter(
         &self,
         ray: &Ray,
         hit_rec: &HitRecord,
-        attenuation: &mut Color3,
-        scattered: &mut Ray,
+        srec: &mut ScatterRecord,
+        sampler: &mut Sampler,
     ) -> bool {
-        match *self {
+        match self {
             Self::Lambertian { albedo } => {
-                *attenuation = albedo;
-                self.scatter_lambertian(ray, hit_rec, scattered)
+                srec.attenuation = albedo.value(hit_rec.u, hit_rec.v, hit_rec.p);
+                srec.is_specular = false;
+                self.scatter_lambertian(ray, hit_rec, srec, sampler)
             }
             Self::Metal { albedo, fuzz } => {
-                *attenuation = albedo;
-                self.scatter_metal(ray, hit_rec, scattered, fuzz)
+                srec.attenuation = albedo.value(hit_rec.u, hit_rec.v, hit_rec.p);
+                srec.is_specular = true;
+                srec.pdf = 0.0;
+                self.scatter_metal(ray, hit_rec, &mut srec.scattered, *fuzz, sampler)
             }
             Self::Dielectric { refraction_index } => {
-                *attenuation = Color3::new(1.0, 1.0, 1.0);
-                self.scatter_dielectric(ray, hit_rec, scattered, refraction_index)
+                srec.attenuation = Color3::new(1.0, 1.0, 1.0);
+                srec.is_specular = true;
+                srec.pdf = 0.0;
+                self.scatter_dielectric(
+                    ray,
+                    hit_rec,
+                    &mut srec.scattered,
+                    *refraction_index,
+                    sampler,
+                )
+            }
+            Self::DiffuseLight { .. } => false,
+            Self::Isotropic { albedo } => {
+                srec.attenuation = albedo.value(hit_rec.u, hit_rec.v, hit_rec.p);
+                srec.is_specular = false;
+                self.scatter_phase(ray, hit_rec, srec, 0.0, sampler)
+            }
+            Self::HenyeyGreenstein { albedo, g } => {
+                srec.attenuation = albedo.value(hit_rec.u, hit_rec.v, hit_rec.p);
+                srec.is_specular = false;
+                self.scatter_phase(ray, hit_rec, srec, *g, sampler)
             }
         }
     }
 
+    /// BSDF times the cosine term for light arriving from `direction`, or
+    /// the phase function for media. Always black for specular materials.
     #[inline]
-    fn scatter_lambertian(&self, ray: &Ray, hit_rec: &HitRecord, scattered: &mut Ray) -> bool {
-        let dir = hit_rec.normal + Vec3::random_unit_vector_on_hemisphere(hit_rec.normal);
-        *scattered = Ray::new(hit_rec.p, dir);
+    pub fn eval(&self, hit_rec: &HitRecord, direction: Vec3) -> Color3 {
+        match self {
+            Self::Lambertian { albedo } => {
+                let cosine = Vec3::dot_product(hit_rec.normal, Vec3::unit(direction));
+                albedo.value(hit_rec.u, hit_rec.v, hit_rec.p) * (cosine.max(0.0) / PI)
+            }
+            Self::Isotropic { albedo } | Self::HenyeyGreenstein { albedo, .. } => {
+                albedo.value(hit_rec.u, hit_rec.v, hit_rec.p) * self.pdf(hit_rec, direction)
+            }
+            _ => Color3::new(0.0, 0.0, 0.0),
+        }
+    }
+
+    /// Solid angle density with which `scatter` picks `direction`.
+    #[inline]
+    pub fn pdf(&self, hit_rec: &HitRecord, direction: Vec3) -> f32 {
+        match *self {
+            Self::Lambertian { .. } => {
+                let cosine = Vec3::dot_product(hit_rec.normal, Vec3::unit(direction));
+                cosine.max(0.0) / PI
+            }
+            Self::Isotropic { .. } => 1.0 / (4.0 * PI),
+            Self::HenyeyGreenstein { g, .. } => {
+                let cosine = Vec3::dot_product(hit_rec.normal, Vec3::unit(direction));
+                henyey_greenstein(g, cosine)
+            }
+            _ => 0.0,
+        }
+    }
+
+    /// Radiance emitted at the hit point, black for non-emissive materials.
+    /// Lights emit from both sides of their surface.
+    #[inline]
+    pub fn emitted(&self, _hit_rec: &HitRecord) -> Color3 {
+        match self {
+            Self::DiffuseLight { emit } => *emit,
+            _ => Color3::new(0.0, 0.0, 0.0),
+        }
+    }
+
+    pub fn is_emissive(&self) -> bool {
+        matches!(self, Self::DiffuseLight { .. })
+    }
+
+    /// Whether this is a phase function, scattering inside a medium instead
+    /// of at a surface.
+    pub fn is_phase_function(&self) -> bool {
+        matches!(self, Self::Isotropic { .. } | Self::HenyeyGreenstein { .. })
+    }
+
+    #[inline]
+    fn scatter_lambertian(
+        &self,
+        ray: &Ray,
+        hit_rec: &HitRecord,
+        srec: &mut ScatterRecord,
+        sampler: &mut Sampler,
+    ) -> bool {
+        let (u1, u2) = sampler.get_2d();
+        let dir = Onb::new(hit_rec.normal).transform(Vec3::sample_cosine_direction(u1, u2));
+        srec.pdf = self.pdf(hit_rec, dir);
+        srec.scattered = Ray::new(hit_rec.p, dir).with_time(ray.time());
+        srec.pdf > 0.0
+    }
+
+    /// Samples the Henyey-Greenstein phase function, which is isotropic for
+    /// `g = 0`. Scattering points in media carry the direction the ray
+    /// travelled as their normal, and angles are measured from it.
+    #[inline]
+    fn scatter_phase(
+        &self,
+        ray: &Ray,
+        hit_rec: &HitRecord,
+        srec: &mut ScatterRecord,
+        g: f32,
+        sampler: &mut Sampler,
+    ) -> bool {
+        let (u1, u2) = sampler.get_2d();
+        let cos_theta = if g.abs() < 1e-3 {
+            1.0 - 2.0 * u1
+        } else {
+            let s = (1.0 - g * g) / (1.0 + g - 2.0 * g * u1);
+            ((1.0 + g * g - s * s) / (2.0 * g)).clamp(-1.0, 1.0)
+        };
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * u2;
+        let dir = Onb::new(hit_rec.normal).transform(Vec3::new(
+            sin_theta * phi.cos(),
+            sin_theta * phi.sin(),
+            cos_theta,
+        ));
+        srec.pdf = self.pdf(hit_rec, dir);
+        srec.scattered = Ray::new(hit_rec.p, dir).with_time(ray.time());
         true
     }
 
@@ -50,10 +212,16 @@ impl Material {
         hit_rec: &HitRecord,
         scattered: &mut Ray,
         fuzz: f32,
+        sampler: &mut Sampler,
     ) -> bool {
         let mut dir = Vec3::reflect(ray.direction(), hit_rec.normal);
-        dir = dir + (fuzz * Vec3::random_unit_vector_on_hemisphere(hit_rec.normal));
-        *scattered = Ray::new(hit_rec.p, dir);
+        let (u1, u2) = sampler.get_2d();
+        let mut perturbation = Vec3::sample_unit_vector(u1, u2);
+        if Vec3::dot_product(perturbation, hit_rec.normal) < 0.0 {
+            perturbation = -perturbation;
+        }
+        dir += fuzz * perturbation;
+        *scattered = Ray::new(hit_rec.p, dir).with_time(ray.time());
         true
     }
 
@@ -64,6 +232,7 @@ impl Material {
         hit_rec: &HitRecord,
         scattered: &mut Ray,
         refraction_index: f32,
+        sampler: &mut Sampler,
     ) -> bool {
         let source_medium_ref_index = if hit_rec.front_face {
             1.0
@@ -86,18 +255,20 @@ impl Material {
             r0 + (1.0 - r0) * (1.0 - cos_theta).powf(5.0)
         };
 
-        let mut direction = Vec3::default();
-        if cannot_refract || reflectance() > random_f32() {
-            direction = Vec3::reflect(unit_vector, hit_rec.normal);
+        // Drawn even under total internal reflection, so every hit on glass
+        // uses up the same sampler dimensions
+        let u = sampler.get_1d();
+        let direction = if cannot_refract || reflectance() > u {
+            Vec3::reflect(unit_vector, hit_rec.normal)
         } else {
-            direction = Vec3::refract(
+            Vec3::refract(
                 unit_vector,
                 hit_rec.normal,
                 source_medium_ref_index,
                 dest_medium_ref_index,
             )
-        }
-        *scattered = Ray::new(hit_rec.p, direction);
+        };
+        *scattered = Ray::new(hit_rec.p, direction).with_time(ray.time());
         true
     }
 }
@@ -105,7 +276,47 @@ impl Material {
 impl Default for Material {
     fn default() -> Self {
         Material::Lambertian {
-            albedo: Color3::new(0.0, 0.0, 0.0),
+            albedo: Texture::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sampler::sampler::SamplerKind;
+
+    #[test]
+    fn test_henyey_greenstein_sampling() {
+        let forward = Vec3::new(0.0, 0.0, 1.0);
+        let hit_rec = HitRecord {
+            normal: forward,
+            ..Default::default()
+        };
+        let mut sampler = Sampler::new(SamplerKind::Sobol, 4096);
+        for g in [0.0, 0.6, -0.3] {
+            let material = Material::HenyeyGreenstein {
+                albedo: Color3::new(0.5, 0.5, 0.5).into(),
+                g,
+            };
+            // The mean cosine of the scattering angle is `g`, and the
+            // reported density matches the phase function's
+            let mut srec = ScatterRecord::default();
+            let mut mean_cosine = 0.0;
+            for i in 0..4096 {
+                sampler.start_sample(1, i);
+                assert!(material.scatter(&Ray::default(), &hit_rec, &mut srec, &mut sampler));
+                let direction = srec.scattered.direction();
+                mean_cosine += Vec3::dot_product(forward, Vec3::unit(direction)) / 4096.0;
+                assert!((srec.pdf - material.pdf(&hit_rec, direction)).abs() < 1e-3 * srec.pdf);
+                assert_eq!(srec.attenuation, Color3::new(0.5, 0.5, 0.5));
+            }
+            assert!((mean_cosine - g).abs() < 0.01, "{} vs {}", mean_cosine, g);
         }
+
+        let isotropic = Material::Isotropic {
+            albedo: Color3::new(1.0, 1.0, 1.0).into(),
+        };
+        assert!((isotropic.pdf(&hit_rec, -forward) - 1.0 / (4.0 * PI)).abs() < 1e-7);
     }
 }
@@ -0,0 +1,156 @@
+use std::ops::Range;
+
+use crate::{
+    ray::ray::Ray,
+    utils::interval::Interval,
+    vec3::vec3::{Point3, Vec3},
+    world::{aabb::Aabb, bvh::Bvh, hit_record::HitRecord, scene_object::SceneObject},
+};
+
+// Interpolated vertex normals shorter than this are treated as zero
+const MIN_NORMAL_LENGTH_SQUARED: f32 = 1e-12;
+
+/// Corner of a mesh triangle, indexing into the mesh's attribute arrays.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct MeshVertex {
+    pub position: usize,
+    pub normal: Option<usize>,
+    pub texcoord: Option<usize>,
+}
+
+/// Indexed triangle mesh with its own BVH over the triangles.
+#[derive(Debug, Clone, Default)]
+pub struct TriangleMesh {
+    pub positions: Vec<Point3>,
+    pub normals: Vec<Vec3>,
+    pub texcoords: Vec<(f32, f32)>,
+    pub triangles: Vec<[MeshVertex; 3]>,
+    /// Named ranges of `triangles`, e.g. the `g` groups of an OBJ file.
+    pub groups: Vec<(String, Range<usize>)>,
+    bvh: Bvh,
+}
+
+impl TriangleMesh {
+    pub fn new(
+        positions: Vec<Point3>,
+        normals: Vec<Vec3>,
+        texcoords: Vec<(f32, f32)>,
+        triangles: Vec<[MeshVertex; 3]>,
+        groups: Vec<(String, Range<usize>)>,
+    ) -> Self {
+        let mut mesh = TriangleMesh {
+            positions,
+            normals,
+            texcoords,
+            triangles,
+            groups,
+            bvh: Bvh::default(),
+        };
+        mesh.build_bvh();
+        mesh
+    }
+
+    fn build_bvh(&mut self) {
+        let bounds: Vec<Aabb> = (0..self.triangles.len())
+            .map(|i| {
+                let [p0, p1, p2] = self.triangle_positions(i);
+                let mut bbox = Aabb::from_points(p0, p1);
+                bbox.expand(p2);
+                bbox.pad(1e-4)
+            })
+            .collect();
+        self.bvh = Bvh::new(&bounds);
+    }
+
+    /// Replaces all vertex normals with area-weighted averages of the
+    /// adjacent face normals, giving smooth shading to meshes that were
+    /// exported without normals. Vertices whose faces cancel out, e.g. on
+    /// double-sided geometry, are left with a zero normal, and hits near
+    /// them are shaded with the face normal instead.
+    pub fn compute_vertex_normals(&mut self) {
+        let mut normals = vec![Vec3::default(); self.positions.len()];
+        for triangle in &self.triangles {
+            let [p0, p1, p2] = triangle.map(|v| self.positions[v.position]);
+            // Unnormalized, so larger faces weigh more
+            let face_normal = Vec3::cross_product(p1 - p0, p2 - p0);
+            for vertex in triangle {
+                normals[vertex.position] += face_normal;
+            }
+        }
+
+        for normal in normals.iter_mut() {
+            if normal.length_squared() > 0.0 {
+                *normal = Vec3::unit(*normal);
+            }
+        }
+        for triangle in self.triangles.iter_mut() {
+            for vertex in triangle.iter_mut() {
+                vertex.normal = Some(vertex.position);
+            }
+        }
+        self.normals = normals;
+    }
+
+    /// Drops the vertex normals so every triangle is shaded flat.
+    pub fn clear_vertex_normals(&mut self) {
+        self.normals.clear();
+        for triangle in self.triangles.iter_mut() {
+            for vertex in triangle.iter_mut() {
+                vertex.normal = None;
+            }
+        }
+    }
+
+    pub fn bounding_box(&self) -> Aabb {
+        self.bvh.bounding_box()
+    }
+
+    #[inline]
+    fn triangle_positions(&self, i: usize) -> [Point3; 3] {
+        self.triangles[i].map(|v| self.positions[v.position])
+    }
+
+    #[inline]
+    pub fn hit(&self, ray: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+        self.bvh.hit(ray, ray_t, rec, |i, r, t, rec| {
+            let [p0, p1, p2] = self.triangle_positions(i);
+            if !SceneObject::hit_triangle(p0, p1, p2, r, t, rec) {
+                return false;
+            }
+            self.shade(i, rec);
+            true
+        })
+    }
+
+    /// Replaces the geometric normal and barycentric (u, v) of a triangle
+    /// hit with interpolated vertex attributes where the mesh has them.
+    #[inline]
+    fn shade(&self, i: usize, rec: &mut HitRecord) {
+        let [a, b, c] = self.triangles[i];
+        let (b1, b2) = (rec.u, rec.v);
+        let b0 = 1.0 - b1 - b2;
+
+        if let (Some(na), Some(nb), Some(nc)) = (a.normal, b.normal, c.normal) {
+            let interpolated =
+                b0 * self.normals[na] + b1 * self.normals[nb] + b2 * self.normals[nc];
+            // Zero or opposing vertex normals can cancel out, leaving no
+            // direction to shade with but the geometric normal
+            if interpolated.length_squared() > MIN_NORMAL_LENGTH_SQUARED {
+                let shading_normal = Vec3::unit(interpolated);
+                // Keep it on the side of the geometric normal, which
+                // already faces the incoming ray
+                rec.normal = if Vec3::dot_product(shading_normal, rec.normal) < 0.0 {
+                    -shading_normal
+                } else {
+                    shading_normal
+                };
+            }
+        }
+
+        if let (Some(ta), Some(tb), Some(tc)) = (a.texcoord, b.texcoord, c.texcoord) {
+            let (ta, tb, tc) = (self.texcoords[ta], self.texcoords[tb], self.texcoords[tc]);
+            rec.u = b0 * ta.0 + b1 * tb.0 + b2 * tc.0;
+            rec.v = b0 * ta.1 + b1 * tb.1 + b2 * tc.1;
+        }
+    }
+}
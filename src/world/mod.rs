@@ -1,4 +1,9 @@
+pub mod aabb;
+pub mod bvh;
 pub mod hit_record;
 pub mod material;
+pub mod mesh;
+pub mod perlin;
 pub mod scene_object;
 pub mod scene_object_list;
+pub mod texture;
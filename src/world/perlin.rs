@@ -0,0 +1,159 @@
+use crate::vec3::vec3::{Point3, Vec3};
+
+const POINT_COUNT: usize = 256;
+
+/// Seedable Perlin gradient noise over 3D space.
+#[derive(Debug, Clone)]
+pub struct Perlin {
+    gradients: Vec<Vec3>,
+    perm_x: Vec<usize>,
+    perm_y: Vec<usize>,
+    perm_z: Vec<usize>,
+}
+
+impl Perlin {
+    pub fn new(seed: u64) -> Self {
+        let mut rng = fastrand::Rng::with_seed(seed);
+        let gradients = (0..POINT_COUNT)
+            .map(|_| loop {
+                // Rejection sampling keeps the directions uniform
+                let v = Vec3::new(
+                    rng.f32() * 2.0 - 1.0,
+                    rng.f32() * 2.0 - 1.0,
+                    rng.f32() * 2.0 - 1.0,
+                );
+                let len_sq = v.length_squared();
+                if len_sq > 1e-6 && len_sq <= 1.0 {
+                    break v / len_sq.sqrt();
+                }
+            })
+            .collect();
+
+        let mut permutation = || {
+            let mut perm: Vec<usize> = (0..POINT_COUNT).collect();
+            rng.shuffle(&mut perm);
+            perm
+        };
+        Perlin {
+            gradients,
+            perm_x: permutation(),
+            perm_y: permutation(),
+            perm_z: permutation(),
+        }
+    }
+
+    /// Gradient noise in roughly [-1, 1], zero at every integer lattice
+    /// point.
+    pub fn noise(&self, p: Point3) -> f32 {
+        let (fx, fy, fz) = (p.x.floor(), p.y.floor(), p.z.floor());
+        let (u, v, w) = (p.x - fx, p.y - fy, p.z - fz);
+        let (i, j, k) = (fx as i64, fy as i64, fz as i64);
+
+        let mut corners = [[[0.0; 2]; 2]; 2];
+        for (di, plane) in corners.iter_mut().enumerate() {
+            for (dj, row) in plane.iter_mut().enumerate() {
+                for (dk, corner) in row.iter_mut().enumerate() {
+                    let gradient = self.gradient(i + di as i64, j + dj as i64, k + dk as i64);
+                    let offset = Vec3::new(u - di as f32, v - dj as f32, w - dk as f32);
+                    *corner = Vec3::dot_product(gradient, offset);
+                }
+            }
+        }
+
+        // Quintic fade keeps the second derivative continuous across cells
+        let fade = |t: f32| t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+        let (u, v, w) = (fade(u), fade(v), fade(w));
+        let lerp = |a: f32, b: f32, t: f32| a + t * (b - a);
+
+        let x00 = lerp(corners[0][0][0], corners[1][0][0], u);
+        let x10 = lerp(corners[0][1][0], corners[1][1][0], u);
+        let x01 = lerp(corners[0][0][1], corners[1][0][1], u);
+        let x11 = lerp(corners[0][1][1], corners[1][1][1], u);
+        lerp(lerp(x00, x10, v), lerp(x01, x11, v), w)
+    }
+
+    /// Sum of `octaves` layers of noise, each `lacunarity` times the
+    /// frequency and `gain` times the amplitude of the previous one.
+    pub fn fbm(&self, p: Point3, octaves: u32, lacunarity: f32, gain: f32) -> f32 {
+        let mut sum = 0.0;
+        let mut point = p;
+        let mut amplitude = 1.0;
+        for _ in 0..octaves {
+            sum += amplitude * self.noise(point);
+            point *= lacunarity;
+            amplitude *= gain;
+        }
+        sum
+    }
+
+    /// Like `fbm` with the usual doubling frequency and halving amplitude,
+    /// but summing absolute values, which creases the noise into the veined
+    /// look of turbulence.
+    pub fn turbulence(&self, p: Point3, octaves: u32) -> f32 {
+        let mut sum = 0.0;
+        let mut point = p;
+        let mut amplitude = 1.0;
+        for _ in 0..octaves {
+            sum += amplitude * self.noise(point).abs();
+            point *= 2.0;
+            amplitude *= 0.5;
+        }
+        sum
+    }
+
+    #[inline]
+    fn gradient(&self, i: i64, j: i64, k: i64) -> Vec3 {
+        let mask = POINT_COUNT as i64 - 1;
+        let index = self.perm_x[(i & mask) as usize]
+            ^ self.perm_y[(j & mask) as usize]
+            ^ self.perm_z[(k & mask) as usize];
+        self.gradients[index]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sample_points() -> impl Iterator<Item = Point3> {
+        (0..500).map(|i| {
+            let t = i as f32 * 0.37;
+            Point3::new(t.sin() * 7.3, t * 0.11 - 3.0, t.cos() * 5.9)
+        })
+    }
+
+    #[test]
+    fn test_seed_is_deterministic() {
+        let a = Perlin::new(7);
+        let b = Perlin::new(7);
+        let c = Perlin::new(8);
+        let p = Point3::new(0.3, 1.7, -2.2);
+        assert_eq!(a.noise(p), b.noise(p));
+        assert_ne!(a.noise(p), c.noise(p));
+    }
+
+    #[test]
+    fn test_noise_range_and_lattice() {
+        let perlin = Perlin::new(1);
+        assert_eq!(perlin.noise(Point3::new(3.0, -2.0, 5.0)), 0.0);
+
+        let mut spread = 0.0f32;
+        for p in sample_points() {
+            let n = perlin.noise(p);
+            assert!((-1.0..=1.0).contains(&n), "{}", n);
+            spread = spread.max(n.abs());
+        }
+        assert!(spread > 0.2);
+    }
+
+    #[test]
+    fn test_turbulence_and_fbm() {
+        let perlin = Perlin::new(3);
+        for p in sample_points() {
+            let turbulence = perlin.turbulence(p, 7);
+            assert!((0.0..2.0).contains(&turbulence));
+            // A single octave is the noise itself
+            assert_eq!(perlin.fbm(p, 1, 2.0, 0.5), perlin.noise(p));
+        }
+    }
+}
@@ -1,12 +1,16 @@
-use std::rc::Rc;
 use std::sync::Arc;
-//use std::sync::Arc;
 
 use crate::ray::ray::Ray;
+use crate::utils::constants::{INFINITY, PI, UNIVERSE};
 use crate::utils::interval::Interval;
+use crate::utils::polynomial::solve_in;
+use crate::vec3::onb::Onb;
+use crate::vec3::transform::{AnimatedTransform, Transform};
 use crate::vec3::vec3::*;
+use crate::world::aabb::Aabb;
 use crate::world::hit_record::HitRecord;
 use crate::world::material::*;
+use crate::world::mesh::TriangleMesh;
 
 #[derive(Debug, Clone)]
 pub enum SceneObject {
@@ -15,12 +19,183 @@ pub enum SceneObject {
         center: Point3,
         material: Arc<Material>,
     },
+    /// Sphere moving in a straight line from `center` at time 0 to
+    /// `end_center` at time 1, resting at the ends outside that range.
+    MovingSphere {
+        radius: f32,
+        center: Point3,
+        end_center: Point3,
+        material: Arc<Material>,
+    },
+    /// Cylinder of `radius` around the axis from `base` to `top`, closed by
+    /// flat disks at the ends whose cap flag is set.
     Cylinder {
         radius: f32,
+        base: Point3,
+        top: Point3,
+        base_cap: bool,
+        top_cap: bool,
+        material: Arc<Material>,
+    },
+    /// Cone or frustum around the axis from `base` to `top`, whose radius
+    /// changes linearly between the two ends; a zero `top_radius` gives a
+    /// pointed cone. Ends with a positive radius can be capped.
+    Cone {
+        base: Point3,
+        top: Point3,
+        base_radius: f32,
+        top_radius: f32,
+        base_cap: bool,
+        top_cap: bool,
+        material: Arc<Material>,
+    },
+    /// Flat disk of `radius` around `center`, facing along `normal`.
+    Disk {
         center: Point3,
-        height: f32,
+        normal: Vec3,
+        radius: f32,
+        material: Arc<Material>,
+    },
+    /// Ring of `major_radius` around `axis` through `center`, swept by a
+    /// tube of `minor_radius`.
+    Torus {
+        center: Point3,
+        axis: Vec3,
+        major_radius: f32,
+        minor_radius: f32,
+        material: Arc<Material>,
+    },
+    /// Points within `radius` of the segment from `base` to `top`: a
+    /// cylinder closed by two hemispheres.
+    Capsule {
+        radius: f32,
+        base: Point3,
+        top: Point3,
         material: Arc<Material>,
     },
+    /// Infinite plane through `point`; it has no finite bounds and is kept
+    /// out of the BVH.
+    Plane {
+        point: Point3,
+        normal: Vec3,
+        material: Arc<Material>,
+    },
+    /// Parallelogram spanned by the edges `u` and `v` from the corner `q`.
+    Quad {
+        q: Point3,
+        u: Vec3,
+        v: Vec3,
+        material: Arc<Material>,
+    },
+    /// Triangle with counter-clockwise vertices `v0`, `v1`, `v2`.
+    Triangle {
+        v0: Point3,
+        v1: Point3,
+        v2: Point3,
+        material: Arc<Material>,
+    },
+    /// Triangle mesh, shared so the same geometry can be placed many times.
+    Mesh {
+        mesh: Arc<TriangleMesh>,
+        material: Arc<Material>,
+    },
+    /// A shared `object` placed in the world by `transform`. Rays are taken
+    /// into the object's space instead of moving the object, so every copy
+    /// costs only the transform.
+    Instance {
+        object: Arc<SceneObject>,
+        transform: Transform,
+    },
+    /// A shared `object` placed by a transform that changes over time, so
+    /// each ray sees it where it is at the ray's time.
+    MovingInstance {
+        object: Arc<SceneObject>,
+        motion: AnimatedTransform,
+    },
+    /// Smoke or fog of uniform `density` filling the closed `boundary`.
+    /// Rays scatter off it at random distances inside, following the
+    /// `phase` material. It has no surface for `hit` to find; the camera
+    /// samples where rays scatter in it through `medium_span`.
+    ConstantMedium {
+        boundary: Arc<SceneObject>,
+        density: f32,
+        phase: Arc<Material>,
+    },
+}
+
+// Rays closer to parallel than this are treated as missing flat primitives
+const PARALLEL_EPSILON: f32 = 1e-8;
+// Minimum thickness of the bounding box of flat primitives
+const FLAT_BOX_PADDING: f32 = 1e-4;
+
+/// Roots of `a t^2 - 2 h t + c` in ascending order, in the half-`b` form
+/// the sphere intersection uses.
+#[inline]
+fn quadratic_roots(a: f32, h: f32, c: f32) -> Option<[f32; 2]> {
+    let discriminant = h * h - a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    let sqrtd = discriminant.sqrt();
+    let (t0, t1) = ((h - sqrtd) / a, (h + sqrtd) / a);
+    Some([t0.min(t1), t0.max(t1)])
+}
+
+/// Distance a ray travels through a medium of `density` before scattering,
+/// by inverting the exponential distribution at the uniform sample `u`.
+#[inline]
+pub fn free_flight_distance(density: f32, u: f32) -> f32 {
+    -(1.0 - u).ln() / density
+}
+
+/// Center at `time` of a sphere moving from `center` to `end_center`.
+#[inline]
+fn moving_center(center: Point3, end_center: Point3, time: f32) -> Point3 {
+    let t = time.clamp(0.0, 1.0);
+    (1.0 - t) * center + t * end_center
+}
+
+/// Texture coordinate around an axis of a point at `(x, y)` in an `Onb` on
+/// that axis; upright shapes follow the sphere's longitude convention.
+#[inline]
+fn around_axis(x: f32, y: f32) -> f32 {
+    (y.atan2(-x) + PI) / (2.0 * PI)
+}
+
+/// Half size of the bounding box of a disk of `radius` facing along the
+/// unit vector `w`: its reach along each axis is `radius` times the sine of
+/// the angle between that axis and `w`.
+#[inline]
+fn disk_extent(w: Vec3, radius: f32) -> Vec3 {
+    radius
+        * Vec3::new(
+            (1.0 - w.x * w.x).max(0.0).sqrt(),
+            (1.0 - w.y * w.y).max(0.0).sqrt(),
+            (1.0 - w.z * w.z).max(0.0).sqrt(),
+        )
+}
+
+fn box_corners(bbox: &Aabb) -> [Point3; 8] {
+    let mut corners = [Point3::default(); 8];
+    for (i, corner) in corners.iter_mut().enumerate() {
+        *corner = Point3::new(
+            if i & 1 == 0 { bbox.x.min } else { bbox.x.max },
+            if i & 2 == 0 { bbox.y.min } else { bbox.y.max },
+            if i & 4 == 0 { bbox.z.min } else { bbox.z.max },
+        );
+    }
+    corners
+}
+
+/// Bounds of the end disks of a cylinder or cone.
+fn axis_bounding_box(base: Point3, top: Point3, base_radius: f32, top_radius: f32) -> Aabb {
+    let w = Vec3::unit(top - base);
+    let base_extent = disk_extent(w, base_radius);
+    let top_extent = disk_extent(w, top_radius);
+    let mut bbox = Aabb::from_points(base - base_extent, base + base_extent);
+    bbox.expand(top - top_extent);
+    bbox.expand(top + top_extent);
+    bbox
 }
 
 impl SceneObject {
@@ -56,52 +231,428 @@ impl SceneObject {
         rec.p = ray.at(root);
         let outward_normal = (rec.p - center) / radius;
         rec.set_face_normal(ray, outward_normal);
+        // Longitude from -x around through +z, latitude from the south pole
+        rec.u = ((-outward_normal.z).atan2(outward_normal.x) + PI) / (2.0 * PI);
+        rec.v = (-outward_normal.y).clamp(-1.0, 1.0).acos() / PI;
         true
     }
 
+    /// Cone side and caps; a cylinder is the case of equal radii.
     #[inline]
-    fn hit_cylinder(
+    fn hit_cone(
+        base: Point3,
+        top: Point3,
+        radii: (f32, f32),
+        caps: (bool, bool),
+        ray: &Ray,
+        ray_t: Interval,
+        rec: &mut HitRecord,
+    ) -> bool {
+        let (base_radius, top_radius) = radii;
+        let axis = top - base;
+        let height = axis.length();
+        let w = axis / height;
+        let slope = (top_radius - base_radius) / height;
+        let oc = ray.origin() - base;
+        let oc_axial = Vec3::dot_product(oc, w);
+        let d_axial = Vec3::dot_product(ray.direction(), w);
+        let oc_perp = oc - oc_axial * w;
+        let d_perp = ray.direction() - d_axial * w;
+
+        // Side: the distance from the axis, `oc_perp + t * d_perp`, equals
+        // the radius at the height reached, `r + slope * t * d_axial`
+        let mut hit_anything = false;
+        let mut closest = ray_t.max;
+        let mut outward_normal = Vec3::new(0.0, 0.0, 0.0);
+        let mut cap_radius = None;
+        let r = base_radius + slope * oc_axial;
+        let a = d_perp.length_squared() - slope * slope * d_axial * d_axial;
+        let h = slope * r * d_axial - Vec3::dot_product(oc_perp, d_perp);
+        let c = oc_perp.length_squared() - r * r;
+        let side_roots = if a.abs() > PARALLEL_EPSILON {
+            quadratic_roots(a, h, c)
+        } else if h.abs() > PARALLEL_EPSILON {
+            // Rays parallel to a line of the cone cross it once, and rays
+            // parallel to a cylinder's axis not at all
+            Some([c / (2.0 * h); 2])
+        } else {
+            None
+        };
+        for root in side_roots.into_iter().flatten() {
+            let s = oc_axial + root * d_axial;
+            if ray_t.contains(root) && (0.0..=height).contains(&s) {
+                let radial = oc_perp + root * d_perp;
+                let distance = radial.length();
+                let radial = if distance > 0.0 {
+                    radial / distance
+                } else {
+                    radial
+                };
+                hit_anything = true;
+                closest = root;
+                outward_normal = Vec3::unit(radial - slope * w);
+                break;
+            }
+        }
+
+        // End caps: disks at axial distance 0 (facing -w) and `height` (facing +w)
+        if d_axial.abs() > PARALLEL_EPSILON {
+            let (base_cap, top_cap) = caps;
+            for (capped, s, radius, normal) in [
+                (base_cap, 0.0, base_radius, -w),
+                (top_cap, height, top_radius, w),
+            ] {
+                let root = (s - oc_axial) / d_axial;
+                if !capped || !ray_t.contains(root) || (hit_anything && root >= closest) {
+                    continue;
+                }
+                if (oc_perp + root * d_perp).length_squared() <= radius * radius {
+                    hit_anything = true;
+                    closest = root;
+                    outward_normal = normal;
+                    cap_radius = Some(radius);
+                }
+            }
+        }
+
+        if !hit_anything {
+            return false;
+        }
+
+        rec.t = closest;
+        rec.p = ray.at(closest);
+        rec.set_face_normal(ray, outward_normal);
+        // Angle and height on the side; the caps map the disk onto the unit square
+        let frame = Onb::new(w);
+        let local = rec.p - base;
+        let x = Vec3::dot_product(local, frame.u);
+        let y = Vec3::dot_product(local, frame.v);
+        match cap_radius {
+            Some(radius) => {
+                rec.u = 0.5 + x / (2.0 * radius);
+                rec.v = 0.5 + y / (2.0 * radius);
+            }
+            None => {
+                rec.u = around_axis(x, y);
+                rec.v = Vec3::dot_product(local, w) / height;
+            }
+        }
+        true
+    }
+
+    #[inline]
+    fn hit_disk(
+        center: Point3,
+        normal: Vec3,
         radius: f32,
+        ray: &Ray,
+        ray_t: Interval,
+        rec: &mut HitRecord,
+    ) -> bool {
+        let denom = Vec3::dot_product(normal, ray.direction());
+        if denom.abs() < PARALLEL_EPSILON * normal.length() {
+            return false;
+        }
+
+        let t = Vec3::dot_product(normal, center - ray.origin()) / denom;
+        if !ray_t.contains(t) {
+            return false;
+        }
+
+        let p = ray.at(t);
+        let offset = p - center;
+        if offset.length_squared() > radius * radius {
+            return false;
+        }
+
+        rec.t = t;
+        rec.p = p;
+        // The disk inscribed in the unit square of texture space
+        let basis = Onb::new(normal);
+        rec.u = 0.5 + Vec3::dot_product(offset, basis.u) / (2.0 * radius);
+        rec.v = 0.5 + Vec3::dot_product(offset, basis.v) / (2.0 * radius);
+        rec.set_face_normal(ray, basis.w);
+        true
+    }
+
+    /// The quartic is set up in `f64` in the torus' frame, from the point of
+    /// the ray closest to the center, so that rays from far away keep their
+    /// precision, and solved by root isolation instead of Ferrari's formula.
+    #[inline]
+    fn hit_torus(
         center: Point3,
-        height: f32,
+        axis: Vec3,
+        radii: (f32, f32),
         ray: &Ray,
         ray_t: Interval,
         rec: &mut HitRecord,
     ) -> bool {
-        let oc = center - ray.origin();
-        let a = ray.direction().x * ray.direction().x + ray.direction().z * ray.direction().z;
-        let h = ray.direction().x * oc.x + ray.direction().z * oc.z;
-        let c = oc.x * oc.x + oc.z * oc.z - radius * radius;
-        let discriminant = h * h - a * c;
+        let (major_radius, minor_radius) = radii;
+        let frame = Onb::new(axis);
+        let to_local = |v: Vec3| {
+            [
+                Vec3::dot_product(v, frame.u) as f64,
+                Vec3::dot_product(v, frame.v) as f64,
+                Vec3::dot_product(v, frame.w) as f64,
+            ]
+        };
+        let dot = |a: [f64; 3], b: [f64; 3]| a[0] * b[0] + a[1] * b[1] + a[2] * b[2];
 
-        if discriminant < 0.0 {
+        // Unit direction, and the origin moved to the closest approach
+        let length = ray.direction().length() as f64;
+        let d = to_local(ray.direction()).map(|x| x / length);
+        let o = to_local(ray.origin() - center);
+        let shift = -dot(o, d);
+        let o = [
+            o[0] + shift * d[0],
+            o[1] + shift * d[1],
+            o[2] + shift * d[2],
+        ];
+
+        // Every hit lies within the bounding sphere, at most `extent` from
+        // the closest approach
+        let (big_r, small_r) = (major_radius as f64, minor_radius as f64);
+        let extent = big_r + small_r;
+        let o_squared = dot(o, o);
+        if o_squared > extent * extent {
             return false;
         }
+        let lo = (ray_t.min as f64 * length - shift).max(-extent);
+        let hi = (ray_t.max as f64 * length - shift).min(extent);
 
-        let sqrtd = discriminant.sqrt();
-        let half_height = height / 2.0;
-        let y_min = center.y - half_height;
-        let y_max = center.y + half_height;
+        // (|p|^2 + R^2 - r^2)^2 = 4 R^2 (p_x^2 + p_y^2) along p = o + s d
+        let n = dot(o, d);
+        let k = o_squared + big_r * big_r - small_r * small_r;
+        let four_r2 = 4.0 * big_r * big_r;
+        let coeffs = [
+            k * k - four_r2 * (o[0] * o[0] + o[1] * o[1]),
+            4.0 * n * k - 2.0 * four_r2 * (o[0] * d[0] + o[1] * d[1]),
+            4.0 * n * n + 2.0 * k - four_r2 * (d[0] * d[0] + d[1] * d[1]),
+            4.0 * n,
+            1.0,
+        ];
+        let roots = solve_in(&coeffs, lo, hi);
+        let Some(&s) = roots.as_slice().first() else {
+            return false;
+        };
+        let t = ((s + shift) / length) as f32;
+        if !ray_t.contains(t) {
+            return false;
+        }
 
-        let mut root = (h - sqrtd) / a;
-        let mut hit_point = ray.at(root);
+        // Normal away from the nearest point of the ring
+        let p = [o[0] + s * d[0], o[1] + s * d[1], o[2] + s * d[2]];
+        let ring_distance = (p[0] * p[0] + p[1] * p[1]).sqrt();
+        let scale = if ring_distance > 0.0 {
+            big_r / ring_distance
+        } else {
+            0.0
+        };
+        let normal = Vec3::new(
+            ((p[0] - scale * p[0]) / small_r) as f32,
+            ((p[1] - scale * p[1]) / small_r) as f32,
+            (p[2] / small_r) as f32,
+        );
 
-        if ray_t.surrounds(root) || hit_point.y < y_min || hit_point.y > y_max {
-            root = (h + sqrtd) / a;
-            hit_point = ray.at(root);
-            if ray_t.surrounds(root) || hit_point.y < y_min || hit_point.y > y_max {
-                return false;
+        rec.t = t;
+        rec.p = ray.at(t);
+        rec.set_face_normal(ray, Vec3::unit(frame.transform(normal)));
+        // Angle around the axis, and around the tube with the seam on the
+        // inside of the ring
+        rec.u = around_axis(p[0] as f32, p[1] as f32);
+        rec.v = ((p[2].atan2(ring_distance - big_r) as f32) + PI) / (2.0 * PI);
+        true
+    }
+
+    #[inline]
+    fn hit_capsule(
+        radius: f32,
+        base: Point3,
+        top: Point3,
+        ray: &Ray,
+        ray_t: Interval,
+        rec: &mut HitRecord,
+    ) -> bool {
+        let axis = top - base;
+        let height = axis.length();
+        let w = axis / height;
+        let oc = ray.origin() - base;
+        let oc_axial = Vec3::dot_product(oc, w);
+        let d_axial = Vec3::dot_product(ray.direction(), w);
+        let oc_perp = oc - oc_axial * w;
+        let d_perp = ray.direction() - d_axial * w;
+
+        // Side wall between the two end spheres
+        let mut hit_anything = false;
+        let mut closest = ray_t.max;
+        let mut center = base;
+        let a = d_perp.length_squared();
+        if a > PARALLEL_EPSILON {
+            let h = -Vec3::dot_product(oc_perp, d_perp);
+            let c = oc_perp.length_squared() - radius * radius;
+            for root in quadratic_roots(a, h, c).into_iter().flatten() {
+                let s = oc_axial + root * d_axial;
+                if ray_t.contains(root) && (0.0..=height).contains(&s) {
+                    hit_anything = true;
+                    closest = root;
+                    center = base + s * w;
+                    break;
+                }
             }
         }
 
-        rec.t = root;
-        rec.p = hit_point;
-        let outward_normal = Vec3::new(
-            (rec.p.x - center.x) / radius,
-            0.0, // Y component is 0 for cylinder sides
-            (rec.p.z - center.z) / radius,
+        // Each end sphere only counts beyond its end of the axis
+        let a = ray.direction().length_squared();
+        for (end, beyond) in [(base, -1.0), (top, 1.0)] {
+            let end_origin = end - ray.origin();
+            let h = Vec3::dot_product(ray.direction(), end_origin);
+            let c = end_origin.length_squared() - radius * radius;
+            for root in quadratic_roots(a, h, c).into_iter().flatten() {
+                if !ray_t.contains(root) || (hit_anything && root >= closest) {
+                    continue;
+                }
+                let s = oc_axial + root * d_axial;
+                let end_s = if beyond < 0.0 { 0.0 } else { height };
+                if beyond * (s - end_s) >= 0.0 {
+                    hit_anything = true;
+                    closest = root;
+                    center = end;
+                    break;
+                }
+            }
+        }
+
+        if !hit_anything {
+            return false;
+        }
+
+        rec.t = closest;
+        rec.p = ray.at(closest);
+        rec.set_face_normal(ray, (rec.p - center) / radius);
+        // Angle around the axis, and distance along the profile from the
+        // bottom pole
+        let frame = Onb::new(w);
+        let local = rec.p - base;
+        rec.u = around_axis(
+            Vec3::dot_product(local, frame.u),
+            Vec3::dot_product(local, frame.v),
         );
-        rec.set_face_normal(ray, outward_normal);
+        rec.v = (Vec3::dot_product(local, w) + radius) / (height + 2.0 * radius);
+        true
+    }
+
+    #[inline]
+    fn hit_plane(
+        point: Point3,
+        normal: Vec3,
+        ray: &Ray,
+        ray_t: Interval,
+        rec: &mut HitRecord,
+    ) -> bool {
+        let denom = Vec3::dot_product(normal, ray.direction());
+        if denom.abs() < PARALLEL_EPSILON * normal.length() {
+            return false;
+        }
+
+        let t = Vec3::dot_product(normal, point - ray.origin()) / denom;
+        if !ray_t.contains(t) {
+            return false;
+        }
+
+        rec.t = t;
+        rec.p = ray.at(t);
+        // Plane coordinates relative to `point` in an arbitrary tangent basis
+        let basis = Onb::new(normal);
+        let offset = rec.p - point;
+        rec.u = Vec3::dot_product(offset, basis.u);
+        rec.v = Vec3::dot_product(offset, basis.v);
+        rec.set_face_normal(ray, Vec3::unit(normal));
+        true
+    }
+
+    #[inline]
+    fn hit_quad(
+        q: Point3,
+        u: Vec3,
+        v: Vec3,
+        ray: &Ray,
+        ray_t: Interval,
+        rec: &mut HitRecord,
+    ) -> bool {
+        let n = Vec3::cross_product(u, v);
+        let denom = Vec3::dot_product(n, ray.direction());
+        if denom.abs() < PARALLEL_EPSILON * n.length() {
+            return false;
+        }
+
+        let t = Vec3::dot_product(n, q - ray.origin()) / denom;
+        if !ray_t.contains(t) {
+            return false;
+        }
+
+        // Express the hit point in the (u, v) frame of the quad
+        let p = ray.at(t);
+        let planar = p - q;
+        let w = n / n.length_squared();
+        let alpha = Vec3::dot_product(w, Vec3::cross_product(planar, v));
+        let beta = Vec3::dot_product(w, Vec3::cross_product(u, planar));
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return false;
+        }
+
+        rec.t = t;
+        rec.p = p;
+        rec.u = alpha;
+        rec.v = beta;
+        rec.set_face_normal(ray, Vec3::unit(n));
+        true
+    }
+
+    /// Möller-Trumbore intersection. The record's `(u, v)` are the
+    /// barycentric weights of `v1` and `v2`.
+    #[inline]
+    pub(crate) fn hit_triangle(
+        v0: Point3,
+        v1: Point3,
+        v2: Point3,
+        ray: &Ray,
+        ray_t: Interval,
+        rec: &mut HitRecord,
+    ) -> bool {
+        let edge1 = v1 - v0;
+        let edge2 = v2 - v0;
+        let normal = Vec3::cross_product(edge1, edge2);
+        let pvec = Vec3::cross_product(ray.direction(), edge2);
+        // `det` is the cosine scaled by the area and the ray's length, so
+        // small triangles are not taken for edge-on ones
+        let det = Vec3::dot_product(edge1, pvec);
+        if det.abs() < PARALLEL_EPSILON * normal.length() * ray.direction().length() {
+            return false;
+        }
+
+        let inv_det = 1.0 / det;
+        let tvec = ray.origin() - v0;
+        let b1 = Vec3::dot_product(tvec, pvec) * inv_det;
+        if !(0.0..=1.0).contains(&b1) {
+            return false;
+        }
+
+        let qvec = Vec3::cross_product(tvec, edge1);
+        let b2 = Vec3::dot_product(ray.direction(), qvec) * inv_det;
+        if b2 < 0.0 || b1 + b2 > 1.0 {
+            return false;
+        }
+
+        let t = Vec3::dot_product(edge2, qvec) * inv_det;
+        if !ray_t.contains(t) {
+            return false;
+        }
+
+        rec.t = t;
+        rec.p = ray.at(t);
+        rec.u = b1;
+        rec.v = b2;
+        rec.set_face_normal(ray, Vec3::unit(normal));
         true
     }
 
@@ -114,7 +665,21 @@ impl SceneObject {
                 material,
             } => {
                 if Self::hit_sphere(*radius, *center, r, interval, rec) {
-                    rec.material = Arc::clone(&material);
+                    rec.material = Arc::clone(material);
+                    true
+                } else {
+                    false
+                }
+            }
+            Self::MovingSphere {
+                radius,
+                center,
+                end_center,
+                material,
+            } => {
+                let center = moving_center(*center, *end_center, r.time());
+                if Self::hit_sphere(*radius, center, r, interval, rec) {
+                    rec.material = Arc::clone(material);
                     true
                 } else {
                     false
@@ -122,17 +687,1248 @@ impl SceneObject {
             }
             Self::Cylinder {
                 radius,
+                base,
+                top,
+                base_cap,
+                top_cap,
+                material,
+            } => {
+                let radii = (*radius, *radius);
+                let caps = (*base_cap, *top_cap);
+                if Self::hit_cone(*base, *top, radii, caps, r, interval, rec) {
+                    rec.material = Arc::clone(material);
+                    true
+                } else {
+                    false
+                }
+            }
+            Self::Cone {
+                base,
+                top,
+                base_radius,
+                top_radius,
+                base_cap,
+                top_cap,
+                material,
+            } => {
+                let radii = (*base_radius, *top_radius);
+                let caps = (*base_cap, *top_cap);
+                if Self::hit_cone(*base, *top, radii, caps, r, interval, rec) {
+                    rec.material = Arc::clone(material);
+                    true
+                } else {
+                    false
+                }
+            }
+            Self::Disk {
+                center,
+                normal,
+                radius,
+                material,
+            } => {
+                if Self::hit_disk(*center, *normal, *radius, r, interval, rec) {
+                    rec.material = Arc::clone(material);
+                    true
+                } else {
+                    false
+                }
+            }
+            Self::Torus {
                 center,
-                height,
+                axis,
+                major_radius,
+                minor_radius,
+                material,
+            } => {
+                let radii = (*major_radius, *minor_radius);
+                if Self::hit_torus(*center, *axis, radii, r, interval, rec) {
+                    rec.material = Arc::clone(material);
+                    true
+                } else {
+                    false
+                }
+            }
+            Self::Capsule {
+                radius,
+                base,
+                top,
                 material,
             } => {
-                if Self::hit_cylinder(*radius, *center, *height, r, interval, rec) {
-                    rec.material = Arc::clone(&material);
+                if Self::hit_capsule(*radius, *base, *top, r, interval, rec) {
+                    rec.material = Arc::clone(material);
+                    true
+                } else {
+                    false
+                }
+            }
+            Self::Plane {
+                point,
+                normal,
+                material,
+            } => {
+                if Self::hit_plane(*point, *normal, r, interval, rec) {
+                    rec.material = Arc::clone(material);
+                    true
+                } else {
+                    false
+                }
+            }
+            Self::Quad { q, u, v, material } => {
+                if Self::hit_quad(*q, *u, *v, r, interval, rec) {
+                    rec.material = Arc::clone(material);
+                    true
+                } else {
+                    false
+                }
+            }
+            Self::Triangle {
+                v0,
+                v1,
+                v2,
+                material,
+            } => {
+                if Self::hit_triangle(*v0, *v1, *v2, r, interval, rec) {
+                    rec.material = Arc::clone(material);
+                    true
+                } else {
+                    false
+                }
+            }
+            Self::Mesh { mesh, material } => {
+                if mesh.hit(r, interval, rec) {
+                    rec.material = Arc::clone(material);
                     true
                 } else {
                     false
                 }
             }
+            Self::Instance { object, transform } => {
+                Self::hit_instance(object, transform, r, interval, rec)
+            }
+            Self::MovingInstance { object, motion } => {
+                Self::hit_instance(object, &motion.at(r.time()), r, interval, rec)
+            }
+            Self::ConstantMedium { .. } => false,
+        }
+    }
+
+    /// Density and phase function of a `ConstantMedium`, possibly placed by
+    /// instances, or `None` if the object is not one.
+    pub fn medium(&self) -> Option<(f32, &Arc<Material>)> {
+        match self {
+            Self::ConstantMedium { density, phase, .. } => Some((*density, phase)),
+            Self::Instance { object, .. } | Self::MovingInstance { object, .. } => object.medium(),
+            _ => None,
+        }
+    }
+
+    /// Part of `ray_t` a ray spends inside a `ConstantMedium`, possibly
+    /// placed by instances, or `None` if it misses the medium or the object
+    /// is not one. The boundary is searched over the whole line, so that
+    /// rays starting inside still see the medium ahead of them.
+    pub fn medium_span(&self, ray: &Ray, ray_t: Interval) -> Option<Interval> {
+        let boundary = match self {
+            Self::ConstantMedium { boundary, .. } => boundary,
+            Self::Instance { object, transform } => {
+                return object.medium_span(&Self::local_ray(transform, ray), ray_t)
+            }
+            Self::MovingInstance { object, motion } => {
+                let transform = motion.at(ray.time());
+                return object.medium_span(&Self::local_ray(&transform, ray), ray_t);
+            }
+            _ => return None,
+        };
+        let mut entry = HitRecord::default();
+        if !boundary.hit(ray, Interval::new(-INFINITY, INFINITY), &mut entry) {
+            return None;
+        }
+        let mut exit = HitRecord::default();
+        if !boundary.hit(ray, Interval::new(entry.t + 1e-4, INFINITY), &mut exit) {
+            return None;
+        }
+
+        let span = Interval::new(entry.t.max(ray_t.min), exit.t.min(ray_t.max));
+        (span.min < span.max).then_some(span)
+    }
+
+    #[inline]
+    fn hit_instance(
+        object: &SceneObject,
+        transform: &Transform,
+        ray: &Ray,
+        ray_t: Interval,
+        rec: &mut HitRecord,
+    ) -> bool {
+        if !object.hit(&Self::local_ray(transform, ray), ray_t, rec) {
+            return false;
+        }
+        rec.p = transform.point(rec.p);
+        rec.normal = Vec3::unit(transform.normal(rec.normal));
+        true
+    }
+
+    /// `ray` in the object space of an instance. The direction is not
+    /// renormalized, so `t` carries over.
+    #[inline]
+    fn local_ray(transform: &Transform, ray: &Ray) -> Ray {
+        Ray::new(
+            transform.inverse_point(ray.origin()),
+            transform.inverse_vector(ray.direction()),
+        )
+        .with_time(ray.time())
+    }
+
+    /// Whether `random_direction` and `pdf_value` are implemented, i.e. the
+    /// object can be sampled explicitly when it is a light.
+    pub fn supports_sampling(&self) -> bool {
+        match self {
+            Self::Sphere { .. } | Self::Quad { .. } | Self::Triangle { .. } => true,
+            Self::Instance { object, .. } | Self::MovingInstance { object, .. } => {
+                object.supports_sampling()
+            }
+            _ => false,
+        }
+    }
+
+    /// Solid angle density of `random_direction` from `origin` at `time`
+    /// producing `direction`.
+    pub fn pdf_value(&self, origin: Point3, direction: Vec3, time: f32) -> f32 {
+        match self {
+            Self::Sphere { radius, center, .. } => {
+                let mut rec = HitRecord::default();
+                let ray = Ray::new(origin, direction);
+                if !Self::hit_sphere(
+                    *radius,
+                    *center,
+                    &ray,
+                    Interval::new(0.001, INFINITY),
+                    &mut rec,
+                ) {
+                    return 0.0;
+                }
+
+                let distance_squared = (*center - origin).length_squared();
+                if distance_squared <= radius * radius {
+                    return 0.0;
+                }
+                let cos_theta_max = (1.0 - radius * radius / distance_squared).sqrt();
+                let solid_angle = 2.0 * PI * (1.0 - cos_theta_max);
+                1.0 / solid_angle
+            }
+            Self::Quad { q, u, v, .. } => {
+                let area = Vec3::cross_product(*u, *v).length();
+                Self::flat_pdf_value(origin, direction, area, |ray, rec| {
+                    Self::hit_quad(*q, *u, *v, ray, Interval::new(0.001, INFINITY), rec)
+                })
+            }
+            Self::Triangle { v0, v1, v2, .. } => {
+                let area = 0.5 * Vec3::cross_product(*v1 - *v0, *v2 - *v0).length();
+                Self::flat_pdf_value(origin, direction, area, |ray, rec| {
+                    Self::hit_triangle(*v0, *v1, *v2, ray, Interval::new(0.001, INFINITY), rec)
+                })
+            }
+            Self::Instance { object, transform } => {
+                Self::instance_pdf_value(object, transform, origin, direction, time)
+            }
+            Self::MovingInstance { object, motion } => {
+                Self::instance_pdf_value(object, &motion.at(time), origin, direction, time)
+            }
+            Self::MovingSphere { .. }
+            | Self::Cylinder { .. }
+            | Self::Cone { .. }
+            | Self::Disk { .. }
+            | Self::Torus { .. }
+            | Self::Capsule { .. }
+            | Self::Plane { .. }
+            | Self::Mesh { .. }
+            | Self::ConstantMedium { .. } => 0.0,
+        }
+    }
+
+    /// Maps the density the instanced object gives the local direction to
+    /// world space. Unit directions `u'` map to `M u' / |M u'|`, which
+    /// stretches solid angle by `|det M| / |M u'|^3`.
+    #[inline]
+    fn instance_pdf_value(
+        object: &SceneObject,
+        transform: &Transform,
+        origin: Point3,
+        direction: Vec3,
+        time: f32,
+    ) -> f32 {
+        let local = transform.inverse_vector(direction);
+        let pdf = object.pdf_value(transform.inverse_point(origin), local, time);
+        let stretch = direction.length() / local.length();
+        pdf * stretch * stretch * stretch / transform.volume_scale().abs()
+    }
+
+    /// Converts the uniform area density of a flat primitive to solid angle
+    /// as seen from `origin`.
+    #[inline]
+    fn flat_pdf_value<F>(origin: Point3, direction: Vec3, area: f32, hit: F) -> f32
+    where
+        F: Fn(&Ray, &mut HitRecord) -> bool,
+    {
+        let mut rec = HitRecord::default();
+        if !hit(&Ray::new(origin, direction), &mut rec) {
+            return 0.0;
+        }
+
+        let distance_squared = rec.t * rec.t * direction.length_squared();
+        let cosine = (Vec3::dot_product(direction, rec.normal) / direction.length()).abs();
+        if cosine <= 0.0 {
+            return 0.0;
+        }
+        distance_squared / (cosine * area)
+    }
+
+    /// Direction from `origin` towards a point of the object as it is at
+    /// `time`, chosen by the sample `(u1, u2)` from the unit square, or
+    /// `None` if the object does not support sampling.
+    pub fn random_direction(&self, origin: Point3, time: f32, u1: f32, u2: f32) -> Option<Vec3> {
+        match self {
+            Self::Sphere { radius, center, .. } => {
+                // Uniform over the cone of directions the sphere subtends
+                let direction = *center - origin;
+                let distance_squared = direction.length_squared();
+                if distance_squared <= radius * radius {
+                    return Some(Vec3::sample_unit_vector(u1, u2));
+                }
+
+                let cos_theta_max = (1.0 - radius * radius / distance_squared).sqrt();
+                let z = 1.0 + u2 * (cos_theta_max - 1.0);
+                let phi = 2.0 * PI * u1;
+                let sin_theta = (1.0 - z * z).sqrt();
+                Some(Onb::new(direction).transform(Vec3::new(
+                    phi.cos() * sin_theta,
+                    phi.sin() * sin_theta,
+                    z,
+                )))
+            }
+            Self::Quad { q, u, v, .. } => {
+                let p = *q + u1 * *u + u2 * *v;
+                Some(p - origin)
+            }
+            Self::Triangle { v0, v1, v2, .. } => {
+                // Uniform barycentric coordinates via the square root warp
+                let sqrt_u1 = u1.sqrt();
+                let p = (1.0 - sqrt_u1) * *v0 + (sqrt_u1 * (1.0 - u2)) * *v1 + (sqrt_u1 * u2) * *v2;
+                Some(p - origin)
+            }
+            Self::Instance { object, transform } => object
+                .random_direction(transform.inverse_point(origin), time, u1, u2)
+                .map(|direction| transform.vector(direction)),
+            Self::MovingInstance { object, motion } => {
+                let transform = motion.at(time);
+                object
+                    .random_direction(transform.inverse_point(origin), time, u1, u2)
+                    .map(|direction| transform.vector(direction))
+            }
+            Self::MovingSphere { .. }
+            | Self::Cylinder { .. }
+            | Self::Cone { .. }
+            | Self::Disk { .. }
+            | Self::Torus { .. }
+            | Self::Capsule { .. }
+            | Self::Plane { .. }
+            | Self::Mesh { .. }
+            | Self::ConstantMedium { .. } => None,
+        }
+    }
+
+    pub fn material(&self) -> &Arc<Material> {
+        match self {
+            Self::Sphere { material, .. }
+            | Self::MovingSphere { material, .. }
+            | Self::Cylinder { material, .. }
+            | Self::Cone { material, .. }
+            | Self::Disk { material, .. }
+            | Self::Torus { material, .. }
+            | Self::Capsule { material, .. }
+            | Self::Plane { material, .. }
+            | Self::Quad { material, .. }
+            | Self::Triangle { material, .. }
+            | Self::Mesh { material, .. } => material,
+            Self::Instance { object, .. } | Self::MovingInstance { object, .. } => {
+                object.material()
+            }
+            Self::ConstantMedium { phase, .. } => phase,
+        }
+    }
+
+    pub fn bounding_box(&self) -> Aabb {
+        match self {
+            Self::Sphere { radius, center, .. } => {
+                let r = Vec3::new(*radius, *radius, *radius);
+                Aabb::from_points(*center - r, *center + r)
+            }
+            Self::MovingSphere {
+                radius,
+                center,
+                end_center,
+                ..
+            } => {
+                let r = Vec3::new(*radius, *radius, *radius);
+                let start = Aabb::from_points(*center - r, *center + r);
+                let end = Aabb::from_points(*end_center - r, *end_center + r);
+                Aabb::surrounding(&start, &end)
+            }
+            Self::Cylinder {
+                radius, base, top, ..
+            } => axis_bounding_box(*base, *top, *radius, *radius),
+            Self::Cone {
+                base,
+                top,
+                base_radius,
+                top_radius,
+                ..
+            } => axis_bounding_box(*base, *top, *base_radius, *top_radius),
+            Self::Disk {
+                center,
+                normal,
+                radius,
+                ..
+            } => {
+                let extent = disk_extent(Vec3::unit(*normal), *radius);
+                Aabb::from_points(*center - extent, *center + extent).pad(FLAT_BOX_PADDING)
+            }
+            Self::Torus {
+                center,
+                axis,
+                major_radius,
+                minor_radius,
+                ..
+            } => {
+                let tube = Vec3::new(*minor_radius, *minor_radius, *minor_radius);
+                let extent = disk_extent(Vec3::unit(*axis), *major_radius) + tube;
+                Aabb::from_points(*center - extent, *center + extent)
+            }
+            Self::Capsule {
+                radius, base, top, ..
+            } => {
+                let r = Vec3::new(*radius, *radius, *radius);
+                let mut bbox = Aabb::from_points(*base - r, *base + r);
+                bbox.expand(*top - r);
+                bbox.expand(*top + r);
+                bbox
+            }
+            Self::Plane { .. } => Aabb::new(UNIVERSE, UNIVERSE, UNIVERSE),
+            Self::Quad { q, u, v, .. } => {
+                let mut bbox = Aabb::from_points(*q, *q + *u + *v);
+                bbox.expand(*q + *u);
+                bbox.expand(*q + *v);
+                bbox.pad(FLAT_BOX_PADDING)
+            }
+            Self::Triangle { v0, v1, v2, .. } => {
+                let mut bbox = Aabb::from_points(*v0, *v1);
+                bbox.expand(*v2);
+                bbox.pad(FLAT_BOX_PADDING)
+            }
+            Self::Mesh { mesh, .. } => mesh.bounding_box(),
+            Self::Instance { object, transform } => {
+                if object.is_unbounded() {
+                    return Aabb::new(UNIVERSE, UNIVERSE, UNIVERSE);
+                }
+                // Box around the eight transformed corners
+                let mut bbox = Aabb::EMPTY;
+                for corner in box_corners(&object.bounding_box()) {
+                    bbox.expand(transform.point(corner));
+                }
+                bbox
+            }
+            Self::MovingInstance { object, motion } => {
+                if object.is_unbounded() {
+                    return Aabb::new(UNIVERSE, UNIVERSE, UNIVERSE);
+                }
+                // Corners along the whole motion, padded for rotations
+                // carrying them off the straight lines between samples
+                let corners = box_corners(&object.bounding_box());
+                let mut bbox = Aabb::EMPTY;
+                for transform in motion.sweep() {
+                    for corner in corners {
+                        bbox.expand(transform.point(corner));
+                    }
+                }
+                let radius = corners.iter().map(|c| c.length()).fold(0.0, f32::max);
+                let grow = 2.0 * motion.sweep_padding(radius);
+                Aabb::new(
+                    bbox.x.expand(grow),
+                    bbox.y.expand(grow),
+                    bbox.z.expand(grow),
+                )
+            }
+            Self::ConstantMedium { boundary, .. } => boundary.bounding_box(),
+        }
+    }
+
+    /// Whether the object extends infinitely and cannot go into a BVH.
+    pub fn is_unbounded(&self) -> bool {
+        match self {
+            Self::Plane { .. } => true,
+            Self::Instance { object, .. } | Self::MovingInstance { object, .. } => {
+                object.is_unbounded()
+            }
+            Self::ConstantMedium { boundary, .. } => boundary.is_unbounded(),
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::vec3::color3::Color3;
+    use crate::vec3::transform::{Keyframe, Quaternion};
+
+    fn interval() -> Interval {
+        Interval::new(0.001, INFINITY)
+    }
+
+    // Unit square in the z = 0 plane, facing +Z
+    fn unit_quad() -> SceneObject {
+        SceneObject::Quad {
+            q: Point3::new(0.0, 0.0, 0.0),
+            u: Vec3::new(1.0, 0.0, 0.0),
+            v: Vec3::new(0.0, 1.0, 0.0),
+            material: Arc::new(Material::default()),
+        }
+    }
+
+    // Right triangle in the z = 0 plane, facing +Z
+    fn unit_triangle() -> SceneObject {
+        SceneObject::Triangle {
+            v0: Point3::new(0.0, 0.0, 0.0),
+            v1: Point3::new(1.0, 0.0, 0.0),
+            v2: Point3::new(0.0, 1.0, 0.0),
+            material: Arc::new(Material::default()),
+        }
+    }
+
+    fn ground_plane() -> SceneObject {
+        SceneObject::Plane {
+            point: Point3::new(0.0, 0.0, 0.0),
+            normal: Vec3::new(0.0, 1.0, 0.0),
+            material: Arc::new(Material::default()),
+        }
+    }
+
+    fn down_z(x: f32, y: f32) -> Ray {
+        Ray::new(Point3::new(x, y, 5.0), Vec3::new(0.0, 0.0, -1.0))
+    }
+
+    #[test]
+    fn test_sphere_uv() {
+        let sphere = SceneObject::Sphere {
+            radius: 1.0,
+            center: Point3::new(0.0, 0.0, 0.0),
+            material: Arc::new(Material::default()),
+        };
+        let mut rec = HitRecord::default();
+        let ray = Ray::new(Point3::new(5.0, 0.0, 0.0), Vec3::new(-1.0, 0.0, 0.0));
+        assert!(sphere.hit(&ray, interval(), &mut rec));
+        assert!((rec.u - 0.5).abs() < 1e-6 && (rec.v - 0.5).abs() < 1e-6);
+
+        assert!(sphere.hit(&down_z(0.0, 0.0), interval(), &mut rec));
+        assert!((rec.u - 0.25).abs() < 1e-6);
+
+        let ray = Ray::new(Point3::new(0.0, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        assert!(sphere.hit(&ray, interval(), &mut rec));
+        assert!((rec.v - 1.0).abs() < 1e-6);
+    }
+
+    // Unit radius cylinder from (0, -1, 0) to (0, 1, 0)
+    fn upright_cylinder(capped: bool) -> SceneObject {
+        SceneObject::Cylinder {
+            radius: 1.0,
+            base: Point3::new(0.0, -1.0, 0.0),
+            top: Point3::new(0.0, 1.0, 0.0),
+            base_cap: capped,
+            top_cap: capped,
+            material: Arc::new(Material::default()),
+        }
+    }
+
+    #[test]
+    fn test_cylinder_uv() {
+        let cylinder = upright_cylinder(false);
+        let mut rec = HitRecord::default();
+        let ray = Ray::new(Point3::new(5.0, 0.5, 0.0), Vec3::new(-1.0, 0.0, 0.0));
+        assert!(cylinder.hit(&ray, interval(), &mut rec));
+        assert!((rec.u - 0.5).abs() < 1e-6);
+        assert!((rec.v - 0.75).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_cylinder_along_axis() {
+        let mut rec = HitRecord::default();
+        let down = Ray::new(Point3::new(0.5, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        // An open tube lets rays parallel to the axis straight through
+        assert!(!upright_cylinder(false).hit(&down, interval(), &mut rec));
+
+        assert!(upright_cylinder(true).hit(&down, interval(), &mut rec));
+        assert_eq!(rec.t, 4.0);
+        assert!(rec.front_face);
+        assert_eq!(rec.normal, Vec3::new(0.0, 1.0, 0.0));
+
+        // From inside, the far cap is hit from behind
+        let inside = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        assert!(upright_cylinder(true).hit(&inside, interval(), &mut rec));
+        assert_eq!(rec.t, 1.0);
+        assert!(!rec.front_face);
+        assert_eq!(rec.normal, Vec3::new(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn test_cylinder_through_caps() {
+        // Oblique ray entering the top cap and leaving through the side
+        let ray = Ray::new(Point3::new(0.0, 2.0, 0.0), Vec3::new(0.5, -1.0, 0.0));
+        let mut rec = HitRecord::default();
+        assert!(upright_cylinder(true).hit(&ray, interval(), &mut rec));
+        assert!((rec.t - 1.0).abs() < 1e-6);
+        assert_eq!(rec.normal, Vec3::new(0.0, 1.0, 0.0));
+        assert!((rec.u - 0.25).abs() < 1e-6 && (rec.v - 0.5).abs() < 1e-6);
+
+        // Without the cap the ray meets the inside of the wall
+        assert!(upright_cylinder(false).hit(&ray, interval(), &mut rec));
+        assert!((rec.t - 2.0).abs() < 1e-6);
+        assert!(!rec.front_face);
+        assert!((rec.normal - Vec3::new(-1.0, 0.0, 0.0)).length() < 1e-6);
+
+        // Only the base is capped: the same ray reversed enters through it
+        let cylinder = SceneObject::Cylinder {
+            radius: 1.0,
+            base: Point3::new(0.0, -1.0, 0.0),
+            top: Point3::new(0.0, 1.0, 0.0),
+            base_cap: true,
+            top_cap: false,
+            material: Arc::new(Material::default()),
+        };
+        let up = Ray::new(Point3::new(0.0, -2.0, 0.0), Vec3::new(0.5, 1.0, 0.0));
+        assert!(cylinder.hit(&up, interval(), &mut rec));
+        assert_eq!(rec.normal, Vec3::new(0.0, -1.0, 0.0));
+    }
+
+    #[test]
+    fn test_cylinder_grazing_rim() {
+        let cylinder = upright_cylinder(true);
+        let mut rec = HitRecord::default();
+        // Just above the top rim, and just outside the side wall
+        assert!(!cylinder.hit(&down_z(0.0, 1.0001), interval(), &mut rec));
+        assert!(!cylinder.hit(&down_z(1.0001, 0.0), interval(), &mut rec));
+        // Just inside the rim along the top edge
+        assert!(cylinder.hit(&down_z(0.0, 0.9999), interval(), &mut rec));
+        assert!((rec.t - 4.0).abs() < 1e-3);
+        assert!((rec.normal - Vec3::new(0.0, 0.0, 1.0)).length() < 1e-3);
+
+        // Diagonal ray clipping the corner between the cap and the wall
+        let corner = Ray::new(Point3::new(-2.0, 2.0, 0.0), Vec3::new(1.0, -1.0, 0.0));
+        assert!(cylinder.hit(&corner, interval(), &mut rec));
+        assert!((rec.p - Point3::new(-1.0, 1.0, 0.0)).length() < 1e-5);
+    }
+
+    #[test]
+    fn test_tilted_cylinder() {
+        // Axis along x + y; a ray down the axis hits the near cap head on
+        let cylinder = SceneObject::Cylinder {
+            radius: 0.5,
+            base: Point3::new(0.0, 0.0, 0.0),
+            top: Point3::new(2.0, 2.0, 0.0),
+            base_cap: true,
+            top_cap: true,
+            material: Arc::new(Material::default()),
+        };
+        let axis = Vec3::unit(Vec3::new(1.0, 1.0, 0.0));
+        let ray = Ray::new(Point3::new(4.0, 4.0, 0.0), -axis);
+        let mut rec = HitRecord::default();
+        assert!(cylinder.hit(&ray, interval(), &mut rec));
+        assert!((rec.p - Point3::new(2.0, 2.0, 0.0)).length() < 1e-5);
+        assert!((rec.normal - axis).length() < 1e-6);
+
+        // Side hit perpendicular to the axis, half way along it
+        let across = Ray::new(Point3::new(1.0, 1.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(cylinder.hit(&across, interval(), &mut rec));
+        assert!((rec.t - 4.5).abs() < 1e-5);
+        assert!((rec.v - 0.5).abs() < 1e-5);
+
+        let bbox = cylinder.bounding_box();
+        let reach = 0.5 * 0.5f32.sqrt();
+        assert!((bbox.x.min + reach).abs() < 1e-5 && (bbox.x.max - 2.0 - reach).abs() < 1e-5);
+        assert!((bbox.z.min + 0.5).abs() < 1e-5 && (bbox.z.max - 0.5).abs() < 1e-5);
+    }
+
+    fn assert_near(a: Vec3, b: Vec3, tolerance: f32) {
+        assert!((a - b).length() < tolerance, "{} != {}", a, b);
+    }
+
+    // Cone from a unit base disk at the origin up to the `top_radius` at y = 2
+    fn upright_cone(top_radius: f32, top_cap: bool) -> SceneObject {
+        SceneObject::Cone {
+            base: Point3::new(0.0, 0.0, 0.0),
+            top: Point3::new(0.0, 2.0, 0.0),
+            base_radius: 1.0,
+            top_radius,
+            base_cap: true,
+            top_cap,
+            material: Arc::new(Material::default()),
+        }
+    }
+
+    #[test]
+    fn test_cone_side_and_apex() {
+        let cone = upright_cone(0.0, false);
+        let mut rec = HitRecord::default();
+        // Half way up the radius is 0.5
+        assert!(cone.hit(&down_z(0.0, 1.0), interval(), &mut rec));
+        assert!((rec.t - 4.5).abs() < 1e-6);
+        assert!(rec.front_face);
+        assert_near(rec.normal, Vec3::unit(Vec3::new(0.0, 0.5, 1.0)), 1e-6);
+        assert!((rec.v - 0.5).abs() < 1e-6);
+        assert!(!cone.hit(&down_z(0.0, 2.0001), interval(), &mut rec));
+
+        let down = Ray::new(Point3::new(0.5, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        assert!(cone.hit(&down, interval(), &mut rec));
+        assert!((rec.t - 4.0).abs() < 1e-6);
+
+        // Straight down the axis onto the apex
+        let apex = Ray::new(Point3::new(0.0, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        assert!(cone.hit(&apex, interval(), &mut rec));
+        assert!((rec.t - 3.0).abs() < 1e-5);
+        assert_near(rec.normal, Vec3::new(0.0, 1.0, 0.0), 1e-6);
+
+        // Parallel to the far side's slant, the ray crosses the cone once
+        // on the near side
+        let slant = Ray::new(Point3::new(-1.0, 3.0, 0.0), Vec3::new(1.0, -2.0, 0.0));
+        assert!(cone.hit(&slant, interval(), &mut rec));
+        assert!((rec.t - 0.75).abs() < 1e-6);
+        assert_near(rec.p, Point3::new(-0.25, 1.5, 0.0), 1e-6);
+    }
+
+    #[test]
+    fn test_frustum_caps() {
+        let down = |x: f32| Ray::new(Point3::new(x, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let mut rec = HitRecord::default();
+        let frustum = upright_cone(0.5, true);
+        assert!(frustum.hit(&down(0.25), interval(), &mut rec));
+        assert_eq!(rec.t, 3.0);
+        assert_eq!(rec.normal, Vec3::new(0.0, 1.0, 0.0));
+        // Outside the top cap, the ray lands on the sloped side
+        assert!(frustum.hit(&down(0.75), interval(), &mut rec));
+        assert!((rec.t - 4.0).abs() < 1e-6);
+
+        // Through the open top onto the inside of the base cap
+        let open = upright_cone(0.5, false);
+        assert!(open.hit(&down(0.25), interval(), &mut rec));
+        assert_eq!(rec.t, 5.0);
+        assert!(!rec.front_face);
+        assert_eq!(rec.normal, Vec3::new(0.0, 1.0, 0.0));
+        assert!((rec.u - 0.375).abs() < 1e-6 && (rec.v - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_disk() {
+        let disk = SceneObject::Disk {
+            center: Point3::new(0.0, 0.0, 0.0),
+            normal: Vec3::new(0.0, 0.0, 2.0),
+            radius: 1.0,
+            material: Arc::new(Material::default()),
+        };
+        let mut rec = HitRecord::default();
+        assert!(disk.hit(&down_z(0.5, 0.5), interval(), &mut rec));
+        assert_eq!(rec.t, 5.0);
+        assert!(rec.front_face);
+        assert_eq!(rec.normal, Vec3::new(0.0, 0.0, 1.0));
+        assert!((0.0..=1.0).contains(&rec.u) && (0.0..=1.0).contains(&rec.v));
+        assert!(disk.hit(&down_z(0.0, 0.0), interval(), &mut rec));
+        assert!((rec.u - 0.5).abs() < 1e-6 && (rec.v - 0.5).abs() < 1e-6);
+
+        // Inside the bounding square but outside the circle
+        assert!(!disk.hit(&down_z(0.75, 0.75), interval(), &mut rec));
+
+        let from_below = Ray::new(Point3::new(0.0, 0.5, -1.0), Vec3::new(0.0, 0.0, 1.0));
+        assert!(disk.hit(&from_below, interval(), &mut rec));
+        assert!(!rec.front_face);
+        assert_eq!(rec.normal, Vec3::new(0.0, 0.0, -1.0));
+
+        let edge_on = Ray::new(Point3::new(-2.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        assert!(!disk.hit(&edge_on, interval(), &mut rec));
+    }
+
+    // Ring of radius 1 in the xz plane with a tube of radius 0.25
+    fn flat_torus() -> SceneObject {
+        SceneObject::Torus {
+            center: Point3::new(0.0, 0.0, 0.0),
+            axis: Vec3::new(0.0, 1.0, 0.0),
+            major_radius: 1.0,
+            minor_radius: 0.25,
+            material: Arc::new(Material::default()),
+        }
+    }
+
+    #[test]
+    fn test_torus_hits() {
+        let torus = flat_torus();
+        let mut rec = HitRecord::default();
+        // Outer equator, then through the hole along the axis
+        assert!(torus.hit(&down_z(0.0, 0.0), interval(), &mut rec));
+        assert!((rec.t - 3.75).abs() < 1e-5);
+        assert!(rec.front_face);
+        assert_near(rec.normal, Vec3::new(0.0, 0.0, 1.0), 1e-5);
+        assert!((rec.v - 0.5).abs() < 1e-5);
+        let axis = Ray::new(Point3::new(0.0, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        assert!(!torus.hit(&axis, interval(), &mut rec));
+
+        // Oblique entry into the tube
+        assert!(torus.hit(&down_z(1.0, 0.0), interval(), &mut rec));
+        assert!((rec.t - 4.25).abs() < 1e-5);
+        assert_near(rec.normal, Vec3::new(0.8, 0.0, 0.6), 1e-5);
+
+        // From inside the tube the wall is hit from behind
+        let inside = Ray::new(Point3::new(1.0, 0.0, 0.0), Vec3::new(2.0, 0.0, 0.0));
+        assert!(torus.hit(&inside, interval(), &mut rec));
+        assert!((rec.t - 0.125).abs() < 1e-5);
+        assert!(!rec.front_face);
+        assert_near(rec.normal, Vec3::new(-1.0, 0.0, 0.0), 1e-5);
+    }
+
+    #[test]
+    fn test_torus_is_robust() {
+        let torus = flat_torus();
+        let mut rec = HitRecord::default();
+        // Grazing the top of the tube, where two roots nearly coincide
+        let down = |x: f32| Ray::new(Point3::new(x, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        assert!(torus.hit(&down(1.2499), interval(), &mut rec));
+        assert!((rec.t - 5.0).abs() < 0.01);
+        assert!(!torus.hit(&down(1.2501), interval(), &mut rec));
+
+        // From far away the hit point keeps its precision
+        let far = Ray::new(Point3::new(1.0, 0.0, 1e4), Vec3::new(0.0, 0.0, -1.0));
+        assert!(torus.hit(&far, interval(), &mut rec));
+        assert_near(rec.p, Point3::new(1.0, 0.0, 0.75), 1e-3);
+        assert_near(rec.normal, Vec3::new(0.8, 0.0, 0.6), 1e-3);
+
+        // A tilted torus agrees with the upright one turned the same way
+        let tilted = SceneObject::Torus {
+            center: Point3::new(0.0, 0.0, 0.0),
+            axis: Vec3::new(1.0, 0.0, 0.0),
+            major_radius: 1.0,
+            minor_radius: 0.25,
+            material: Arc::new(Material::default()),
+        };
+        let across = Ray::new(Point3::new(5.0, 1.0, 0.0), Vec3::new(-1.0, 0.0, 0.0));
+        assert!(tilted.hit(&across, interval(), &mut rec));
+        assert!((rec.t - 4.75).abs() < 1e-5);
+        assert_near(rec.normal, Vec3::new(1.0, 0.0, 0.0), 1e-5);
+    }
+
+    #[test]
+    fn test_capsule() {
+        let capsule = SceneObject::Capsule {
+            radius: 0.5,
+            base: Point3::new(0.0, -1.0, 0.0),
+            top: Point3::new(0.0, 1.0, 0.0),
+            material: Arc::new(Material::default()),
+        };
+        let mut rec = HitRecord::default();
+        // Side wall
+        assert!(capsule.hit(&down_z(0.0, 0.0), interval(), &mut rec));
+        assert!((rec.t - 4.5).abs() < 1e-6);
+        assert_near(rec.normal, Vec3::new(0.0, 0.0, 1.0), 1e-6);
+        assert!((rec.v - 0.5).abs() < 1e-6);
+
+        // Top hemisphere, off its pole and at it
+        assert!(capsule.hit(&down_z(0.0, 1.3), interval(), &mut rec));
+        assert!((rec.t - 4.6).abs() < 1e-5);
+        assert_near(rec.normal, Vec3::new(0.0, 0.6, 0.8), 1e-5);
+        assert!(!capsule.hit(&down_z(0.0, 1.5001), interval(), &mut rec));
+        let down = Ray::new(Point3::new(0.0, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        assert!(capsule.hit(&down, interval(), &mut rec));
+        assert!((rec.t - 3.5).abs() < 1e-6);
+        assert!((rec.v - 1.0).abs() < 1e-6);
+
+        // From the center out through the bottom pole
+        let inside = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        assert!(capsule.hit(&inside, interval(), &mut rec));
+        assert!((rec.t - 1.5).abs() < 1e-6);
+        assert!(!rec.front_face);
+        assert_near(rec.normal, Vec3::new(0.0, 1.0, 0.0), 1e-6);
+    }
+
+    fn unit_sphere() -> Arc<SceneObject> {
+        Arc::new(SceneObject::Sphere {
+            radius: 1.0,
+            center: Point3::new(0.0, 0.0, 0.0),
+            material: Arc::new(Material::default()),
+        })
+    }
+
+    #[test]
+    fn test_instance_moves_shared_object() {
+        let sphere = unit_sphere();
+        let moved = SceneObject::Instance {
+            object: Arc::clone(&sphere),
+            transform: Transform::scale(Vec3::new(2.0, 2.0, 2.0))
+                .then(&Transform::translate(Vec3::new(3.0, 0.0, 0.0))),
+        };
+        let mut rec = HitRecord::default();
+        assert!(moved.hit(&down_z(3.0, 0.0), interval(), &mut rec));
+        assert!((rec.t - 3.0).abs() < 1e-6);
+        assert_near(rec.p, Point3::new(3.0, 0.0, 2.0), 1e-6);
+        assert_near(rec.normal, Vec3::new(0.0, 0.0, 1.0), 1e-6);
+        assert!(rec.front_face);
+        // The original stays where it was
+        assert!(!moved.hit(&down_z(0.0, 0.0), interval(), &mut rec));
+        assert!(sphere.hit(&down_z(0.0, 0.0), interval(), &mut rec));
+
+        let bbox = moved.bounding_box();
+        assert_eq!((bbox.x.min, bbox.x.max), (1.0, 5.0));
+        assert_eq!((bbox.z.min, bbox.z.max), (-2.0, 2.0));
+    }
+
+    #[test]
+    fn test_instance_normals_use_inverse_transpose() {
+        // Ellipsoid x^2 / 4 + y^2 + z^2 = 1, whose gradient at
+        // (sqrt(2), 0, sqrt(1/2)) points along (1, 0, 2)
+        let ellipsoid = SceneObject::Instance {
+            object: unit_sphere(),
+            transform: Transform::scale(Vec3::new(2.0, 1.0, 1.0)),
+        };
+        let mut rec = HitRecord::default();
+        assert!(ellipsoid.hit(&down_z(2.0f32.sqrt(), 0.0), interval(), &mut rec));
+        assert_near(rec.p, Point3::new(2.0f32.sqrt(), 0.0, 0.5f32.sqrt()), 1e-5);
+        assert_near(rec.normal, Vec3::unit(Vec3::new(1.0, 0.0, 2.0)), 1e-5);
+    }
+
+    #[test]
+    fn test_rotated_instance() {
+        // The unit quad turned a quarter around +y faces +x
+        let turned = SceneObject::Instance {
+            object: Arc::new(unit_quad()),
+            transform: Transform::rotate(Vec3::new(0.0, 1.0, 0.0), 90.0),
+        };
+        let ray = Ray::new(Point3::new(5.0, 0.5, -0.5), Vec3::new(-1.0, 0.0, 0.0));
+        let mut rec = HitRecord::default();
+        assert!(turned.hit(&ray, interval(), &mut rec));
+        assert!((rec.t - 5.0).abs() < 1e-5);
+        assert!(rec.front_face);
+        assert_near(rec.normal, Vec3::new(1.0, 0.0, 0.0), 1e-6);
+        assert!((rec.u - 0.5).abs() < 1e-5 && (rec.v - 0.5).abs() < 1e-5);
+        assert!(!turned.hit(&down_z(0.5, 0.5), interval(), &mut rec));
+
+        let bbox = turned.bounding_box();
+        assert!(bbox.z.min < -0.999 && bbox.z.max > -1e-4 && bbox.z.max < 1e-4);
+
+        let plane = SceneObject::Instance {
+            object: Arc::new(ground_plane()),
+            transform: Transform::rotate(Vec3::new(1.0, 0.0, 0.0), 30.0),
+        };
+        assert!(plane.is_unbounded());
+    }
+
+    #[test]
+    fn test_moving_sphere() {
+        let sphere = SceneObject::MovingSphere {
+            radius: 1.0,
+            center: Point3::new(0.0, 0.0, 0.0),
+            end_center: Point3::new(4.0, 0.0, 0.0),
+            material: Arc::new(Material::default()),
+        };
+        let mut rec = HitRecord::default();
+        assert!(sphere.hit(&down_z(0.0, 0.0), interval(), &mut rec));
+        assert!(!sphere.hit(&down_z(0.0, 0.0).with_time(0.5), interval(), &mut rec));
+        assert!(sphere.hit(&down_z(2.0, 0.0).with_time(0.5), interval(), &mut rec));
+        assert!((rec.t - 4.0).abs() < 1e-6);
+        assert_near(rec.normal, Vec3::new(0.0, 0.0, 1.0), 1e-6);
+        // Resting at the end after time 1
+        assert!(sphere.hit(&down_z(4.0, 0.0).with_time(3.0), interval(), &mut rec));
+
+        let bbox = sphere.bounding_box();
+        assert_eq!((bbox.x.min, bbox.x.max), (-1.0, 5.0));
+        assert_eq!((bbox.y.min, bbox.y.max), (-1.0, 1.0));
+    }
+
+    #[test]
+    fn test_moving_instance() {
+        // The unit quad sliding 4 along x while turning a third around z
+        let keyframe = |time, degrees, x| Keyframe {
+            time,
+            scale: Vec3::new(1.0, 1.0, 1.0),
+            rotation: Quaternion::from_axis_angle(Vec3::new(0.0, 0.0, 1.0), degrees),
+            translate: Vec3::new(x, 0.0, 0.0),
+        };
+        let motion =
+            AnimatedTransform::new(vec![keyframe(0.0, 0.0, 0.0), keyframe(1.0, 120.0, 4.0)]);
+        let moving = SceneObject::MovingInstance {
+            object: Arc::new(unit_quad()),
+            motion: motion.clone(),
+        };
+        let mut rec = HitRecord::default();
+        assert!(moving.hit(&down_z(0.5, 0.5), interval(), &mut rec));
+        assert!(!moving.hit(&down_z(0.5, 0.5).with_time(1.0), interval(), &mut rec));
+        // The quad's center turned by 60 and then 120 degrees
+        let (s, c) = (PI / 3.0).sin_cos();
+        let half = down_z(2.0 + 0.5 * (c - s), 0.5 * (s + c)).with_time(0.5);
+        assert!(moving.hit(&half, interval(), &mut rec));
+        assert!((rec.u - 0.5).abs() < 1e-4 && (rec.v - 0.5).abs() < 1e-4);
+        let end = down_z(4.0 - 0.5 * (c + s), 0.5 * (s - c)).with_time(1.0);
+        assert!(moving.hit(&end, interval(), &mut rec));
+        assert!((rec.u - 0.5).abs() < 1e-4 && (rec.v - 0.5).abs() < 1e-4);
+
+        // The box covers every corner at every time, including the
+        // diagonal swinging out beyond the keyframe positions
+        let bbox = moving.bounding_box();
+        for i in 0..=1000 {
+            let transform = motion.at(i as f32 / 1000.0);
+            for corner in box_corners(&unit_quad().bounding_box()) {
+                assert!(bbox.contains(transform.point(corner)));
+            }
+        }
+        assert!(bbox.y.max > 2.0f32.sqrt() - 1e-3 && bbox.y.max < 1.5);
+    }
+
+    #[test]
+    fn test_constant_medium_span() {
+        let fog = SceneObject::ConstantMedium {
+            boundary: unit_sphere(),
+            density: 1.0,
+            phase: Arc::new(Material::Isotropic {
+                albedo: Color3::new(1.0, 1.0, 1.0).into(),
+            }),
+        };
+        // Media have no surface to hit
+        let mut rec = HitRecord::default();
+        assert!(!fog.hit(&down_z(0.0, 0.0), interval(), &mut rec));
+
+        let span = fog.medium_span(&down_z(0.0, 0.0), interval()).unwrap();
+        assert!((span.min - 4.0).abs() < 1e-5 && (span.max - 6.0).abs() < 1e-5);
+        // Rays starting inside see only the medium ahead of them
+        let inside = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0));
+        let span = fog.medium_span(&inside, interval()).unwrap();
+        assert!(span.min == 0.001 && (span.max - 1.0).abs() < 1e-5);
+        // Cut short by whatever the ray hits first
+        let span = fog.medium_span(&down_z(0.0, 0.0), Interval::new(0.001, 5.0));
+        assert!((span.unwrap().max - 5.0).abs() < 1e-5);
+        assert!(fog
+            .medium_span(&down_z(0.0, 0.0), Interval::new(0.001, 3.0))
+            .is_none());
+        assert!(fog.medium_span(&down_z(1.5, 0.0), interval()).is_none());
+        assert!(unit_sphere()
+            .medium_span(&down_z(0.0, 0.0), interval())
+            .is_none());
+        assert_eq!(fog.bounding_box().x.max, 1.0);
+    }
+
+    #[test]
+    fn test_quad_front_hit_and_uv() {
+        let mut rec = HitRecord::default();
+        assert!(unit_quad().hit(&down_z(0.25, 0.75), interval(), &mut rec));
+        assert_eq!(rec.t, 5.0);
+        assert!(rec.front_face);
+        assert_eq!(rec.normal, Vec3::new(0.0, 0.0, 1.0));
+        assert_eq!(rec.u, 0.25);
+        assert_eq!(rec.v, 0.75);
+    }
+
+    #[test]
+    fn test_quad_back_face() {
+        let ray = Ray::new(Point3::new(0.5, 0.5, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        let mut rec = HitRecord::default();
+        assert!(unit_quad().hit(&ray, interval(), &mut rec));
+        assert!(!rec.front_face);
+        assert_eq!(rec.normal, Vec3::new(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn test_quad_edge_on() {
+        let ray = Ray::new(Point3::new(-1.0, 0.5, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        let mut rec = HitRecord::default();
+        assert!(!unit_quad().hit(&ray, interval(), &mut rec));
+    }
+
+    #[test]
+    fn test_quad_near_miss() {
+        let mut rec = HitRecord::default();
+        assert!(!unit_quad().hit(&down_z(1.0001, 0.5), interval(), &mut rec));
+        assert!(!unit_quad().hit(&down_z(0.5, -0.0001), interval(), &mut rec));
+        assert!(unit_quad().hit(&down_z(0.9999, 0.0001), interval(), &mut rec));
+    }
+
+    #[test]
+    fn test_triangle_front_hit_and_barycentrics() {
+        let mut rec = HitRecord::default();
+        assert!(unit_triangle().hit(&down_z(0.2, 0.3), interval(), &mut rec));
+        assert_eq!(rec.t, 5.0);
+        assert!(rec.front_face);
+        assert_eq!(rec.normal, Vec3::new(0.0, 0.0, 1.0));
+        assert!((rec.u - 0.2).abs() < 1e-6);
+        assert!((rec.v - 0.3).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_triangle_back_face() {
+        let ray = Ray::new(Point3::new(0.2, 0.2, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        let mut rec = HitRecord::default();
+        assert!(unit_triangle().hit(&ray, interval(), &mut rec));
+        assert!(!rec.front_face);
+        assert_eq!(rec.normal, Vec3::new(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn test_triangle_edge_on() {
+        let ray = Ray::new(Point3::new(-1.0, 0.2, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        let mut rec = HitRecord::default();
+        assert!(!unit_triangle().hit(&ray, interval(), &mut rec));
+    }
+
+    #[test]
+    fn test_triangle_near_miss() {
+        let mut rec = HitRecord::default();
+        // Just beyond the hypotenuse
+        assert!(!unit_triangle().hit(&down_z(0.5001, 0.5), interval(), &mut rec));
+        assert!(unit_triangle().hit(&down_z(0.4999, 0.5), interval(), &mut rec));
+        assert!(!unit_triangle().hit(&down_z(-0.0001, 0.5), interval(), &mut rec));
+    }
+
+    #[test]
+    fn test_tiny_triangle() {
+        // Edges of 5e-5, as in a finely tessellated mesh
+        let tiny = SceneObject::Triangle {
+            v0: Point3::new(0.0, 0.0, 0.0),
+            v1: Point3::new(5e-5, 0.0, 0.0),
+            v2: Point3::new(0.0, 5e-5, 0.0),
+            material: Arc::new(Material::default()),
+        };
+        let mut rec = HitRecord::default();
+        assert!(tiny.hit(&down_z(1e-5, 2e-5), interval(), &mut rec));
+        assert!((rec.t - 5.0).abs() < 1e-6);
+        assert_eq!(rec.normal, Vec3::new(0.0, 0.0, 1.0));
+        assert!(!tiny.hit(&down_z(4e-5, 2e-5), interval(), &mut rec));
+    }
+
+    #[test]
+    fn test_plane_hits() {
+        let ray = Ray::new(Point3::new(3.0, 2.0, -7.0), Vec3::new(0.0, -1.0, 0.0));
+        let mut rec = HitRecord::default();
+        assert!(ground_plane().hit(&ray, interval(), &mut rec));
+        assert_eq!(rec.t, 2.0);
+        assert!(rec.front_face);
+
+        let from_below = Ray::new(Point3::new(0.0, -2.0, 0.0), Vec3::new(0.3, 1.0, 0.0));
+        assert!(ground_plane().hit(&from_below, interval(), &mut rec));
+        assert!(!rec.front_face);
+        assert_eq!(rec.normal, Vec3::new(0.0, -1.0, 0.0));
+    }
+
+    #[test]
+    fn test_plane_edge_on_and_behind() {
+        let mut rec = HitRecord::default();
+        let parallel = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        assert!(!ground_plane().hit(&parallel, interval(), &mut rec));
+        let away = Ray::new(Point3::new(0.0, 1.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        assert!(!ground_plane().hit(&away, interval(), &mut rec));
+    }
+
+    #[test]
+    fn test_plane_with_short_normal() {
+        // The parallel test scales with the normal, which is not normalized
+        let plane = SceneObject::Plane {
+            point: Point3::new(0.0, 0.0, 0.0),
+            normal: Vec3::new(0.0, 1e-9, 0.0),
+            material: Arc::new(Material::default()),
+        };
+        let ray = Ray::new(Point3::new(0.0, 1.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let mut rec = HitRecord::default();
+        assert!(plane.hit(&ray, interval(), &mut rec));
+        assert!((rec.t - 1.0).abs() < 1e-6);
+        assert_eq!(rec.normal, Vec3::new(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn test_quad_pdf_matches_solid_angle() {
+        // Small quad far away: pdf is about distance^2 / area
+        let quad = SceneObject::Quad {
+            q: Point3::new(-0.05, -0.05, -10.0),
+            u: Vec3::new(0.1, 0.0, 0.0),
+            v: Vec3::new(0.0, 0.1, 0.0),
+            material: Arc::new(Material::default()),
+        };
+        let origin = Point3::new(0.0, 0.0, 0.0);
+        let direction = quad.random_direction(origin, 0.0, 0.3, 0.8).unwrap();
+        let pdf = quad.pdf_value(origin, direction, 0.0);
+        assert!((pdf - 10_000.0).abs() / 10_000.0 < 0.01, "{}", pdf);
+        assert_eq!(quad.pdf_value(origin, Vec3::new(0.0, 0.0, 1.0), 0.0), 0.0);
+        // Shapes without sampling give no direction rather than a guess
+        assert!(ground_plane()
+            .random_direction(origin, 0.0, 0.3, 0.8)
+            .is_none());
+    }
+
+    // Cell centers of an `n` by `n` grid over the unit square
+    fn unit_square_grid(n: usize) -> impl Iterator<Item = (f32, f32)> {
+        (0..n * n).map(move |k| {
+            let cell = |i: usize| (i as f32 + 0.5) / n as f32;
+            (cell(k / n), cell(k % n))
+        })
+    }
+
+    #[test]
+    fn test_instance_pdf_integrates_to_one() {
+        // An ellipsoid ahead, stretched three times along x. Every direction
+        // towards it crosses the z = -5 plane inside the grid, where a patch
+        // of area dA spans a solid angle of 5 dA / r^3.
+        let ellipsoid = SceneObject::Instance {
+            object: unit_sphere(),
+            transform: Transform::scale(Vec3::new(3.0, 1.0, 1.0))
+                .then(&Transform::translate(Vec3::new(0.0, 0.0, -5.0))),
+        };
+        assert!(ellipsoid.supports_sampling());
+        let origin = Point3::new(0.0, 0.0, 0.0);
+        let step = 0.01;
+        let mut total = 0.0;
+        for i in 0..700 {
+            for j in 0..300 {
+                let x = -3.5 + (i as f32 + 0.5) * step;
+                let y = -1.5 + (j as f32 + 0.5) * step;
+                let direction = Vec3::new(x, y, -5.0);
+                let solid_angle = 5.0 * step * step / direction.length().powi(3);
+                total += (ellipsoid.pdf_value(origin, direction, 0.0) * solid_angle) as f64;
+            }
+        }
+        assert!((total - 1.0).abs() < 0.01, "{}", total);
+
+        let mut rec = HitRecord::default();
+        for (u1, u2) in unit_square_grid(10) {
+            let direction = ellipsoid.random_direction(origin, 0.0, u1, u2).unwrap();
+            assert!(ellipsoid.hit(&Ray::new(origin, direction), interval(), &mut rec));
+        }
+    }
+
+    #[test]
+    fn test_moving_instance_samples_at_time() {
+        // A unit sphere sliding from the origin to x = 4, seen from above
+        // its end position
+        let keyframe = |time, x| Keyframe {
+            time,
+            scale: Vec3::new(1.0, 1.0, 1.0),
+            rotation: Quaternion::IDENTITY,
+            translate: Vec3::new(x, 0.0, 0.0),
+        };
+        let motion = AnimatedTransform::new(vec![keyframe(0.0, 0.0), keyframe(1.0, 4.0)]);
+        let moving = SceneObject::MovingInstance {
+            object: unit_sphere(),
+            motion: motion.clone(),
+        };
+        let at_end = SceneObject::Instance {
+            object: unit_sphere(),
+            transform: motion.at(1.0),
+        };
+        let origin = Point3::new(4.0, 0.0, 5.0);
+        let mut rec = HitRecord::default();
+        for (u1, u2) in unit_square_grid(10) {
+            let direction = moving.random_direction(origin, 1.0, u1, u2).unwrap();
+            assert!(at_end.hit(&Ray::new(origin, direction), interval(), &mut rec));
+            let pdf = moving.pdf_value(origin, direction, 1.0);
+            assert!((pdf - at_end.pdf_value(origin, direction, 1.0)).abs() < 1e-3 * pdf);
+            assert_eq!(moving.pdf_value(origin, direction, 0.0), 0.0);
         }
     }
 }
@@ -1,27 +1,200 @@
-use crate::{ray::ray::Ray, utils::interval::Interval, world::scene_object::SceneObject};
+use crate::{
+    ray::ray::Ray,
+    sampler::sampler::Sampler,
+    utils::interval::Interval,
+    vec3::vec3::{Point3, Vec3},
+    world::{
+        aabb::Aabb,
+        bvh::Bvh,
+        scene_object::{free_flight_distance, SceneObject},
+    },
+};
 
 use super::hit_record::HitRecord;
 
+#[derive(Default)]
 pub struct SceneObjectList {
     pub objects: Vec<SceneObject>,
+    // Indices of the emissive objects that can be sampled directly
+    lights: Vec<usize>,
+    // Indices of the constant media, which rays scatter in rather than hit
+    media: Vec<usize>,
+    bvh: Option<ObjectBvh>,
+}
+
+struct ObjectBvh {
+    bvh: Bvh,
+    // Maps BVH primitive indices back to `objects`
+    bounded: Vec<usize>,
+    // Objects without finite bounds, tested against every ray
+    unbounded: Vec<usize>,
 }
 
 impl SceneObjectList {
     pub fn new() -> Self {
         SceneObjectList {
             objects: Vec::new(),
+            lights: Vec::new(),
+            media: Vec::new(),
+            bvh: None,
         }
     }
 
     pub fn add(&mut self, object: SceneObject) {
+        if object.material().is_emissive() && object.supports_sampling() {
+            self.lights.push(self.objects.len());
+        }
+        if object.medium().is_some() {
+            self.media.push(self.objects.len());
+        }
         self.objects.push(object);
+        self.bvh = None;
     }
 
     pub fn clear(&mut self) {
         self.objects.clear();
+        self.lights.clear();
+        self.media.clear();
+        self.bvh = None;
+    }
+
+    /// Builds the acceleration structure used by `hit`. Adding or clearing
+    /// objects afterwards drops it again, falling back to the linear scan.
+    pub fn build_bvh(&mut self) {
+        // Media have no surface to hit, so they stay out of the tree
+        let mut is_medium = vec![false; self.objects.len()];
+        for &i in &self.media {
+            is_medium[i] = true;
+        }
+        let (unbounded, bounded): (Vec<usize>, Vec<usize>) = (0..self.objects.len())
+            .filter(|&i| !is_medium[i])
+            .partition(|&i| self.objects[i].is_unbounded());
+        let bounds: Vec<Aabb> = bounded
+            .iter()
+            .map(|&i| self.objects[i].bounding_box())
+            .collect();
+        self.bvh = Some(ObjectBvh {
+            bvh: Bvh::new(&bounds),
+            bounded,
+            unbounded,
+        });
+    }
+
+    pub fn bounding_box(&self) -> Aabb {
+        match &self.bvh {
+            Some(tree) if tree.unbounded.is_empty() => tree.bvh.bounding_box(),
+            _ => self.objects.iter().fold(Aabb::EMPTY, |acc, o| {
+                Aabb::surrounding(&acc, &o.bounding_box())
+            }),
+        }
+    }
+
+    pub fn has_lights(&self) -> bool {
+        !self.lights.is_empty()
+    }
+
+    /// Direction from `origin` towards a uniformly chosen light as it is at
+    /// `time`, with the light and the point on it drawn from `sampler`.
+    /// `None` if there are no lights.
+    pub fn random_light_direction(
+        &self,
+        origin: Point3,
+        time: f32,
+        sampler: &mut Sampler,
+    ) -> Option<Vec3> {
+        if self.lights.is_empty() {
+            return None;
+        }
+        let i = ((sampler.get_1d() * self.lights.len() as f32) as usize).min(self.lights.len() - 1);
+        let (u1, u2) = sampler.get_2d();
+        self.objects[self.lights[i]].random_direction(origin, time, u1, u2)
+    }
+
+    /// Density of `random_light_direction(origin, time)` producing
+    /// `direction`, averaged over all lights since any of them may have been
+    /// chosen.
+    pub fn light_pdf(&self, origin: Point3, direction: Vec3, time: f32) -> f32 {
+        if self.lights.is_empty() {
+            return 0.0;
+        }
+        let sum: f32 = self
+            .lights
+            .iter()
+            .map(|&i| self.objects[i].pdf_value(origin, direction, time))
+            .sum();
+        sum / self.lights.len() as f32
+    }
+
+    /// Where `ray` first scatters in the constant media over `ray_t`, with
+    /// an independent free-flight distance drawn from `sampler` for each
+    /// medium it passes through. Fills `rec` with the scattering point and
+    /// returns whether there is one.
+    pub fn sample_media(
+        &self,
+        ray: &Ray,
+        ray_t: Interval,
+        sampler: &mut Sampler,
+        rec: &mut HitRecord,
+    ) -> bool {
+        let ray_length = ray.direction().length();
+        let mut scattered = false;
+        let mut closest = ray_t;
+        for &i in &self.media {
+            let object = &self.objects[i];
+            let Some((density, phase)) = object.medium() else {
+                continue;
+            };
+            let Some(span) = object.medium_span(ray, closest) else {
+                continue;
+            };
+            let t = span.min + free_flight_distance(density, sampler.get_1d()) / ray_length;
+            if t < span.max {
+                rec.set_medium_point(ray, t, phase);
+                closest.max = t;
+                scattered = true;
+            }
+        }
+        scattered
+    }
+
+    /// Fraction of the light travelling along `ray` over `ray_t` that the
+    /// constant media let through unscattered.
+    pub fn media_transmittance(&self, ray: &Ray, ray_t: Interval) -> f32 {
+        let ray_length = ray.direction().length();
+        self.media
+            .iter()
+            .filter_map(|&i| {
+                let (density, _) = self.objects[i].medium()?;
+                let span = self.objects[i].medium_span(ray, ray_t)?;
+                Some((-density * (span.max - span.min) * ray_length).exp())
+            })
+            .product()
+    }
+
+    pub fn bvh(&self) -> Option<&Bvh> {
+        self.bvh.as_ref().map(|tree| &tree.bvh)
     }
 
     pub fn hit(&self, ray: &Ray, ray_t: Interval, hit_rec: &mut HitRecord) -> bool {
+        let Some(tree) = &self.bvh else {
+            return self.hit_linear(ray, ray_t, hit_rec);
+        };
+
+        let mut hit_anything = false;
+        let mut closest = ray_t;
+        for &i in &tree.unbounded {
+            if self.objects[i].hit(ray, closest, hit_rec) {
+                hit_anything = true;
+                closest.max = hit_rec.t;
+            }
+        }
+
+        tree.bvh.hit(ray, closest, hit_rec, |i, r, t, rec| {
+            self.objects[tree.bounded[i]].hit(r, t, rec)
+        }) || hit_anything
+    }
+
+    pub fn hit_linear(&self, ray: &Ray, ray_t: Interval, hit_rec: &mut HitRecord) -> bool {
         let mut hit_anything = false;
         let mut temp_interval = ray_t;
 
@@ -36,3 +209,186 @@ impl SceneObjectList {
         hit_anything
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        sampler::sampler::{Sampler, SamplerKind},
+        scene::random_spheres::{random_spheres_camera, random_spheres_scene},
+        utils::{constants::INFINITY, helpers::random_f32},
+    };
+
+    #[test]
+    fn test_bvh_matches_linear() {
+        let mut world = random_spheres_scene(&mut rand::thread_rng());
+        world.build_bvh();
+        let cam = random_spheres_camera();
+        let mut sampler = Sampler::new(SamplerKind::Independent, 1);
+
+        for _ in 0..20_000 {
+            let x = (random_f32() * cam.image_width as f32) as u32;
+            let y = (random_f32() * cam.image_height as f32) as u32;
+            let ray = cam.get_ray(x, y, &mut sampler);
+            let interval = Interval::new(0.001, INFINITY);
+
+            let mut linear_rec = HitRecord::default();
+            let mut bvh_rec = HitRecord::default();
+            let linear_hit = world.hit_linear(&ray, interval, &mut linear_rec);
+            let bvh_hit = world.hit(&ray, interval, &mut bvh_rec);
+
+            assert_eq!(linear_hit, bvh_hit);
+            if linear_hit {
+                assert_eq!(linear_rec.t, bvh_rec.t);
+                assert_eq!(linear_rec.p, bvh_rec.p);
+                assert_eq!(linear_rec.normal, bvh_rec.normal);
+            }
+        }
+    }
+
+    #[test]
+    fn test_bounding_box_covers_objects() {
+        let mut world = random_spheres_scene(&mut rand::thread_rng());
+        let linear_box = world.bounding_box();
+        world.build_bvh();
+        let bvh_box = world.bounding_box();
+        assert_eq!(linear_box.x.min, bvh_box.x.min);
+        assert_eq!(linear_box.y.max, bvh_box.y.max);
+        // The ground sphere dominates the scene bounds.
+        assert_eq!(bvh_box.y.min, -2000.0);
+    }
+
+    #[test]
+    fn test_bvh_with_unbounded_objects() {
+        use crate::world::material::Material;
+        use std::sync::Arc;
+
+        let mut world = random_spheres_scene(&mut rand::thread_rng());
+        world.add(SceneObject::Plane {
+            point: Point3::new(0.0, 0.1, 0.0),
+            normal: Vec3::new(0.0, 1.0, 0.0),
+            material: Arc::new(Material::default()),
+        });
+        world.build_bvh();
+        let cam = random_spheres_camera();
+        let mut sampler = Sampler::new(SamplerKind::Independent, 1);
+
+        for _ in 0..5_000 {
+            let x = (random_f32() * cam.image_width as f32) as u32;
+            let y = (random_f32() * cam.image_height as f32) as u32;
+            let ray = cam.get_ray(x, y, &mut sampler);
+            let interval = Interval::new(0.001, INFINITY);
+
+            let mut linear_rec = HitRecord::default();
+            let mut bvh_rec = HitRecord::default();
+            assert_eq!(
+                world.hit_linear(&ray, interval, &mut linear_rec),
+                world.hit(&ray, interval, &mut bvh_rec)
+            );
+            assert_eq!(linear_rec.t, bvh_rec.t);
+        }
+    }
+
+    #[test]
+    fn test_media_scatter_and_transmittance() {
+        use crate::{utils::helpers::seed_random, vec3::color3::Color3, world::material::Material};
+        use std::sync::Arc;
+
+        let mut world = SceneObjectList::new();
+        world.add(SceneObject::ConstantMedium {
+            boundary: Arc::new(SceneObject::Sphere {
+                radius: 1.0,
+                center: Point3::new(0.0, 0.0, 0.0),
+                material: Arc::new(Material::default()),
+            }),
+            density: 1.0,
+            phase: Arc::new(Material::Isotropic {
+                albedo: Color3::new(1.0, 1.0, 1.0).into(),
+            }),
+        });
+        world.build_bvh();
+        let ray = Ray::new(Point3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        let interval = Interval::new(0.001, INFINITY);
+        let mut rec = HitRecord::default();
+        assert!(!world.hit(&ray, interval, &mut rec));
+
+        // Two units of fog through the middle leave e^-2 of the rays
+        seed_random(7);
+        let mut sampler = Sampler::new(SamplerKind::Independent, 1);
+        let trials = 20_000;
+        let mut scattered = 0;
+        for _ in 0..trials {
+            if world.sample_media(&ray, interval, &mut sampler, &mut rec) {
+                scattered += 1;
+                assert!(rec.p.z >= -1.0 && rec.p.z <= 1.0);
+                assert_eq!(rec.normal, Vec3::new(0.0, 0.0, -1.0));
+                assert!(rec.material.is_phase_function());
+            }
+        }
+        let expected = (-2.0f32).exp();
+        assert!((1.0 - scattered as f32 / trials as f32 - expected).abs() < 0.01);
+        assert!((world.media_transmittance(&ray, interval) - expected).abs() < 1e-5);
+
+        // Stopping halfway through leaves one unit of fog
+        let halfway = Interval::new(0.001, 5.0);
+        assert!((world.media_transmittance(&ray, halfway) - (-1.0f32).exp()).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_instanced_media() {
+        use crate::{
+            vec3::{color3::Color3, transform::Transform},
+            world::material::Material,
+        };
+        use std::sync::Arc;
+
+        // Unit fog sphere blown up to radius 2 and moved to x = 3; the
+        // density stays per unit of world distance
+        let fog = SceneObject::ConstantMedium {
+            boundary: Arc::new(SceneObject::Sphere {
+                radius: 1.0,
+                center: Point3::new(0.0, 0.0, 0.0),
+                material: Arc::new(Material::default()),
+            }),
+            density: 1.0,
+            phase: Arc::new(Material::Isotropic {
+                albedo: Color3::new(1.0, 1.0, 1.0).into(),
+            }),
+        };
+        let mut world = SceneObjectList::new();
+        world.add(SceneObject::Instance {
+            object: Arc::new(fog),
+            transform: Transform::scale(Vec3::new(2.0, 2.0, 2.0))
+                .then(&Transform::translate(Vec3::new(3.0, 0.0, 0.0))),
+        });
+        world.build_bvh();
+        let interval = Interval::new(0.001, INFINITY);
+        let through = Ray::new(Point3::new(3.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        let mut rec = HitRecord::default();
+        assert!(!world.hit(&through, interval, &mut rec));
+        let expected = (-4.0f32).exp();
+        assert!((world.media_transmittance(&through, interval) - expected).abs() < 1e-5);
+        let beside = Ray::new(Point3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        assert_eq!(world.media_transmittance(&beside, interval), 1.0);
+
+        let mut sampler = Sampler::new(SamplerKind::Independent, 1);
+        let mut scattered = false;
+        for _ in 0..100 {
+            if world.sample_media(&through, interval, &mut sampler, &mut rec) {
+                scattered = true;
+                assert!(rec.p.z >= -2.0 && rec.p.z <= 2.0);
+                assert!(rec.material.is_phase_function());
+            }
+        }
+        assert!(scattered);
+    }
+
+    #[test]
+    fn test_add_invalidates_bvh() {
+        let mut world = random_spheres_scene(&mut rand::thread_rng());
+        world.build_bvh();
+        assert!(world.bvh().is_some());
+        world.add(world.objects[0].clone());
+        assert!(world.bvh().is_none());
+    }
+}
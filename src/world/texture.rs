@@ -0,0 +1,572 @@
+use std::{fmt, fs, io, path::Path, sync::Arc};
+
+use crate::{
+    vec3::{color3::Color3, vec3::Point3},
+    world::perlin::Perlin,
+};
+
+/// Spatially varying color, evaluated at a hit point's surface coordinates
+/// `(u, v)` or its position `p`.
+#[derive(Debug, Clone)]
+pub enum Texture {
+    Solid(Color3),
+    /// Checkerboard of cubes with edge length `scale` in world space.
+    Checker {
+        scale: f32,
+        even: Arc<Texture>,
+        odd: Arc<Texture>,
+    },
+    /// Checkerboard of `columns` x `rows` cells over the unit (u, v) square.
+    UvChecker {
+        columns: f32,
+        rows: f32,
+        even: Arc<Texture>,
+        odd: Arc<Texture>,
+    },
+    Image(Arc<ImageTexture>),
+    /// Bands along the z axis distorted by turbulence.
+    Marble {
+        noise: Arc<Perlin>,
+        scale: f32,
+        turbulence: f32,
+        ramp: Arc<ColorRamp>,
+    },
+    /// Concentric rings around the y axis, `rings` per unit of radius,
+    /// wobbled by fractal noise.
+    Wood {
+        noise: Arc<Perlin>,
+        scale: f32,
+        rings: f32,
+        turbulence: f32,
+        ramp: Arc<ColorRamp>,
+    },
+    /// Fractal noise with `octaves` layers mapped straight onto the ramp.
+    Clouds {
+        noise: Arc<Perlin>,
+        scale: f32,
+        octaves: u32,
+        ramp: Arc<ColorRamp>,
+    },
+}
+
+impl Texture {
+    #[inline]
+    pub fn value(&self, u: f32, v: f32, p: Point3) -> Color3 {
+        match self {
+            Self::Solid(color) => *color,
+            Self::Checker { scale, even, odd } => {
+                let x = (p.x / scale).floor() as i64;
+                let y = (p.y / scale).floor() as i64;
+                let z = (p.z / scale).floor() as i64;
+                if (x + y + z).rem_euclid(2) == 0 {
+                    even.value(u, v, p)
+                } else {
+                    odd.value(u, v, p)
+                }
+            }
+            Self::UvChecker {
+                columns,
+                rows,
+                even,
+                odd,
+            } => {
+                let i = (u * columns).floor() as i64;
+                let j = (v * rows).floor() as i64;
+                if (i + j).rem_euclid(2) == 0 {
+                    even.value(u, v, p)
+                } else {
+                    odd.value(u, v, p)
+                }
+            }
+            Self::Image(image) => image.sample(u, v),
+            Self::Marble {
+                noise,
+                scale,
+                turbulence,
+                ramp,
+            } => {
+                let p = *scale * p;
+                let phase = p.z + turbulence * noise.turbulence(p, 7);
+                ramp.sample(0.5 * (1.0 + phase.sin()))
+            }
+            Self::Wood {
+                noise,
+                scale,
+                rings,
+                turbulence,
+                ramp,
+            } => {
+                let p = *scale * p;
+                let radius = (p.x * p.x + p.z * p.z).sqrt();
+                let rings = radius * rings + turbulence * noise.fbm(p, 4, 2.0, 0.5);
+                ramp.sample(rings - rings.floor())
+            }
+            Self::Clouds {
+                noise,
+                scale,
+                octaves,
+                ramp,
+            } => {
+                let density = noise.fbm(*scale * p, *octaves, 2.0, 0.5);
+                ramp.sample(0.5 * (1.0 + density))
+            }
+        }
+    }
+}
+
+/// Piecewise linear map from [0, 1] to colors, used by the procedural
+/// textures.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColorRamp {
+    /// `(position, color)` pairs sorted by position.
+    stops: Vec<(f32, Color3)>,
+}
+
+impl ColorRamp {
+    /// Builds a ramp from stops in any order. Panics without any stops.
+    pub fn new(mut stops: Vec<(f32, Color3)>) -> Self {
+        assert!(!stops.is_empty(), "a color ramp needs at least one stop");
+        stops.sort_by(|a, b| a.0.total_cmp(&b.0));
+        ColorRamp { stops }
+    }
+
+    /// Spreads `colors` evenly over [0, 1].
+    pub fn uniform(colors: &[Color3]) -> Self {
+        let last = colors.len().saturating_sub(1).max(1) as f32;
+        Self::new(
+            colors
+                .iter()
+                .enumerate()
+                .map(|(i, &c)| (i as f32 / last, c))
+                .collect(),
+        )
+    }
+
+    /// Color at `t`, holding the end colors outside the stops.
+    pub fn sample(&self, t: f32) -> Color3 {
+        let upper = self.stops.partition_point(|(position, _)| *position <= t);
+        if upper == 0 {
+            return self.stops[0].1;
+        }
+        if upper == self.stops.len() {
+            return self.stops[upper - 1].1;
+        }
+        let (t0, c0) = self.stops[upper - 1];
+        let (t1, c1) = self.stops[upper];
+        let f = (t - t0) / (t1 - t0);
+        (1.0 - f) * c0 + f * c1
+    }
+}
+
+impl From<Color3> for Texture {
+    fn from(color: Color3) -> Self {
+        Texture::Solid(color)
+    }
+}
+
+impl Default for Texture {
+    fn default() -> Self {
+        Texture::Solid(Color3::new(0.0, 0.0, 0.0))
+    }
+}
+
+/// How texture coordinates outside [0, 1] map back onto the image.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum WrapMode {
+    #[default]
+    Repeat,
+    Clamp,
+    Mirror,
+}
+
+impl WrapMode {
+    #[inline]
+    fn apply(&self, i: i64, n: usize) -> usize {
+        let n = n as i64;
+        let wrapped = match self {
+            Self::Repeat => i.rem_euclid(n),
+            Self::Clamp => i.clamp(0, n - 1),
+            Self::Mirror => {
+                let m = i.rem_euclid(2 * n);
+                if m >= n {
+                    2 * n - 1 - m
+                } else {
+                    m
+                }
+            }
+        };
+        wrapped as usize
+    }
+}
+
+#[derive(Debug)]
+pub enum TextureError {
+    Io(io::Error),
+    Decode(String),
+}
+
+impl fmt::Display for TextureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "failed to read image: {}", err),
+            Self::Decode(message) => write!(f, "failed to decode image: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for TextureError {}
+
+impl From<io::Error> for TextureError {
+    fn from(err: io::Error) -> Self {
+        TextureError::Io(err)
+    }
+}
+
+/// Bilinearly filtered image in linear color. Row 0 is the top of the
+/// image, which is `v = 1`.
+#[derive(Debug, Clone)]
+pub struct ImageTexture {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<Color3>,
+    pub wrap: WrapMode,
+}
+
+impl ImageTexture {
+    pub fn new(width: usize, height: usize, pixels: Vec<Color3>, wrap: WrapMode) -> Self {
+        assert_eq!(pixels.len(), width * height, "pixel count must match size");
+        ImageTexture {
+            width,
+            height,
+            pixels,
+            wrap,
+        }
+    }
+
+    /// Loads an 8 or 16 bit PNG, or a P3/P6 PPM, converting from sRGB to
+    /// linear color.
+    pub fn load(path: impl AsRef<Path>, wrap: WrapMode) -> Result<Self, TextureError> {
+        let bytes = fs::read(path.as_ref())?;
+        let (width, height, srgb) = if bytes.starts_with(b"\x89PNG") {
+            decode_png(&bytes)?
+        } else if bytes.starts_with(b"P3") || bytes.starts_with(b"P6") {
+            decode_ppm(&bytes)?
+        } else {
+            return Err(TextureError::Decode(
+                "unrecognized format, expected PNG or PPM".to_string(),
+            ));
+        };
+
+        let pixels = srgb
+            .into_iter()
+            .map(|c| {
+                Color3::new(
+                    srgb_to_linear(c.x),
+                    srgb_to_linear(c.y),
+                    srgb_to_linear(c.z),
+                )
+            })
+            .collect();
+        Ok(ImageTexture::new(width, height, pixels, wrap))
+    }
+
+    #[inline]
+    fn texel(&self, x: i64, y: i64) -> Color3 {
+        let x = self.wrap.apply(x, self.width);
+        let y = self.wrap.apply(y, self.height);
+        self.pixels[y * self.width + x]
+    }
+
+    #[inline]
+    pub fn sample(&self, u: f32, v: f32) -> Color3 {
+        // Texel centers sit at half-integer coordinates
+        let x = u * self.width as f32 - 0.5;
+        let y = (1.0 - v) * self.height as f32 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+
+        let top = (1.0 - fx) * self.texel(x0, y0) + fx * self.texel(x0 + 1, y0);
+        let bottom = (1.0 - fx) * self.texel(x0, y0 + 1) + fx * self.texel(x0 + 1, y0 + 1);
+        (1.0 - fy) * top + fy * bottom
+    }
+}
+
+#[inline]
+fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+type DecodedImage = (usize, usize, Vec<Color3>);
+
+fn decode_png(bytes: &[u8]) -> Result<DecodedImage, TextureError> {
+    let mut decoder = png::Decoder::new(bytes);
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder
+        .read_info()
+        .map_err(|e| TextureError::Decode(e.to_string()))?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader
+        .next_frame(&mut buffer)
+        .map_err(|e| TextureError::Decode(e.to_string()))?;
+
+    let channels = info.color_type.samples();
+    let pixels = buffer[..info.buffer_size()]
+        .chunks_exact(channels)
+        .map(|px| {
+            let c = |i: usize| px[i] as f32 / 255.0;
+            match channels {
+                // Grayscale, with or without alpha
+                1 | 2 => Color3::new(c(0), c(0), c(0)),
+                _ => Color3::new(c(0), c(1), c(2)),
+            }
+        })
+        .collect();
+    Ok((info.width as usize, info.height as usize, pixels))
+}
+
+fn decode_ppm(bytes: &[u8]) -> Result<DecodedImage, TextureError> {
+    let err = |message: &str| TextureError::Decode(format!("PPM: {}", message));
+
+    // Header: magic, width, height and maxval separated by whitespace or
+    // comments, followed by a single whitespace byte in the binary variant
+    let mut pos = 0;
+    let mut header = Vec::new();
+    while header.len() < 4 {
+        while pos < bytes.len() && (bytes[pos].is_ascii_whitespace() || bytes[pos] == b'#') {
+            if bytes[pos] == b'#' {
+                while pos < bytes.len() && bytes[pos] != b'\n' {
+                    pos += 1;
+                }
+            } else {
+                pos += 1;
+            }
+        }
+        let start = pos;
+        while pos < bytes.len() && !bytes[pos].is_ascii_whitespace() {
+            pos += 1;
+        }
+        if start == pos {
+            return Err(err("truncated header"));
+        }
+        header.push(std::str::from_utf8(&bytes[start..pos]).map_err(|_| err("invalid header"))?);
+    }
+
+    let number = |s: &str| s.parse::<usize>().map_err(|_| err("invalid header number"));
+    let (width, height, maxval) = (number(header[1])?, number(header[2])?, number(header[3])?);
+    if width == 0 || height == 0 || maxval == 0 || maxval > 65535 {
+        return Err(err("invalid image size or maxval"));
+    }
+    let count = width
+        .checked_mul(height)
+        .and_then(|pixels| pixels.checked_mul(3))
+        .ok_or_else(|| err("image too large"))?;
+
+    let samples: Vec<usize> = if header[0] == "P6" {
+        let data = &bytes[(pos + 1).min(bytes.len())..];
+        let sample_size = if maxval < 256 { 1 } else { 2 };
+        if data.len() / sample_size < count {
+            return Err(err("truncated pixel data"));
+        }
+        if sample_size == 1 {
+            data[..count].iter().map(|&b| b as usize).collect()
+        } else {
+            data[..count * 2]
+                .chunks_exact(2)
+                .map(|b| ((b[0] as usize) << 8) | b[1] as usize)
+                .collect()
+        }
+    } else {
+        let text = std::str::from_utf8(&bytes[pos..]).map_err(|_| err("invalid pixel data"))?;
+        let samples = text
+            .split_whitespace()
+            .take(count)
+            .map(number)
+            .collect::<Result<Vec<_>, _>>()?;
+        if samples.len() < count {
+            return Err(err("truncated pixel data"));
+        }
+        samples
+    };
+
+    let scale = 1.0 / maxval as f32;
+    let pixels = samples
+        .chunks_exact(3)
+        .map(|c| Color3::new(c[0] as f32, c[1] as f32, c[2] as f32) * scale)
+        .collect();
+    Ok((width, height, pixels))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn black() -> Arc<Texture> {
+        Arc::new(Texture::Solid(Color3::new(0.0, 0.0, 0.0)))
+    }
+
+    fn white() -> Arc<Texture> {
+        Arc::new(Texture::Solid(Color3::new(1.0, 1.0, 1.0)))
+    }
+
+    #[test]
+    fn test_checker_alternates() {
+        let checker = Texture::Checker {
+            scale: 1.0,
+            even: white(),
+            odd: black(),
+        };
+        let origin = Point3::new(0.5, 0.5, 0.5);
+        assert_eq!(checker.value(0.0, 0.0, origin).x, 1.0);
+        assert_eq!(checker.value(0.0, 0.0, Point3::new(1.5, 0.5, 0.5)).x, 0.0);
+        assert_eq!(checker.value(0.0, 0.0, Point3::new(-0.5, 0.5, 0.5)).x, 0.0);
+    }
+
+    #[test]
+    fn test_uv_checker() {
+        let checker = Texture::UvChecker {
+            columns: 4.0,
+            rows: 2.0,
+            even: white(),
+            odd: black(),
+        };
+        let p = Point3::default();
+        assert_eq!(checker.value(0.1, 0.1, p).x, 1.0);
+        assert_eq!(checker.value(0.3, 0.1, p).x, 0.0);
+        assert_eq!(checker.value(0.3, 0.6, p).x, 1.0);
+    }
+
+    fn two_by_one(wrap: WrapMode) -> ImageTexture {
+        let pixels = vec![Color3::new(0.0, 0.0, 0.0), Color3::new(1.0, 1.0, 1.0)];
+        ImageTexture::new(2, 1, pixels, wrap)
+    }
+
+    #[test]
+    fn test_color_ramp() {
+        let ramp = ColorRamp::uniform(&[
+            Color3::new(0.0, 0.0, 0.0),
+            Color3::new(1.0, 0.0, 0.0),
+            Color3::new(1.0, 1.0, 0.0),
+        ]);
+        assert_eq!(ramp.sample(-1.0), Color3::new(0.0, 0.0, 0.0));
+        assert_eq!(ramp.sample(0.25), Color3::new(0.5, 0.0, 0.0));
+        assert_eq!(ramp.sample(0.5), Color3::new(1.0, 0.0, 0.0));
+        assert_eq!(ramp.sample(2.0), Color3::new(1.0, 1.0, 0.0));
+
+        let single = ColorRamp::uniform(&[Color3::new(0.2, 0.2, 0.2)]);
+        assert_eq!(single.sample(0.7), Color3::new(0.2, 0.2, 0.2));
+    }
+
+    #[test]
+    fn test_procedural_textures_stay_on_ramp() {
+        let noise = Arc::new(Perlin::new(42));
+        let ramp = Arc::new(ColorRamp::uniform(&[
+            Color3::new(0.0, 0.0, 0.0),
+            Color3::new(1.0, 1.0, 1.0),
+        ]));
+        let textures = [
+            Texture::Marble {
+                noise: noise.clone(),
+                scale: 4.0,
+                turbulence: 10.0,
+                ramp: ramp.clone(),
+            },
+            Texture::Wood {
+                noise: noise.clone(),
+                scale: 1.0,
+                rings: 8.0,
+                turbulence: 0.5,
+                ramp: ramp.clone(),
+            },
+            Texture::Clouds {
+                noise,
+                scale: 2.0,
+                octaves: 5,
+                ramp,
+            },
+        ];
+        for (index, texture) in textures.iter().enumerate() {
+            let mut values = Vec::new();
+            for i in 0..200 {
+                let t = i as f32 * 0.173;
+                let p = Point3::new(t.sin() * 3.0, t * 0.05, t.cos() * 3.0);
+                let c = texture.value(0.0, 0.0, p);
+                assert!((0.0..=1.0).contains(&c.x), "texture {}", index);
+                assert_eq!(c.x, c.y);
+                values.push(c.x);
+            }
+            let (min, max) = values
+                .iter()
+                .fold((1.0f32, 0.0f32), |(lo, hi), &x| (lo.min(x), hi.max(x)));
+            assert!(max - min > 0.3, "texture {} is nearly flat", index);
+        }
+    }
+
+    #[test]
+    fn test_bilinear_filtering() {
+        let image = two_by_one(WrapMode::Clamp);
+        // Texel centers
+        assert_eq!(image.sample(0.25, 0.5).x, 0.0);
+        assert_eq!(image.sample(0.75, 0.5).x, 1.0);
+        // Halfway between them
+        assert!((image.sample(0.5, 0.5).x - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_wrap_modes() {
+        assert_eq!(WrapMode::Repeat.apply(-1, 4), 3);
+        assert_eq!(WrapMode::Repeat.apply(5, 4), 1);
+        assert_eq!(WrapMode::Clamp.apply(-1, 4), 0);
+        assert_eq!(WrapMode::Clamp.apply(9, 4), 3);
+        assert_eq!(WrapMode::Mirror.apply(-1, 4), 0);
+        assert_eq!(WrapMode::Mirror.apply(4, 4), 3);
+        assert_eq!(WrapMode::Mirror.apply(8, 4), 0);
+
+        // Repeat blends the left edge with the right one
+        let image = two_by_one(WrapMode::Repeat);
+        assert!((image.sample(0.0, 0.5).x - 0.5).abs() < 1e-6);
+        let image = two_by_one(WrapMode::Clamp);
+        assert_eq!(image.sample(0.0, 0.5).x, 0.0);
+    }
+
+    #[test]
+    fn test_decode_ppm() {
+        let ascii = b"P3\n# comment\n2 1\n255\n255 0 0  0 0 255\n";
+        let (w, h, pixels) = decode_ppm(ascii).unwrap();
+        assert_eq!((w, h), (2, 1));
+        assert_eq!(pixels[0], Color3::new(1.0, 0.0, 0.0));
+
+        let mut binary = b"P6 2 1 255\n".to_vec();
+        binary.extend_from_slice(&[0, 255, 0, 0, 0, 255]);
+        let (_, _, pixels) = decode_ppm(&binary).unwrap();
+        assert_eq!(pixels[0], Color3::new(0.0, 1.0, 0.0));
+        assert_eq!(pixels[1], Color3::new(0.0, 0.0, 1.0));
+
+        assert!(decode_ppm(b"P6 2 1 255\n\x00\x00").is_err());
+
+        let huge = format!("P6 {} 2 255\n", usize::MAX / 2);
+        match decode_ppm(huge.as_bytes()) {
+            Err(TextureError::Decode(message)) => assert!(message.contains("too large")),
+            _ => panic!("expected a decode error"),
+        }
+    }
+
+    #[test]
+    fn test_decode_png() {
+        let mut bytes = Vec::new();
+        {
+            let mut encoder = png::Encoder::new(&mut bytes, 1, 2);
+            encoder.set_color(png::ColorType::Rgb);
+            encoder.set_depth(png::BitDepth::Eight);
+            let mut writer = encoder.write_header().unwrap();
+            writer.write_image_data(&[255, 0, 0, 0, 0, 255]).unwrap();
+        }
+        let (w, h, pixels) = decode_png(&bytes).unwrap();
+        assert_eq!((w, h), (1, 2));
+        assert_eq!(pixels[1], Color3::new(0.0, 0.0, 1.0));
+    }
+}